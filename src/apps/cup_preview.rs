@@ -0,0 +1,180 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use crate::apps::{
+    tracks::{CupList, Icon, TrackDefinition},
+    CupPreviewApp,
+};
+use crate::helpers::menu::{self, MenuCup, MenuCupKind, CUP_NAME_WIDTH, TRACK_NAME_WIDTH};
+use egui::{Context, Sense};
+use egui_extras::RetainedImage;
+
+/// Size of the cup buttons in the preview
+const ICON_SIZE: f32 = 64.0;
+
+impl Default for CupPreviewApp {
+    fn default() -> Self {
+        Self {
+            open: false,
+            list: CupList::Racing,
+            page: 0,
+            selected: None,
+        }
+    }
+}
+
+impl CupPreviewApp {
+    pub fn ui(&mut self, ctx: &Context, editor: &mut TrackDefinition) {
+        let mut open = self.open;
+        let mut jump: Option<usize> = None;
+        egui::Window::new("Cup Select Preview")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for v in CupList::VALUES {
+                        if ui.selectable_label(self.list == v, v.as_str()).clicked() && self.list != v {
+                            self.list = v;
+                            self.page = 0;
+                            self.selected = None;
+                        }
+                    }
+                });
+                let cups = editor.menu_cups(self.list);
+                let pages = menu::page_count(cups.len());
+                self.page = self.page.min(pages - 1);
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(self.page > 0, egui::Button::new("◀")).clicked() {
+                        self.page -= 1;
+                    }
+                    ui.label(format!("Page {} / {}", self.page + 1, pages));
+                    if ui.add_enabled(self.page + 1 < pages, egui::Button::new("▶")).clicked() {
+                        self.page += 1;
+                    }
+                });
+                ui.separator();
+                egui::Grid::new("cup_grid").spacing([8.0, 8.0]).show(ui, |ui| {
+                    for row in 0..menu::ROWS {
+                        for column in 0..menu::COLUMNS {
+                            let i = menu::cup_at(self.page, row, column);
+                            match cups.get(i) {
+                                Some(cup) => {
+                                    if view_cup(ctx, ui, cup, self.selected == Some(i)) {
+                                        self.selected = Some(i);
+                                        if let MenuCupKind::Custom(i) = cup.kind {
+                                            jump = Some(i);
+                                        }
+                                    }
+                                }
+                                None => {
+                                    ui.allocate_space([ICON_SIZE, ICON_SIZE].into());
+                                }
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+                match self.selected.and_then(|v| cups.get(v)) {
+                    Some(cup) => view_tracks(ui, cup),
+                    None => {
+                        ui.weak("Select a cup to see its tracks");
+                    }
+                }
+                view_truncated(ui, &cups);
+            });
+        self.open = open;
+
+        // Custom cups are opened in the editor
+        if let Some(n) = jump {
+            editor.select_list(self.list);
+            editor.selected = n;
+        }
+    }
+}
+
+/// Icon and name of `cup`, which returns whether it's clicked
+fn view_cup(ctx: &Context, ui: &mut egui::Ui, cup: &MenuCup, selected: bool) -> bool {
+    ui.vertical_centered(|ui| {
+        ui.set_width(ICON_SIZE + 16.0);
+        let clicked = match cup.icon.and_then(|v| icon_texture(ctx, v)) {
+            Some(texture) => ui
+                .add(egui::ImageButton::new(texture.texture_id(ctx), [ICON_SIZE, ICON_SIZE]).selected(selected))
+                .clicked(),
+            // Nintendo cups and cups without an icon
+            None => ui
+                .add_sized(
+                    [ICON_SIZE, ICON_SIZE],
+                    egui::SelectableLabel::new(selected, cup.name.split(' ').next().unwrap_or_default()),
+                )
+                .clicked(),
+        };
+        let shown = menu::truncate(&cup.name, CUP_NAME_WIDTH);
+        let label = if shown == cup.name {
+            ui.label(&shown)
+        } else {
+            ui.colored_label(ui.visuals().warn_fg_color, &shown)
+        };
+        clicked || label.interact(Sense::click()).on_hover_text(&cup.name).clicked()
+    })
+    .inner
+}
+
+/// Track names of `cup` as the game shows them next to the cups
+fn view_tracks(ui: &mut egui::Ui, cup: &MenuCup) {
+    ui.strong(&cup.name);
+    for name in &cup.tracks {
+        let shown = menu::truncate(name, TRACK_NAME_WIDTH);
+        if &shown == name {
+            ui.label(name);
+        } else {
+            ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", shown))
+                .on_hover_text(format!("{} is cut off in the game", name));
+        }
+    }
+}
+
+/// Count of the names which don't fit in the game, listed on hover
+fn view_truncated(ui: &mut egui::Ui, cups: &[MenuCup]) {
+    let mut names: Vec<&str> = vec![];
+    for cup in cups {
+        if menu::text_width(&cup.name) > CUP_NAME_WIDTH {
+            names.push(&cup.name);
+        }
+        names.extend(
+            cup.tracks
+                .iter()
+                .filter(|v| menu::text_width(v) > TRACK_NAME_WIDTH)
+                .map(|v| v.as_str()),
+        );
+    }
+    if names.is_empty() {
+        return;
+    }
+    ui.separator();
+    ui.colored_label(
+        ui.visuals().warn_fg_color,
+        format!("⚠ {} names are cut off in the game", names.len()),
+    )
+    .on_hover_text(names.join("\n"));
+}
+
+/// Texture of the icon, kept until the icon changes
+fn icon_texture(ctx: &Context, icon: &Icon) -> Option<Arc<RetainedImage>> {
+    if icon.image.is_empty() {
+        return None;
+    }
+    let mut hasher = DefaultHasher::new();
+    icon.image.hash(&mut hasher);
+    let id = egui::Id::new(("menu_icon", hasher.finish()));
+    if let Some(v) = ctx.memory().data.get_temp::<Arc<RetainedImage>>(id) {
+        return Some(v);
+    }
+    let texture = Arc::new(RetainedImage::from_image_bytes(&icon.filename, &icon.image).ok()?);
+    ctx.memory().data.insert_temp(id, texture.clone());
+    Some(texture)
+}
@@ -0,0 +1,144 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use crate::apps::{
+    tracks::{Cup, TrackDefinition},
+    IconGeneratorApp,
+};
+use crate::helpers::{
+    icon::{Background, IconContent},
+    parser::encode_png,
+};
+use egui::{Context, TextEdit};
+use egui_extras::RetainedImage;
+
+impl Default for IconGeneratorApp {
+    fn default() -> Self {
+        Self {
+            open: false,
+            template: Default::default(),
+            preview: None,
+        }
+    }
+}
+
+impl IconGeneratorApp {
+    pub fn ui(&mut self, ctx: &Context, editor: &mut TrackDefinition) {
+        let mut open = self.open;
+        egui::Window::new("Icon Generator")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.vertical(|ui| self.view_template(ui));
+                    ui.separator();
+                    ui.vertical(|ui| {
+                        let selected = editor.selected;
+                        if let Some(cup) = editor.list_ref().get(selected) {
+                            let texture = self.preview(cup, selected + 1);
+                            ui.image(texture.texture_id(ctx), [128.0, 128.0]);
+                            ui.label(&cup.name);
+                        }
+                    });
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Apply to the selected cup").clicked() {
+                        let selected = editor.selected;
+                        if let Some(cup) = editor.list_mut().get_mut(selected) {
+                            self.apply(cup, selected + 1);
+                        }
+                    }
+                    if ui
+                        .button("Generate missing icons")
+                        .on_hover_text("Generate icons for all cups without an icon")
+                        .clicked()
+                    {
+                        for (i, cup) in editor.list_mut().iter_mut().enumerate() {
+                            if cup.icon.image.is_empty() {
+                                self.apply(cup, i + 1);
+                            }
+                        }
+                    }
+                });
+            });
+        self.open = open;
+    }
+
+    fn view_template(&mut self, ui: &mut egui::Ui) {
+        let template = &mut self.template;
+        egui::ComboBox::from_label("Background")
+            .selected_text(template.background.as_str())
+            .show_ui(ui, |ui| {
+                for v in Background::VALUES {
+                    ui.selectable_value(&mut template.background, v, v.as_str());
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.color_edit_button_srgba(&mut template.top);
+            if template.background == Background::Gradient {
+                ui.color_edit_button_srgba(&mut template.bottom);
+            }
+        });
+        ui.separator();
+        egui::ComboBox::from_label("Content")
+            .selected_text(template.content.as_str())
+            .show_ui(ui, |ui| {
+                for v in IconContent::VALUES {
+                    ui.selectable_value(&mut template.content, v, v.as_str());
+                }
+            });
+        match template.content {
+            IconContent::Number | IconContent::Label => {
+                if template.content == IconContent::Label {
+                    ui.add(
+                        TextEdit::singleline(&mut template.label)
+                            .hint_text("Cup name")
+                            .desired_width(160.0),
+                    )
+                    .on_hover_text("Leave empty to use the name of each cup");
+                }
+                ui.horizontal(|ui| {
+                    ui.color_edit_button_srgba(&mut template.text_color);
+                    ui.label("Text colour");
+                });
+            }
+            IconContent::Collage => {
+                ui.label("Select thumbnails with 🖼 on each track.");
+            }
+        }
+    }
+
+    /// Rendered icon of `cup`, drawn again only when the template or the cup changes
+    fn preview(&mut self, cup: &Cup, n: usize) -> &RetainedImage {
+        let mut hasher = DefaultHasher::new();
+        self.template.hash(&mut hasher);
+        cup.name.hash(&mut hasher);
+        n.hash(&mut hasher);
+        for track in &cup.trackset {
+            track.thumbnail.hash(&mut hasher);
+        }
+        let hash = hasher.finish();
+
+        if self.preview.as_ref().map(|v| v.0) != Some(hash) {
+            let icon = self.template.render(cup, n);
+            let image = egui::ColorImage::from_rgba_unmultiplied(
+                [icon.width() as usize, icon.height() as usize],
+                icon.as_raw(),
+            );
+            self.preview = Some((hash, RetainedImage::from_color_image("icon_generator", image)));
+        }
+        &self.preview.as_ref().unwrap().1
+    }
+
+    /// The original image is kept in `source`, so resizing it again brings it back
+    fn apply(&self, cup: &mut Cup, n: usize) {
+        if let Ok(png) = encode_png(&self.template.render(cup, n)) {
+            cup.icon.filename = "Generated".to_string();
+            cup.icon.image = png;
+        }
+    }
+}
@@ -1,11 +1,29 @@
-use crate::apps::tracks::TrackDefinition;
+use std::{collections::HashMap, path::PathBuf, time::Instant};
+
+use crate::apps::{
+    track_browser::{TrackColumn, TrackFilter, TrackKey},
+    tracks::{CupList, CupSettings, Id, TrackDefinition},
+};
+use crate::helpers::{
+    icon::IconTemplate,
+    validate::{Location, Problem},
+};
 use egui::Context;
+use egui_extras::RetainedImage;
 
 pub mod codes;
+pub mod cup_preview;
+pub mod icon_generator;
+pub mod preferences;
+pub mod problems;
 pub mod settings;
+pub mod source;
+pub mod track_browser;
 pub mod tracks;
+pub mod translations;
+pub mod wiimm_cup;
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub enum Region {
     NTSC,
     PAL,
@@ -13,24 +31,217 @@ pub enum Region {
     KOR,
 }
 
+impl Region {
+    pub const VALUES: [Self; 4] = [Region::PAL, Region::NTSC, Region::JAP, Region::KOR];
+
+    /// Region of Mario Kart Wii from the game ID in sys/boot.bin
+    pub fn from_game_id(id: &str) -> Option<Self> {
+        match id {
+            "RMCE" => Some(Region::NTSC),
+            "RMCP" => Some(Region::PAL),
+            "RMCJ" => Some(Region::JAP),
+            "RMCK" => Some(Region::KOR),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Region::NTSC => "NTSC-U",
+            Region::PAL => "PAL",
+            Region::JAP => "NTSC-J",
+            Region::KOR => "NTSC-K",
+        }
+    }
+
+    pub fn game_id(&self) -> &'static str {
+        match self {
+            Region::NTSC => "RMCE",
+            Region::PAL => "RMCP",
+            Region::JAP => "RMCJ",
+            Region::KOR => "RMCK",
+        }
+    }
+
+    /// Name in the files of LE-CODE, like lecode-PAL.bin
+    pub fn lecode_name(&self) -> &'static str {
+        match self {
+            Region::NTSC => "USA",
+            Region::PAL => "PAL",
+            Region::JAP => "JAP",
+            Region::KOR => "KOR",
+        }
+    }
+
+    /// Region type in Riivolution XML
+    pub fn riivolution_type(&self) -> &'static str {
+        match self {
+            Region::NTSC => "E",
+            Region::PAL => "P",
+            Region::JAP => "J",
+            Region::KOR => "K",
+        }
+    }
+
+    /// Languages the game of the region has message files for
+    pub fn languages(&self) -> &'static [Language] {
+        match self {
+            Region::NTSC => &[Language::EnglishUs, Language::FrenchCa, Language::SpanishLa],
+            Region::PAL => &[
+                Language::English,
+                Language::German,
+                Language::French,
+                Language::Spanish,
+                Language::Italian,
+            ],
+            Region::JAP => &[Language::Japanese],
+            Region::KOR => &[Language::Korean],
+        }
+    }
+}
+
+/// Language of the message files, like Menu_E.szs
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Language {
+    English,
+    German,
+    French,
+    Spanish,
+    Italian,
+    EnglishUs,
+    FrenchCa,
+    SpanishLa,
+    Japanese,
+    Korean,
+}
+
+impl Language {
+    pub const VALUES: [Self; 10] = [
+        Language::English,
+        Language::German,
+        Language::French,
+        Language::Spanish,
+        Language::Italian,
+        Language::EnglishUs,
+        Language::FrenchCa,
+        Language::SpanishLa,
+        Language::Japanese,
+        Language::Korean,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Language::English => "English (PAL)",
+            Language::German => "German",
+            Language::French => "French (PAL)",
+            Language::Spanish => "Spanish (PAL)",
+            Language::Italian => "Italian",
+            Language::EnglishUs => "English (NTSC-U)",
+            Language::FrenchCa => "French (NTSC-U)",
+            Language::SpanishLa => "Spanish (NTSC-U)",
+            Language::Japanese => "Japanese",
+            Language::Korean => "Korean",
+        }
+    }
+
+    /// Letter at the end of the message files
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Language::English => "E",
+            Language::German => "G",
+            Language::French => "F",
+            Language::Spanish => "S",
+            Language::Italian => "I",
+            Language::EnglishUs => "U",
+            Language::FrenchCa => "Q",
+            Language::SpanishLa => "M",
+            Language::Japanese => "J",
+            Language::Korean => "K",
+        }
+    }
+
+    pub fn from_u8(n: u8) -> Option<Self> {
+        Self::VALUES.get(n as usize).copied()
+    }
+}
+
 #[derive(Default)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub struct TrackDefApp {
     pub editor: TrackDefinition,
+    pub generator: IconGeneratorApp,
+    pub translations: TranslationApp,
+    pub wiimm: WiimmCupApp,
+    pub preview: CupPreviewApp,
+    pub browser: TrackBrowserApp,
+    /// Target LE-CODE build of the project, for the limits
+    pub lecode_build: u16,
 }
 
-#[derive(PartialEq)]
+/// Table of cup and track names in every language
+pub struct TranslationApp {
+    pub open: bool,
+    /// Languages shown as columns
+    pub shown: Vec<Language>,
+}
+
+/// Table of the tracks of all cups, to find and edit many tracks at once
+pub struct TrackBrowserApp {
+    pub open: bool,
+    /// Text searched in names, authors and file names
+    pub search: String,
+    pub filter: TrackFilter,
+    pub sort: TrackColumn,
+    pub ascending: bool,
+    pub selected: Vec<TrackKey>,
+    /// `track_layout` of the editor when the tracks were selected
+    pub layout: u64,
+    /// Whether each track file exists, by file name
+    pub files: HashMap<String, bool>,
+    /// When `files` was started, to look at the files again after a while
+    pub files_checked: Option<Instant>,
+    /// Author name to set on the selected tracks
+    pub author: String,
+}
+
+/// Cup select screen of the game, to check the order, icons and names of the cups
+pub struct CupPreviewApp {
+    pub open: bool,
+    pub list: CupList,
+    pub page: usize,
+    /// Index in the cups of the screen
+    pub selected: Option<usize>,
+}
+
+/// Setup of the Wiimm cup, with the tracks of each random slot
+#[derive(Default)]
+pub struct WiimmCupApp {
+    pub open: bool,
+}
+
+pub struct IconGeneratorApp {
+    pub open: bool,
+    pub template: IconTemplate,
+    /// Preview of the selected cup, with the hash of what it's drawn from
+    pub preview: Option<(u64, RetainedImage)>,
+}
+
+#[derive(PartialEq, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "serde", serde(default))]
 pub struct SettingApp {
+    /// LE-CODE build the parameters are written for
+    pub lecode_build: u16,
     // ENABLE-200CC
     pub toggle_200cc: bool,
     // ENGINE
     pub engine_probs: EngineProbSet,
     // PERF-MONITOR
-    // pub toggle_perf: bool,
+    pub toggle_perf: PerfMonitorMode,
     // CUSTOM-TT
     pub toggle_ct_tt: bool,
+    // TT-200CC
+    pub toggle_tt_200cc: bool,
     // XPFLAGS
     pub toggle_custom_presence: bool,
     // BLOCK-TRACK
@@ -41,6 +252,26 @@ pub struct SettingApp {
     pub toggle_drag_blue: bool,
     // THCLOUD-TIME
     pub time_cloud: i16,
+    // DEBUG
+    pub toggle_debug: bool,
+    // CHEAT-MODE
+    pub cheat_mode: CheatMode,
+    // ITEM-CHEAT
+    pub item_cheat: CheatMode,
+    // BT-WORLDWIDE
+    pub toggle_bt_worldwide: bool,
+    // VS-WORLDWIDE
+    pub toggle_vs_worldwide: bool,
+    // BT-REGION
+    pub bt_region: u16,
+    // VS-REGION
+    pub vs_region: u16,
+    // BLOCK-TEXTURES
+    pub toggle_block_textures: bool,
+    // SPEED-LIMIT
+    pub speed_limit: u16,
+    /// Regions a build produces files for
+    pub regions: Vec<Region>,
 }
 
 #[derive(PartialEq, Copy, Clone)]
@@ -65,6 +296,54 @@ impl SpeedometerMode {
     }
 }
 
+#[derive(PartialEq, Copy, Clone)]
+pub enum PerfMonitorMode {
+    Off,
+    On,
+    /// Also shown on Dolphin
+    Force,
+}
+
+impl PerfMonitorMode {
+    pub const VALUES: [Self; 3] = [PerfMonitorMode::Off, PerfMonitorMode::On, PerfMonitorMode::Force];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PerfMonitorMode::Off => "Off",
+            PerfMonitorMode::On => "On",
+            PerfMonitorMode::Force => "Force",
+        }
+    }
+
+    pub fn from_u8(n: u8) -> Option<Self> {
+        Self::VALUES.get(n as usize).copied()
+    }
+}
+
+/// Where cheats are allowed
+#[derive(PartialEq, Copy, Clone)]
+pub enum CheatMode {
+    Off,
+    Offline,
+    Always,
+}
+
+impl CheatMode {
+    pub const VALUES: [Self; 3] = [CheatMode::Off, CheatMode::Offline, CheatMode::Always];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheatMode::Off => "Off",
+            CheatMode::Offline => "Offline",
+            CheatMode::Always => "Always",
+        }
+    }
+
+    pub fn from_u8(n: u8) -> Option<Self> {
+        Self::VALUES.get(n as usize).copied()
+    }
+}
+
 #[derive(PartialEq)]
 pub enum CloudShrinkTime {
     Nintendo = 612,
@@ -93,6 +372,17 @@ pub struct CodeStruct {
     pub code_kor: String,
 }
 
+impl CodeStruct {
+    pub fn body(&self, region: Region) -> &String {
+        match region {
+            Region::NTSC => &self.code_ntsc,
+            Region::PAL => &self.code_pal,
+            Region::JAP => &self.code_jp,
+            Region::KOR => &self.code_kor,
+        }
+    }
+}
+
 impl Default for CodeStruct {
     fn default() -> Self {
         CodeStruct {
@@ -105,7 +395,69 @@ impl Default for CodeStruct {
     }
 }
 
-#[derive(PartialEq)]
+/// Location of Nintendo content used by the build
+pub struct SourceApp {
+    pub open: bool,
+    pub kind: SourceKind,
+    /// Path to ISO or WBFS
+    pub disc_path: String,
+    /// Path to the extracted DATA or files folder
+    pub folder_path: String,
+    /// Path to the Wii common key (16 bytes binary or hex text)
+    pub common_key_path: String,
+    /// Folder with lecode-*.bin. When empty, they're read from rel of the source.
+    pub lecode_path: String,
+    /// Result of the last check
+    pub status: String,
+    /// Whether each original track exists in the source, filled by the last check
+    pub presence: Vec<(Id, bool)>,
+}
+
+/// Results of `Distro::validate`
+pub struct ProblemsApp {
+    pub open: bool,
+    pub dock: Dock,
+    pub problems: Vec<Problem>,
+    /// When the problems were collected, to run the rules again after a while
+    pub checked: Option<Instant>,
+    /// Location of the clicked problem, handled by `Distro::jump_to`
+    pub jump: Option<Location>,
+}
+
+/// Side of the window the problems panel is attached to
+#[derive(PartialEq, Clone, Copy)]
+pub enum Dock {
+    Bottom,
+    Right,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum SourceKind {
+    Disc,
+    Folder,
+}
+
+/// Application preferences kept between runs by eframe persistence
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PreferenceApp {
+    pub open: bool,
+    pub dark_mode: bool,
+    /// Recently opened or saved projects, newest first
+    pub recent: Vec<PathBuf>,
+    /// `SourceApp` paths
+    pub source_folder: bool,
+    pub disc_path: String,
+    pub common_key_path: String,
+    pub folder_path: String,
+    pub lecode_path: String,
+    /// `SettingApp` for new projects
+    pub settings: SettingApp,
+    /// `CupSettings` for new projects
+    pub cup_mode: CupSettings,
+}
+
+#[derive(PartialEq, Clone)]
 pub struct EngineProbSet {
     pub low: i32,
     pub mid: i32,
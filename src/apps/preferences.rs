@@ -0,0 +1,195 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use crate::apps::{ElementView, MainView, PreferenceApp, SourceApp, SourceKind};
+use crate::helpers::{
+    file::{last_dirs, set_last_dirs},
+    parser::{decode_cup_mode, decode_settings, encode_cup_mode, encode_settings, SETTINGS_SIZE_V1},
+    texture::TextureFormat,
+};
+use egui::Context;
+
+/// Number of projects kept in File > Open Recent
+const RECENT_LIMIT: usize = 10;
+
+/// Keys in the eframe storage. Lists are kept one entry per line.
+const KEY_DARK_MODE: &'static str = "dark_mode";
+const KEY_RECENT: &'static str = "recent";
+/// Lines of the dialog name and the folder, split by a tab
+const KEY_LAST_DIRS: &'static str = "last_dirs";
+/// `SettingApp` in the project encoding, as hex
+const KEY_SETTINGS: &'static str = "default_settings";
+/// `CupSettings` in the project encoding
+const KEY_CUP_MODE: &'static str = "default_cup_mode";
+const KEY_SOURCE_FOLDER: &'static str = "source_folder";
+const KEY_DISC_PATH: &'static str = "disc_path";
+const KEY_COMMON_KEY_PATH: &'static str = "common_key_path";
+const KEY_FOLDER_PATH: &'static str = "folder_path";
+const KEY_LECODE_PATH: &'static str = "lecode_path";
+
+impl Default for PreferenceApp {
+    fn default() -> Self {
+        Self {
+            open: false,
+            dark_mode: true,
+            recent: vec![],
+            source_folder: false,
+            disc_path: String::new(),
+            common_key_path: String::new(),
+            folder_path: String::new(),
+            lecode_path: String::new(),
+            settings: Default::default(),
+            cup_mode: Default::default(),
+        }
+    }
+}
+
+impl MainView for PreferenceApp {
+    fn name(&self) -> &'static str {
+        "Preferences"
+    }
+
+    fn ui(&mut self, ctx: &Context) {
+        let mut open = self.open;
+        egui::Window::new(self.name())
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                ui.label("Applied to new projects");
+                egui::CollapsingHeader::new("Default cup setup")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        ui.toggle_value(&mut self.cup_mode.nintendo, "Nintendo Cups");
+                        ui.add_enabled_ui(self.cup_mode.nintendo, |ui| {
+                            ui.toggle_value(&mut self.cup_mode.nin_swap, "Swap Nintendo Cups");
+                        });
+                        ui.toggle_value(&mut self.cup_mode.wiimm_cup, "Wiimm Cup");
+                        egui::ComboBox::from_label("Icon format")
+                            .selected_text(self.cup_mode.icon_format.as_str())
+                            .show_ui(ui, |ui| {
+                                for v in TextureFormat::VALUES {
+                                    ui.selectable_value(&mut self.cup_mode.icon_format, v, v.as_str());
+                                }
+                            });
+                    });
+                egui::CollapsingHeader::new("Default LE-CODE parameters").show(ui, |ui| {
+                    self.settings.view(ctx, ui);
+                    if ui.button("Reset to LE-CODE defaults").clicked() {
+                        self.settings = Default::default();
+                    }
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} recent projects", self.recent.len()));
+                    if ui.button("Clear").on_hover_text("Clear File > Open Recent").clicked() {
+                        self.recent.clear();
+                    }
+                });
+            });
+        self.open = open;
+    }
+}
+
+impl PreferenceApp {
+    /// Read the preferences from storage, keeping the default of missing or broken values
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        let mut p = Self::default();
+        let storage = match storage {
+            Some(v) => v,
+            None => return p,
+        };
+        let get = |key: &str| storage.get_string(key);
+        let flag = |key: &str, default: bool| get(key).map_or(default, |v| v == "1");
+        p.dark_mode = flag(KEY_DARK_MODE, p.dark_mode);
+        if let Some(v) = get(KEY_RECENT) {
+            p.recent = v.lines().map(PathBuf::from).take(RECENT_LIMIT).collect();
+        }
+        if let Some(v) = get(KEY_LAST_DIRS) {
+            let dirs: BTreeMap<String, PathBuf> = v
+                .lines()
+                .filter_map(|v| v.split_once('\t'))
+                .map(|(name, dir)| (name.to_string(), PathBuf::from(dir)))
+                .collect();
+            set_last_dirs(dirs);
+        }
+        if let Some(v) = get(KEY_SETTINGS).and_then(|v| decode_hex(&v)) {
+            if v.len() >= SETTINGS_SIZE_V1 {
+                p.settings = decode_settings(&v);
+            }
+        }
+        if let Some(v) = get(KEY_CUP_MODE).and_then(|v| v.parse().ok()) {
+            p.cup_mode = decode_cup_mode(v);
+        }
+        p.source_folder = flag(KEY_SOURCE_FOLDER, p.source_folder);
+        for (key, path) in [
+            (KEY_DISC_PATH, &mut p.disc_path),
+            (KEY_COMMON_KEY_PATH, &mut p.common_key_path),
+            (KEY_FOLDER_PATH, &mut p.folder_path),
+            (KEY_LECODE_PATH, &mut p.lecode_path),
+        ] {
+            if let Some(v) = get(key) {
+                *path = v;
+            }
+        }
+        p
+    }
+
+    pub fn store(&mut self, storage: &mut dyn eframe::Storage, source: &SourceApp) {
+        self.source_folder = source.kind == SourceKind::Folder;
+        self.disc_path = source.disc_path.clone();
+        self.common_key_path = source.common_key_path.clone();
+        self.folder_path = source.folder_path.clone();
+        self.lecode_path = source.lecode_path.clone();
+
+        let flag = |v: bool| if v { "1" } else { "0" }.to_string();
+        let recent: Vec<String> = self.recent.iter().map(|v| v.to_string_lossy().to_string()).collect();
+        let last_dirs: Vec<String> = last_dirs()
+            .iter()
+            .map(|(name, dir)| format!("{}\t{}", name, dir.to_string_lossy()))
+            .collect();
+        let settings: String = encode_settings(&self.settings)
+            .iter()
+            .map(|v| format!("{:02x}", v))
+            .collect();
+        storage.set_string(KEY_DARK_MODE, flag(self.dark_mode));
+        storage.set_string(KEY_RECENT, recent.join("\n"));
+        storage.set_string(KEY_LAST_DIRS, last_dirs.join("\n"));
+        storage.set_string(KEY_SETTINGS, settings);
+        storage.set_string(KEY_CUP_MODE, encode_cup_mode(&self.cup_mode).to_string());
+        storage.set_string(KEY_SOURCE_FOLDER, flag(self.source_folder));
+        storage.set_string(KEY_DISC_PATH, self.disc_path.clone());
+        storage.set_string(KEY_COMMON_KEY_PATH, self.common_key_path.clone());
+        storage.set_string(KEY_FOLDER_PATH, self.folder_path.clone());
+        storage.set_string(KEY_LECODE_PATH, self.lecode_path.clone());
+    }
+
+    pub fn apply_source(&self, source: &mut SourceApp) {
+        source.kind = if self.source_folder {
+            SourceKind::Folder
+        } else {
+            SourceKind::Disc
+        };
+        source.disc_path = self.disc_path.clone();
+        source.common_key_path = self.common_key_path.clone();
+        source.folder_path = self.folder_path.clone();
+        source.lecode_path = self.lecode_path.clone();
+    }
+
+    pub fn add_recent(&mut self, path: &PathBuf) {
+        self.recent.retain(|v| v != path);
+        self.recent.insert(0, path.clone());
+        self.recent.truncate(RECENT_LIMIT);
+    }
+}
+
+/// Bytes of `hex`, or `None` if it isn't an even number of hex digits
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,97 @@
+use crate::apps::{Dock, MainView, ProblemsApp};
+use crate::helpers::validate::{count, Severity};
+use egui::{Context, RichText};
+
+impl Default for ProblemsApp {
+    fn default() -> Self {
+        Self {
+            open: false,
+            dock: Dock::Bottom,
+            problems: vec![],
+            checked: None,
+            jump: None,
+        }
+    }
+}
+
+impl MainView for ProblemsApp {
+    fn name(&self) -> &'static str {
+        "Problems"
+    }
+
+    fn ui(&mut self, ctx: &Context) {
+        if !self.open {
+            return;
+        }
+        match self.dock {
+            Dock::Bottom => {
+                egui::TopBottomPanel::bottom("problems_panel")
+                    .resizable(true)
+                    .default_height(140.0)
+                    .show(ctx, |ui| self.view(ui));
+            }
+            Dock::Right => {
+                egui::SidePanel::right("problems_panel")
+                    .resizable(true)
+                    .default_width(320.0)
+                    .show(ctx, |ui| self.view(ui));
+            }
+        }
+    }
+}
+
+impl ProblemsApp {
+    fn view(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.heading(self.name());
+            for v in Severity::VALUES {
+                ui.label(format!("{} {} {}", v.icon(), count(&self.problems, v), v.as_str()));
+            }
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("✖").on_hover_text("Close").clicked() {
+                    self.open = false;
+                }
+                let (icon, next) = match self.dock {
+                    Dock::Bottom => ("⏵", Dock::Right),
+                    Dock::Right => ("⏷", Dock::Bottom),
+                };
+                if ui.button(icon).on_hover_text("Move the panel").clicked() {
+                    self.dock = next;
+                }
+                if ui.button("⟳").on_hover_text("Check again").clicked() {
+                    self.checked = None;
+                }
+            });
+        });
+        ui.separator();
+
+        if self.problems.is_empty() {
+            ui.label("No problems are found.");
+            return;
+        }
+        egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| {
+            for problem in &self.problems {
+                let color = match problem.severity {
+                    Severity::Error => ui.visuals().error_fg_color,
+                    Severity::Warning => ui.visuals().warn_fg_color,
+                    Severity::Info => ui.visuals().text_color(),
+                };
+                let text = RichText::new(format!("{} {}", problem.severity.icon(), problem.message)).color(color);
+                match problem.location {
+                    Some(location) => {
+                        if ui
+                            .link(text)
+                            .on_hover_text("Show where to fix it")
+                            .clicked()
+                        {
+                            self.jump = Some(location);
+                        }
+                    }
+                    None => {
+                        ui.label(text);
+                    }
+                }
+            }
+        });
+    }
+}
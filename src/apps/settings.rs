@@ -1,17 +1,42 @@
-use crate::apps::{CloudShrinkTime, MainView, SettingApp, SpeedometerMode};
-use egui::{Align, Context, Layout, SelectableLabel, Slider};
+use crate::{
+    apps::{
+        CheatMode, CloudShrinkTime, ElementView, EngineProbSet, MainView, PerfMonitorMode, Region, SettingApp,
+        SpeedometerMode,
+    },
+    helpers::{
+        lpar::{self, LparKey},
+        profile::{self, LeCodeProfile},
+    },
+};
+use egui::{Align, Context, Layout, Slider, Ui};
+
+/// Engine probabilities always add up to this
+pub const ENGINE_TOTAL: i32 = 100;
 
 impl Default for SettingApp {
     fn default() -> Self {
         Self {
+            lecode_build: profile::latest_build(),
             toggle_200cc: false,
             engine_probs: Default::default(),
+            toggle_perf: PerfMonitorMode::Off,
             toggle_ct_tt: false,
+            toggle_tt_200cc: false,
             toggle_custom_presence: true,
             prevent_selection_online: 0,
             toggle_som: SpeedometerMode::Two,
             toggle_drag_blue: true,
             time_cloud: CloudShrinkTime::LECODE as i16,
+            toggle_debug: false,
+            cheat_mode: CheatMode::Offline,
+            item_cheat: CheatMode::Off,
+            toggle_bt_worldwide: true,
+            toggle_vs_worldwide: true,
+            bt_region: 0,
+            vs_region: 0,
+            toggle_block_textures: false,
+            speed_limit: 0,
+            regions: Region::VALUES.to_vec(),
         }
     }
 }
@@ -26,25 +51,103 @@ impl MainView for SettingApp {
             .resizable(false)
             .show(ctx, |ui| {
                 ui.heading("LPAR Settings");
-                ui.add_enabled(false, SelectableLabel::new(self.toggle_200cc == true, "200cc"))
-                    .on_disabled_hover_text("200cc is not implemented yet.");
-                ui.horizontal(|ui| {
-                    ui.label("Engine Probabilities");
-                    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                        if ui.button("Reset").clicked() {
-                            self.engine_probs = Default::default()
+                self.view(ctx, ui);
+            });
+    }
+}
+
+impl EngineProbSet {
+    pub fn values(&self) -> [i32; 3] {
+        [self.low, self.mid, self.high]
+    }
+
+    fn set_values(&mut self, v: [i32; 3]) {
+        [self.low, self.mid, self.high] = v;
+    }
+
+    /// Set the probability at `index` and change the others to keep the total at 100.
+    /// With `keep_ratio`, the others keep their ratio. Otherwise the neighbour changes first.
+    pub fn set_linked(&mut self, index: usize, value: i32, keep_ratio: bool) {
+        let mut v = self.values();
+        v[index] = value.clamp(0, ENGINE_TOTAL);
+        let others = match index {
+            0 => [1, 2],
+            1 => [2, 0],
+            _ => [1, 0],
+        };
+        let rest = ENGINE_TOTAL - v[index];
+
+        if keep_ratio {
+            let sum = v[others[0]] + v[others[1]];
+            let first = if sum > 0 {
+                (rest * v[others[0]] + sum / 2) / sum
+            } else {
+                rest / 2
+            };
+            v[others[0]] = first;
+            v[others[1]] = rest - first;
+        } else {
+            let mut diff = rest - (v[others[0]] + v[others[1]]);
+            for i in others {
+                let new = (v[i] + diff).clamp(0, ENGINE_TOTAL);
+                diff -= new - v[i];
+                v[i] = new;
+            }
+        }
+        self.set_values(v);
+    }
+
+    /// Scale the probabilities to add up to 100, for projects saved without the limit
+    pub fn normalize(&mut self) {
+        let v = self.values().map(|v| v.max(0));
+        let sum: i32 = v.iter().sum();
+        if sum == 0 {
+            *self = Default::default();
+            return;
+        }
+        let low = (v[0] * ENGINE_TOTAL + sum / 2) / sum;
+        let mid = (v[1] * ENGINE_TOTAL + sum / 2) / sum;
+        self.set_values([low, mid, (ENGINE_TOTAL - low - mid).max(0)]);
+    }
+}
+
+impl ElementView for SettingApp {
+    fn view(&mut self, _ctx: &Context, ui: &mut egui::Ui) {
+        let target = profile::profile(self.lecode_build);
+        ui.horizontal(|ui| {
+            ui.label("Target LE-CODE")
+                .on_hover_text("Parameters which this build doesn't read are disabled and not exported");
+            ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+                egui::ComboBox::from_id_source("lecode_profile")
+                    .selected_text(&target.name)
+                    .show_ui(ui, |ui| {
+                        for v in profile::profiles() {
+                            ui.selectable_value(&mut self.lecode_build, v.build, &v.name)
+                                .on_hover_text(&v.source);
                         }
-                    })
-                });
-                ui.vertical(|ui| {
-                    ui.add(Slider::new(&mut self.engine_probs.low, 0..=100).text(engine_label(false, EngineProbMode::Low)))
-                        .on_hover_text(format!("Probability for {} online", engine_label(false, EngineProbMode::Low)));
-                    ui.add(Slider::new(&mut self.engine_probs.mid, 0..=100).text(engine_label(false, EngineProbMode::Mid)))
-                        .on_hover_text(format!("Probability for {} online", engine_label(false, EngineProbMode::Mid)));
-                    ui.add(Slider::new(&mut self.engine_probs.high, 0..=100).text(engine_label(false, EngineProbMode::High)))
-                        .on_hover_text(format!("Probability for {} online", engine_label(false, EngineProbMode::High)));
-                });
-                ui.separator();
+                    });
+            })
+        });
+        ui.separator();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            lpar_option(ui, target, &lpar::ENABLE_200CC, |ui| {
+                ui.toggle_value(&mut self.toggle_200cc, "200cc")
+                    .on_hover_text("Enable 200cc\nThe engine classes online become 150cc, 200cc and mirror");
+            });
+            lpar_option(ui, target, &lpar::ENGINE, |ui| self.engine_view(ui));
+            ui.separator();
+            lpar_option(ui, target, &lpar::PERF_MONITOR, |ui| {
+                ui.label("Performance Monitor");
+                ui.horizontal(|ui| {
+                    for mode in PerfMonitorMode::VALUES {
+                        ui.selectable_value(&mut self.toggle_perf, mode, mode.as_str());
+                    }
+                })
+                .response
+                .on_hover_text("Force also shows the monitor on Dolphin");
+            });
+            ui.separator();
+            lpar_option(ui, target, &lpar::CUSTOM_TT, |ui| {
                 ui.horizontal(|ui| {
                     ui.label("CT for Time Trial");
                     ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
@@ -52,7 +155,18 @@ impl MainView for SettingApp {
                             .on_hover_text("Allow to select custom tracks on time trial");
                     })
                 });
-                ui.separator();
+            });
+            lpar_option(ui, target, &lpar::TT_200CC, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("200cc for Time Trial");
+                    ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+                        ui.toggle_value(&mut self.toggle_tt_200cc, "Enable")
+                            .on_hover_text("Allow time trials in 200cc");
+                    })
+                });
+            });
+            ui.separator();
+            lpar_option(ui, target, &lpar::XPFLAGS, |ui| {
                 ui.horizontal(|ui| {
                     ui.label("Extended Presence Flags");
                     ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
@@ -60,11 +174,24 @@ impl MainView for SettingApp {
                             .on_hover_text("Allow to work extended presence flag");
                     })
                 });
-                ui.separator();
+            });
+            ui.separator();
+            lpar_option(ui, target, &lpar::BLOCK_TRACK, |ui| {
                 ui.label("Block previous tracks");
                 ui.add(Slider::new(&mut self.prevent_selection_online, 0..=50))
                     .on_hover_text("Number of races that a previously raced track is blocked\nValus between 0 and 50 are allowed");
-                ui.separator();
+            });
+            lpar_option(ui, target, &lpar::BLOCK_TEXTURES, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Block Texture Hacks");
+                    ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+                        ui.toggle_value(&mut self.toggle_block_textures, "Enable")
+                            .on_hover_text("Ignore texture hacks of players online");
+                    })
+                });
+            });
+            ui.separator();
+            lpar_option(ui, target, &lpar::SPEEDOMETER, |ui| {
                 ui.label("Speedometer")
                     .on_hover_text("Setting for speedometer");
                 ui.horizontal_wrapped(|ui| {
@@ -79,7 +206,38 @@ impl MainView for SettingApp {
                     ui.selectable_value(&mut self.toggle_som, SpeedometerMode::Three, ".000")
                         .on_hover_text("Display speedometer at bottom right using format 123.456 km/h");
                 });
-                ui.separator();
+            });
+            lpar_option(ui, target, &lpar::SPEED_LIMIT, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Speed Limit")
+                        .on_hover_text("Maximum speed in km/h\n0 keeps the limit of the game");
+                    ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+                        ui.add(egui::DragValue::new(&mut self.speed_limit).clamp_range(0..=9999).suffix(" km/h"))
+                    })
+                });
+            });
+            lpar_option(ui, target, &lpar::DEBUG, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Debug Information");
+                    ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+                        ui.toggle_value(&mut self.toggle_debug, "Enable")
+                            .on_hover_text("Show debug information on the screen");
+                    })
+                });
+            });
+            ui.separator();
+            lpar_option(ui, target, &lpar::CHEAT_MODE, |ui| {
+                ui.label("Cheat Codes")
+                    .on_hover_text("Where cheat codes of the distribution may be used");
+                cheat_mode_view(ui, &mut self.cheat_mode);
+            });
+            lpar_option(ui, target, &lpar::ITEM_CHEAT, |ui| {
+                ui.label("Item Cheat")
+                    .on_hover_text("Where players may choose their items");
+                cheat_mode_view(ui, &mut self.item_cheat);
+            });
+            ui.separator();
+            lpar_option(ui, target, &lpar::DRAG_BLUE_SHELL, |ui| {
                 ui.horizontal(|ui| {
                     ui.label("Draggable Blue Shell")
                         .on_hover_text("Setting for allowing to drag blue shell");
@@ -88,7 +246,8 @@ impl MainView for SettingApp {
                             .on_hover_text("Allow player to drag blue shell");
                     });
                 });
-                ui.separator();
+            });
+            lpar_option(ui, target, &lpar::THCLOUD_TIME, |ui| {
                 ui.label("Thundercloud Shrink Time")
                     .on_hover_text("Time in frames a player is small after being struck by a thundercloud");
                 ui.horizontal(|ui| {
@@ -99,18 +258,113 @@ impl MainView for SettingApp {
                     ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
                         ui.add(egui::DragValue::new(&mut self.time_cloud).clamp_range(1..=32767))
                     })
-                })
+                });
             });
+            ui.separator();
+            ui.label("Online Matchmaking");
+            for (key, worldwide, label) in [
+                (&lpar::VS_WORLDWIDE, &mut self.toggle_vs_worldwide, "Races"),
+                (&lpar::BT_WORLDWIDE, &mut self.toggle_bt_worldwide, "Battles"),
+            ] {
+                lpar_option(ui, target, key, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+                            ui.toggle_value(worldwide, "Worldwide")
+                                .on_hover_text(format!("Allow worldwide {}", label.to_lowercase()));
+                        })
+                    });
+                });
+            }
+            for (key, region, label) in [
+                (&lpar::VS_REGION, &mut self.vs_region, "Race region"),
+                (&lpar::BT_REGION, &mut self.bt_region, "Battle region"),
+            ] {
+                lpar_option(ui, target, key, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(label)
+                            .on_hover_text("Region number of matches with players of the same region\n0 keeps the original region");
+                        ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
+                            ui.add(egui::DragValue::new(region))
+                        })
+                    });
+                });
+            }
+        });
     }
 }
 
-enum EngineProbMode {
+impl SettingApp {
+    fn engine_view(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Engine Probabilities");
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                if ui.button("Reset").clicked() {
+                    self.engine_probs = Default::default()
+                }
+            })
+        });
+        // The lock is a UI preference, so it stays out of the project
+        let lock_id = egui::Id::new("engine_ratio_locked");
+        let mut locked = ui.data().get_persisted::<bool>(lock_id).unwrap_or(false);
+        if ui
+            .checkbox(&mut locked, "Lock ratio")
+            .on_hover_text("Keep the ratio of the other two classes when one is changed")
+            .changed()
+        {
+            ui.data().insert_persisted(lock_id, locked);
+        }
+        ui.vertical(|ui| {
+            let modes = [EngineProbMode::Low, EngineProbMode::Mid, EngineProbMode::High];
+            for (i, mode) in modes.into_iter().enumerate() {
+                let label = engine_label(self.toggle_200cc, mode);
+                let mut value = self.engine_probs.values()[i];
+                if ui
+                    .add(Slider::new(&mut value, 0..=ENGINE_TOTAL).text(label).suffix("%"))
+                    .on_hover_text(format!("Probability for {} online", label))
+                    .changed()
+                {
+                    self.engine_probs.set_linked(i, value, locked);
+                }
+            }
+            let total: i32 = self.engine_probs.values().iter().sum();
+            if total != ENGINE_TOTAL {
+                ui.horizontal(|ui| {
+                    ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ Total is {}%", total));
+                    if ui.button("Fix").on_hover_text("Scale to a total of 100%").clicked() {
+                        self.engine_probs.normalize();
+                    }
+                });
+            }
+        });
+    }
+}
+
+/// Option of `key`, disabled when the target build doesn't read it
+fn lpar_option(ui: &mut Ui, target: &LeCodeProfile, key: &LparKey, add_contents: impl FnOnce(&mut Ui)) {
+    let supported = target.supports(key);
+    let response = ui.add_enabled_ui(supported, add_contents).response;
+    if !supported {
+        response.on_hover_text(format!("{}\n\n{}", key.unsupported_note(target), key.note()));
+    }
+}
+
+fn cheat_mode_view(ui: &mut Ui, mode: &mut CheatMode) {
+    ui.horizontal(|ui| {
+        for v in CheatMode::VALUES {
+            ui.selectable_value(mode, v, v.as_str());
+        }
+    });
+}
+
+#[derive(Clone, Copy)]
+pub enum EngineProbMode {
     Low,
     Mid,
     High,
 }
 
-fn engine_label(custom_engine: bool, mode: EngineProbMode) -> &'static str {
+pub fn engine_label(custom_engine: bool, mode: EngineProbMode) -> &'static str {
     match mode {
         EngineProbMode::Low => {
             if custom_engine {
@@ -0,0 +1,203 @@
+use std::path::Path;
+
+use crate::apps::{tracks::Id, MainView, Region, SourceApp, SourceKind};
+use crate::helpers::{
+    disc::DiscImage,
+    file::{file_dialog, remember_dir, DIALOG_SOURCE},
+    game::{ExtractedGame, GameFiles},
+};
+use egui::{Context, TextEdit};
+use egui_extras::{Size, TableBuilder};
+
+pub const COURSE_DIR: &'static str = "Race/Course";
+pub const SCENE_UI_DIR: &'static str = "Scene/UI";
+pub const REL_DIR: &'static str = "rel";
+
+impl Default for SourceApp {
+    fn default() -> Self {
+        Self {
+            open: false,
+            kind: SourceKind::Disc,
+            disc_path: String::new(),
+            folder_path: String::new(),
+            common_key_path: String::new(),
+            lecode_path: String::new(),
+            status: String::new(),
+            presence: vec![],
+        }
+    }
+}
+
+impl MainView for SourceApp {
+    fn name(&self) -> &'static str {
+        "Nintendo Source"
+    }
+
+    fn ui(&mut self, ctx: &Context) {
+        let mut open = self.open;
+        egui::Window::new(self.name())
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                ui.label("Original tracks and menu files are read from your own copy of Mario Kart Wii.");
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.kind, SourceKind::Disc, "Disc image")
+                        .on_hover_text("Read from ISO/WBFS with the common key");
+                    ui.selectable_value(&mut self.kind, SourceKind::Folder, "Extracted folder")
+                        .on_hover_text("Read from the DATA or files folder extracted by Dolphin or Wiimms ISO Tools");
+                });
+                match self.kind {
+                    SourceKind::Disc => {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                TextEdit::singleline(&mut self.disc_path)
+                                    .hint_text("Path to ISO/WBFS")
+                                    .desired_width(400.0),
+                            );
+                            if ui.button("...").on_hover_text("Select a disc image").clicked() {
+                                if let Some(path) = file_dialog(DIALOG_SOURCE)
+                                    .add_filter("Disc image", &["iso", "wbfs"])
+                                    .pick_file()
+                                {
+                                    remember_dir(DIALOG_SOURCE, &path);
+                                    self.disc_path = format!("{}", path.to_str().unwrap());
+                                }
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                TextEdit::singleline(&mut self.common_key_path)
+                                    .hint_text("Path to common key (common-key.bin)")
+                                    .desired_width(400.0),
+                            )
+                            .on_hover_text("Korean discs need the Korean common key instead");
+                            if ui.button("...").on_hover_text("Select a common key file").clicked() {
+                                if let Some(path) = file_dialog(DIALOG_SOURCE).pick_file() {
+                                    remember_dir(DIALOG_SOURCE, &path);
+                                    self.common_key_path = format!("{}", path.to_str().unwrap());
+                                }
+                            }
+                        });
+                    }
+                    SourceKind::Folder => {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                TextEdit::singleline(&mut self.folder_path)
+                                    .hint_text("Path to DATA or files folder")
+                                    .desired_width(400.0),
+                            );
+                            if ui.button("...").on_hover_text("Select an extracted game folder").clicked() {
+                                if let Some(path) = file_dialog(DIALOG_SOURCE).pick_folder() {
+                                    remember_dir(DIALOG_SOURCE, &path);
+                                    self.folder_path = format!("{}", path.to_str().unwrap());
+                                }
+                            }
+                        });
+                    }
+                }
+                ui.separator();
+                ui.label("LE-CODE binaries (lecode-PAL.bin and so on) are read from this folder, or from rel of the source.");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        TextEdit::singleline(&mut self.lecode_path)
+                            .hint_text("Path to the folder with lecode-*.bin")
+                            .desired_width(400.0),
+                    );
+                    if ui.button("...").on_hover_text("Select the folder of the LE-CODE binaries").clicked() {
+                        if let Some(path) = file_dialog(DIALOG_SOURCE).pick_folder() {
+                            remember_dir(DIALOG_SOURCE, &path);
+                            self.lecode_path = format!("{}", path.to_str().unwrap());
+                        }
+                    }
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Check").on_hover_text("Check the region and which original tracks exist").clicked() {
+                        self.check();
+                    }
+                    ui.label(&self.status);
+                });
+                if !self.presence.is_empty() {
+                    ui.separator();
+                    self.gen_tables(ui);
+                }
+            });
+        self.open = open;
+    }
+}
+
+impl SourceApp {
+    pub fn is_set(&self) -> bool {
+        match self.kind {
+            SourceKind::Disc => !self.disc_path.is_empty() && !self.common_key_path.is_empty(),
+            SourceKind::Folder => !self.folder_path.is_empty(),
+        }
+    }
+
+    /// Open the configured source and check that it's Mario Kart Wii
+    pub fn open_files(&self) -> Result<Box<dyn GameFiles>, String> {
+        if !self.is_set() {
+            return Err("Select where to read Nintendo content in Build > Nintendo Source.".to_string());
+        }
+        let files: Box<dyn GameFiles> = match self.kind {
+            SourceKind::Disc => Box::new(DiscImage::open(Path::new(&self.disc_path), Path::new(&self.common_key_path))?),
+            SourceKind::Folder => Box::new(ExtractedGame::open(Path::new(&self.folder_path))?),
+        };
+        if Region::from_game_id(files.game_id()).is_none() {
+            return Err(format!("{} is not Mario Kart Wii.", files.game_id()));
+        }
+        Ok(files)
+    }
+
+    fn check(&mut self) {
+        self.presence.clear();
+        let mut files = match self.open_files() {
+            Ok(v) => v,
+            Err(err) => {
+                self.status = err;
+                return;
+            }
+        };
+
+        let courses = files.list_dir(COURSE_DIR);
+        for id in Id::VALUES {
+            let path = format!("{}/{}.szs", COURSE_DIR, id.course_file());
+            self.presence
+                .push((id, courses.iter().any(|v| v.eq_ignore_ascii_case(&path))));
+        }
+
+        let found = self.presence.iter().filter(|v| v.1).count();
+        let scene_ui = files.list_dir(SCENE_UI_DIR).len();
+        let lecode = files.list_dir(REL_DIR).iter().any(|v| v.to_lowercase().contains("lecode"));
+        self.status = format!(
+            "{} ({}): {}/{} tracks, {} Scene/UI archives, LE-CODE binary {}",
+            files.game_id(),
+            Region::from_game_id(files.game_id()).unwrap().as_str(),
+            found,
+            self.presence.len(),
+            scene_ui,
+            if lecode { "found" } else { "not found" }
+        );
+    }
+
+    fn gen_tables(&mut self, ui: &mut egui::Ui) {
+        let table = TableBuilder::new(ui)
+            .striped(true)
+            .column(Size::initial(220.0).at_least(60.0))
+            .column(Size::remainder().at_least(60.0));
+
+        table.body(|body| {
+            body.rows(17.0, self.presence.len(), |i, mut row| {
+                let (id, found) = self.presence[i];
+                row.col(|ui| {
+                    ui.label(id.as_str());
+                });
+                row.col(|ui| {
+                    ui.label(if found { "Found" } else { "Missing" });
+                });
+            })
+        })
+    }
+}
@@ -0,0 +1,438 @@
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::apps::{
+    tracks::{CupList, Id, Track, TrackDefinition},
+    TrackBrowserApp,
+};
+use egui::{Context, TextEdit};
+use egui_extras::{Size, TableBuilder};
+
+/// How long the browser trusts that track files exist or are missing
+const FILE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Columns of the track browser which it can be sorted by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrackColumn {
+    Name,
+    Author,
+    Property,
+    Music,
+    New,
+    Cup,
+    File,
+}
+
+impl TrackColumn {
+    pub const VALUES: [Self; 7] = [
+        TrackColumn::Name,
+        TrackColumn::Author,
+        TrackColumn::Property,
+        TrackColumn::Music,
+        TrackColumn::New,
+        TrackColumn::Cup,
+        TrackColumn::File,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrackColumn::Name => "Name",
+            TrackColumn::Author => "Author",
+            TrackColumn::Property => "Property",
+            TrackColumn::Music => "Music",
+            TrackColumn::New => "New",
+            TrackColumn::Cup => "Cup",
+            TrackColumn::File => "File",
+        }
+    }
+}
+
+/// Conditions of the column filters, `None` for any value
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackFilter {
+    pub property: Option<Id>,
+    pub music: Option<Id>,
+    pub new: Option<bool>,
+    /// Whether the SZS file exists
+    pub file: Option<bool>,
+    pub list: Option<CupList>,
+}
+
+/// Position of a track in the editor, which is valid as long as `track_layout` stays the same
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackKey {
+    pub list: CupList,
+    pub cup: usize,
+    pub track: usize,
+}
+
+struct Row<'a> {
+    key: TrackKey,
+    track: &'a Track,
+    cup: &'a str,
+    exists: bool,
+}
+
+/// Change to apply to every selected track
+enum BulkEdit {
+    Property(Id),
+    Music(Id),
+    New(bool),
+    Author(String),
+}
+
+impl Default for TrackBrowserApp {
+    fn default() -> Self {
+        Self {
+            open: false,
+            search: String::new(),
+            filter: Default::default(),
+            sort: TrackColumn::Cup,
+            ascending: true,
+            selected: vec![],
+            layout: 0,
+            files: HashMap::new(),
+            files_checked: None,
+            author: String::new(),
+        }
+    }
+}
+
+impl TrackBrowserApp {
+    pub fn ui(&mut self, ctx: &Context, editor: &mut TrackDefinition) {
+        let mut open = self.open;
+        let mut edit: Option<BulkEdit> = None;
+        let mut jump: Option<TrackKey> = None;
+        egui::Window::new("Track Browser")
+            .open(&mut open)
+            .collapsible(false)
+            .default_size([900.0, 520.0])
+            .show(ctx, |ui| {
+                // Keys point to other tracks once tracks are moved, added or removed in the editor
+                let layout = track_layout(editor);
+                if layout != self.layout {
+                    self.selected.clear();
+                    self.layout = layout;
+                }
+                self.view_filters(ui);
+                ui.separator();
+                edit = self.view_bulk_edit(ui);
+                ui.separator();
+                self.check_files(editor);
+                let rows = self.rows(editor);
+                ui.label(format!("{} of {} tracks", rows.len(), count_tracks(editor)));
+                jump = self.gen_table(ui, &rows);
+            });
+        self.open = open;
+
+        if let Some(edit) = edit {
+            for key in &self.selected {
+                let track = &mut editor.list_of_mut(key.list)[key.cup].trackset[key.track];
+                match &edit {
+                    BulkEdit::Property(v) if key.list.accepts(*v) => track.property = *v,
+                    BulkEdit::Property(_) => {}
+                    BulkEdit::Music(v) => track.music = *v,
+                    BulkEdit::New(v) => track.new = *v,
+                    BulkEdit::Author(v) => track.author = v.clone(),
+                }
+            }
+        }
+        if let Some(key) = jump {
+            editor.show_cup(key.list, key.cup, Some(key.track));
+        }
+    }
+
+    fn view_filters(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.add(
+                TextEdit::singleline(&mut self.search)
+                    .hint_text("Search names, authors and files")
+                    .desired_width(240.0),
+            );
+            if ui.button("Clear filters").clicked() {
+                self.search.clear();
+                self.filter = Default::default();
+            }
+        });
+        ui.horizontal_wrapped(|ui| {
+            let f = &mut self.filter;
+            filter_combo(ui, "filter_list", "List", &mut f.list, &CupList::VALUES, |v| v.as_str());
+            filter_combo(ui, "filter_property", "Property", &mut f.property, &Id::VALUES, |v| v.as_str());
+            filter_combo(ui, "filter_music", "Music", &mut f.music, &Id::VALUES, |v| v.as_str());
+            filter_combo(ui, "filter_new", "New", &mut f.new, &[true, false], |v| if *v { "Yes" } else { "No" });
+            filter_combo(ui, "filter_file", "File", &mut f.file, &[true, false], |v| {
+                if *v {
+                    "Found"
+                } else {
+                    "Missing"
+                }
+            });
+        });
+    }
+
+    fn view_bulk_edit(&mut self, ui: &mut egui::Ui) -> Option<BulkEdit> {
+        let mut edit = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.label(format!("{} selected", self.selected.len()));
+            ui.add_enabled_ui(!self.selected.is_empty(), |ui| {
+                // Only properties every selected track can use
+                let lists: Vec<CupList> = CupList::VALUES
+                    .into_iter()
+                    .filter(|list| self.selected.iter().any(|v| v.list == *list))
+                    .collect();
+                ui.menu_button("Set property", |ui| {
+                    egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                        for v in Id::VALUES.into_iter().filter(|v| lists.iter().all(|list| list.accepts(*v))) {
+                            if ui.button(v.as_str()).clicked() {
+                                edit = Some(BulkEdit::Property(v));
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                });
+                ui.menu_button("Set music", |ui| {
+                    egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                        for v in Id::VALUES {
+                            if ui.button(v.as_str()).clicked() {
+                                edit = Some(BulkEdit::Music(v));
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                });
+                if ui.button("Mark new").clicked() {
+                    edit = Some(BulkEdit::New(true));
+                }
+                if ui.button("Unmark new").clicked() {
+                    edit = Some(BulkEdit::New(false));
+                }
+                ui.add(
+                    TextEdit::singleline(&mut self.author)
+                        .hint_text("Author name")
+                        .desired_width(120.0),
+                );
+                if ui.button("Set author").clicked() {
+                    edit = Some(BulkEdit::Author(self.author.clone()));
+                }
+                if ui.button("Deselect").clicked() {
+                    self.selected.clear();
+                }
+            });
+        });
+        edit
+    }
+
+    /// Table of `rows`, which returns the track to show in the editor
+    fn gen_table(&mut self, ui: &mut egui::Ui, rows: &[Row]) -> Option<TrackKey> {
+        let mut jump = None;
+        let all = !rows.is_empty() && rows.iter().all(|v| self.selected.contains(&v.key));
+        let mut table = TableBuilder::new(ui)
+            .striped(true)
+            .column(Size::exact(24.0))
+            .column(Size::initial(200.0).at_least(80.0))
+            .column(Size::initial(100.0).at_least(60.0));
+        for _ in 0..2 {
+            table = table.column(Size::initial(150.0).at_least(80.0));
+        }
+        table
+            .column(Size::exact(40.0))
+            .column(Size::initial(140.0).at_least(60.0))
+            .column(Size::exact(40.0))
+            .column(Size::exact(24.0))
+            .header(20.0, |mut header| {
+                header.col(|ui| {
+                    let mut checked = all;
+                    if ui.checkbox(&mut checked, "").on_hover_text("Select all shown tracks").changed() {
+                        if checked {
+                            let added: Vec<TrackKey> =
+                                rows.iter().map(|v| v.key).filter(|v| !self.selected.contains(v)).collect();
+                            self.selected.extend(added);
+                        } else {
+                            self.selected.retain(|key| !rows.iter().any(|v| v.key == *key));
+                        }
+                    }
+                });
+                for column in TrackColumn::VALUES {
+                    header.col(|ui| {
+                        let arrow = match (self.sort == column, self.ascending) {
+                            (false, _) => "",
+                            (true, true) => " ⏶",
+                            (true, false) => " ⏷",
+                        };
+                        if ui.selectable_label(self.sort == column, format!("{}{}", column.as_str(), arrow)).clicked() {
+                            if self.sort == column {
+                                self.ascending = !self.ascending;
+                            } else {
+                                self.sort = column;
+                                self.ascending = true;
+                            }
+                        }
+                    });
+                }
+                header.col(|_| {});
+            })
+            .body(|body| {
+                body.rows(20.0, rows.len(), |i, mut table_row| {
+                    let row = &rows[i];
+                    table_row.col(|ui| {
+                        let mut checked = self.selected.contains(&row.key);
+                        if ui.checkbox(&mut checked, "").changed() {
+                            if checked {
+                                self.selected.push(row.key);
+                            } else {
+                                self.selected.retain(|v| *v != row.key);
+                            }
+                        }
+                    });
+                    table_row.col(|ui| {
+                        ui.label(&row.track.name);
+                    });
+                    table_row.col(|ui| {
+                        ui.label(&row.track.author);
+                    });
+                    table_row.col(|ui| {
+                        ui.label(row.track.property.as_str());
+                    });
+                    table_row.col(|ui| {
+                        ui.label(row.track.music.as_str());
+                    });
+                    table_row.col(|ui| {
+                        ui.label(if row.track.new { "✔" } else { "" });
+                    });
+                    table_row.col(|ui| {
+                        ui.label(row.cup);
+                    });
+                    table_row.col(|ui| {
+                        if row.exists {
+                            ui.label("✔").on_hover_text(&row.track.filename);
+                        } else if row.track.filename.is_empty() {
+                            ui.colored_label(ui.visuals().error_fg_color, "✖").on_hover_text("No SZS file");
+                        } else {
+                            ui.colored_label(ui.visuals().error_fg_color, "✖")
+                                .on_hover_text(format!("Not found: {}", row.track.filename));
+                        }
+                    });
+                    table_row.col(|ui| {
+                        if ui.small_button("➡").on_hover_text("Show in the cup").clicked() {
+                            jump = Some(row.key);
+                        }
+                    });
+                });
+            });
+        jump
+    }
+
+    /// Look up whether the track files exist, again after `FILE_CHECK_INTERVAL`
+    fn check_files(&mut self, editor: &TrackDefinition) {
+        if self.files_checked.map_or(true, |v| v.elapsed() >= FILE_CHECK_INTERVAL) {
+            self.files.clear();
+            self.files_checked = Some(Instant::now());
+        }
+        let tracks = editor.cups.iter().chain(&editor.battle_cups).flat_map(|v| v.trackset.iter());
+        for track in tracks {
+            if !self.files.contains_key(&track.filename) {
+                let exists = !track.filename.is_empty() && Path::new(&track.filename).is_file();
+                self.files.insert(track.filename.clone(), exists);
+            }
+        }
+    }
+
+    /// Tracks of both lists which pass the search and the filters, in the sort order
+    fn rows<'a>(&self, editor: &'a TrackDefinition) -> Vec<Row<'a>> {
+        let search = self.search.to_lowercase();
+        let f = &self.filter;
+        let mut rows: Vec<Row> = vec![];
+        for (list, cups) in [(CupList::Racing, &editor.cups), (CupList::Battle, &editor.battle_cups)] {
+            for (cup_index, cup) in cups.iter().enumerate() {
+                for (i, track) in cup.trackset.iter().enumerate() {
+                    let matched = search.is_empty()
+                        || [&track.name, &track.author, &track.filename]
+                            .iter()
+                            .any(|v| v.to_lowercase().contains(&search));
+                    let exists = self.files.get(&track.filename).copied().unwrap_or(false);
+                    if !matched
+                        || f.list.map_or(false, |v| v != list)
+                        || f.property.map_or(false, |v| v != track.property)
+                        || f.music.map_or(false, |v| v != track.music)
+                        || f.new.map_or(false, |v| v != track.new)
+                        || f.file.map_or(false, |v| v != exists)
+                    {
+                        continue;
+                    }
+                    rows.push(Row {
+                        key: TrackKey {
+                            list,
+                            cup: cup_index,
+                            track: i,
+                        },
+                        track,
+                        cup: &cup.name,
+                        exists,
+                    });
+                }
+            }
+        }
+
+        let order = |a: &Row, b: &Row| -> Ordering {
+            match self.sort {
+                TrackColumn::Name => a.track.name.to_lowercase().cmp(&b.track.name.to_lowercase()),
+                TrackColumn::Author => a.track.author.to_lowercase().cmp(&b.track.author.to_lowercase()),
+                TrackColumn::Property => (a.track.property as u8).cmp(&(b.track.property as u8)),
+                TrackColumn::Music => (a.track.music as u8).cmp(&(b.track.music as u8)),
+                TrackColumn::New => a.track.new.cmp(&b.track.new),
+                TrackColumn::Cup => {
+                    let position = |v: &Row| (v.key.list as u8, v.key.cup, v.key.track);
+                    position(a).cmp(&position(b))
+                }
+                TrackColumn::File => a.exists.cmp(&b.exists),
+            }
+        };
+        // Stable, so equal rows stay in the order of the cups
+        rows.sort_by(|a, b| if self.ascending { order(a, b) } else { order(b, a) });
+        rows
+    }
+}
+
+/// ComboBox of `values` with "Any" for `None`
+fn filter_combo<T: Copy + PartialEq>(
+    ui: &mut egui::Ui,
+    id: &str,
+    label: &str,
+    value: &mut Option<T>,
+    values: &[T],
+    name: impl Fn(&T) -> &'static str,
+) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(format!("{}: {}", label, value.as_ref().map_or("Any", &name)))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(value, None, "Any");
+            for v in values {
+                ui.selectable_value(value, Some(*v), name(v));
+            }
+        });
+}
+
+/// Hash of the cups and what tells their tracks apart, which changes when a track moves to another position
+fn track_layout(editor: &TrackDefinition) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for cups in [&editor.cups, &editor.battle_cups] {
+        cups.len().hash(&mut hasher);
+        for cup in cups {
+            cup.trackset.len().hash(&mut hasher);
+            for track in &cup.trackset {
+                track.name.hash(&mut hasher);
+                track.filename.hash(&mut hasher);
+            }
+        }
+    }
+    hasher.finish()
+}
+
+fn count_tracks(editor: &TrackDefinition) -> usize {
+    editor.cups.iter().chain(&editor.battle_cups).map(|v| v.trackset.len()).sum()
+}
@@ -1,35 +1,282 @@
-use std::{vec, sync::Arc, ops::DerefMut};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    hash::{Hash, Hasher},
+    vec, sync::Arc, ops::{DerefMut, Range},
+};
 
-use egui::{Align, Layout};
+use egui::{Align, Layout, Rect, Sense};
 use egui_extras::{RetainedImage, Size, TableBuilder};
+use image::{imageops::FilterType, RgbaImage};
 
-use super::{ElementView, MainView, TrackDefApp};
+use super::{ElementView, Language, MainView, TrackDefApp};
+use crate::helpers::{
+    build::{FIRST_CUSTOM_SLOT, FIRST_RANDOM_SLOT},
+    parser::resize_image,
+    profile::{self, LeCodeLimits},
+    template::{self, NameTemplate},
+    texture::{quantize, TextureFormat},
+};
 
 pub struct TrackDefinition {
     pub mode: CupSettings,
+    /// Index in the list being edited
     pub selected: usize,
+    /// List being edited
+    pub list: CupList,
+    /// Racing cups
     pub cups: Vec<Cup>,
+    /// Battle cups, empty to keep the original arenas
+    pub battle_cups: Vec<Cup>,
+    /// How track names are written into the game and the definition file
+    pub template: NameTemplate,
+    /// Cup of random slots, shown with `CupSettings::wiimm_cup`
+    pub wiimm: WiimmCup,
+    /// Item currently dragged in the cup list or the track list
+    pub(crate) dragging: Option<DragItem>,
+    /// Track of the selected cup to outline, set by jumping from a problem
+    pub(crate) highlight: Option<usize>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum DragItem {
+    Cup(usize),
+    /// Cup index and slot in the cup
+    Track(usize, usize),
+}
+
+/// Cup lists of LE-CODE, shown in separate menus of the game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CupList {
+    Racing,
+    Battle,
+}
+
+impl CupList {
+    pub const VALUES: [Self; 2] = [CupList::Racing, CupList::Battle];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CupList::Racing => "Racing",
+            CupList::Battle => "Battle",
+        }
+    }
+
+    /// Number of tracks LE-CODE shows in one cup of the list
+    pub fn tracks_per_cup(&self) -> usize {
+        match self {
+            CupList::Racing => 4,
+            CupList::Battle => 5,
+        }
+    }
+
+    /// Whether a track of the list can use `id` as its property
+    pub fn accepts(&self, id: Id) -> bool {
+        match self {
+            CupList::Racing => !id.is_arena() && id != Id::GalaxyArena,
+            CupList::Battle => id.is_arena(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
 pub struct CupSettings {
     // They could be an enum?
     pub nintendo: bool,
     pub nin_swap: bool,
     pub wiimm_cup: bool,
+    /// Texture format of the cup icons in the menu
+    pub icon_format: TextureFormat,
+}
+
+/// Tracks LE-CODE picks from for a random slot of the Wiimm cup
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RandomPool {
+    All,
+    Original,
+    Custom,
+    New,
+}
+
+impl RandomPool {
+    pub const VALUES: [Self; 4] = [RandomPool::All, RandomPool::Original, RandomPool::Custom, RandomPool::New];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RandomPool::All => "All",
+            RandomPool::Original => "Original",
+            RandomPool::Custom => "Custom",
+            RandomPool::New => "New",
+        }
+    }
+
+    /// Name of the slot in the game
+    pub fn default_name(&self) -> &'static str {
+        match self {
+            RandomPool::All => "Random: All Tracks",
+            RandomPool::Original => "Random: Original Tracks",
+            RandomPool::Custom => "Random: Custom Tracks",
+            RandomPool::New => "Random: New Tracks",
+        }
+    }
+
+    /// Slot which LE-CODE reserves for the random pick
+    pub fn slot(&self) -> usize {
+        FIRST_RANDOM_SLOT + *self as usize
+    }
+
+    pub fn from_u8(n: u8) -> Option<Self> {
+        Self::VALUES.get(n as usize).copied()
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RandomSlot {
+    pub pool: RandomPool,
+    pub shown: bool,
+    /// Name in the game, or the default name if empty
+    pub name: String,
+}
+
+impl RandomSlot {
+    pub fn name(&self) -> &str {
+        if self.name.is_empty() {
+            self.pool.default_name()
+        } else {
+            &self.name
+        }
+    }
+}
+
+/// Cup of the random slots, one for each of `RandomPool`
+#[derive(Debug, Clone, PartialEq)]
+pub struct WiimmCup {
+    pub name: String,
+    pub icon: Icon,
+    pub slots: Vec<RandomSlot>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Cup {
     /// path to image
     pub icon: Icon,
     pub name: String,
+    /// Names which differ from `name` in some languages
+    pub names: BTreeMap<Language, String>,
     pub trackset: Vec<Track>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Icon {
     pub filename: String,
+    /// Icon in the menu size as PNG
     pub image: Vec<u8>,
+    /// Selected image file as it is, to resize again with other options
+    pub source: Vec<u8>,
+    /// Save `source` in the project
+    pub keep_source: bool,
+    pub fit: IconFit,
+    pub filter: IconFilter,
+}
+
+/// How an image which is not square becomes the icon
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IconFit {
+    /// Cut off the sides to make it square
+    Crop,
+    /// Keep the whole image and leave the rest transparent
+    Fit,
+    /// Stretch the image to the square
+    Fill,
+}
+
+impl IconFit {
+    pub const VALUES: [Self; 3] = [IconFit::Crop, IconFit::Fit, IconFit::Fill];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IconFit::Crop => "Crop",
+            IconFit::Fit => "Fit",
+            IconFit::Fill => "Fill",
+        }
+    }
+
+    pub fn from_u8(n: u8) -> Option<Self> {
+        Self::VALUES.get(n as usize).copied()
+    }
+}
+
+/// Resampling filter used when resizing the icon
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IconFilter {
+    Nearest,
+    Bilinear,
+    Bicubic,
+    Mitchell,
+    Lanczos3,
+}
+
+impl IconFilter {
+    pub const VALUES: [Self; 5] = [
+        IconFilter::Nearest,
+        IconFilter::Bilinear,
+        IconFilter::Bicubic,
+        IconFilter::Mitchell,
+        IconFilter::Lanczos3,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IconFilter::Nearest => "Nearest (pixel art)",
+            IconFilter::Bilinear => "Bilinear",
+            IconFilter::Bicubic => "Bicubic",
+            IconFilter::Mitchell => "Mitchell",
+            IconFilter::Lanczos3 => "Lanczos3",
+        }
+    }
+
+    pub fn from_u8(n: u8) -> Option<Self> {
+        Self::VALUES.get(n as usize).copied()
+    }
+
+    pub fn kind(&self) -> resize::Type {
+        match self {
+            IconFilter::Nearest => resize::Type::Point,
+            IconFilter::Bilinear => resize::Type::Triangle,
+            IconFilter::Bicubic => resize::Type::Catrom,
+            IconFilter::Mitchell => resize::Type::Mitchell,
+            IconFilter::Lanczos3 => resize::Type::Lanczos3,
+        }
+    }
+}
+
+/// Width and height of a cup icon in the menu
+pub const ICON_SIZE: u32 = 128;
+/// Track thumbnails fill a quarter of a generated icon
+pub const THUMBNAIL_SIZE: u32 = ICON_SIZE / 2;
+
+impl Icon {
+    /// Icon in the size used in the menu, transparent if no image is set
+    pub fn to_rgba(&self) -> RgbaImage {
+        match image::load_from_memory(&self.image) {
+            Ok(v) => v.resize_exact(ICON_SIZE, ICON_SIZE, FilterType::Triangle).to_rgba8(),
+            Err(_) => RgbaImage::new(ICON_SIZE, ICON_SIZE),
+        }
+    }
+
+    /// Make the icon again from `source` with the current options
+    pub fn update(&mut self) -> Result<(), String> {
+        if self.source.is_empty() {
+            return Err("The original image is not kept.".to_string());
+        }
+        self.image = resize_image(&self.source, ICON_SIZE, self.fit, self.filter)?;
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.filename = "".to_string();
+        self.image = vec![];
+        self.source = vec![];
+    }
 }
 
 impl Default for Icon {
@@ -37,30 +284,63 @@ impl Default for Icon {
         Self {
             filename: Default::default(),
             image: Default::default(),
+            source: Default::default(),
+            keep_source: true,
+            fit: IconFit::Crop,
+            filter: IconFilter::Lanczos3,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Track {
     // Doesn't affect to LE-BIN
     pub id: usize,
     pub name: String,
+    /// Names which differ from `name` in some languages
+    pub names: BTreeMap<Language, String>,
+    /// Put before the name by the name template, like `Wii`
+    pub prefix: String,
+    /// Version of the track file, like `v1.2`
+    pub version: String,
     pub author: String,
     pub property: Id,
     pub music: Id,
     pub new: bool,
     pub flag: GroupFlag,
     pub filename: String,
+    /// Picture of the track as PNG, for generated cup icons
+    pub thumbnail: Vec<u8>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum GroupFlag {
     None,
     Header,
     Child,
 }
 
+impl GroupFlag {
+    pub const VALUES: [Self; 3] = [GroupFlag::None, GroupFlag::Header, GroupFlag::Child];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroupFlag::None => "Single track",
+            GroupFlag::Header => "Group header",
+            GroupFlag::Child => "Group member",
+        }
+    }
+
+    pub fn from_u8(n: u8) -> Option<Self> {
+        match n {
+            0 => Some(GroupFlag::None),
+            1 => Some(GroupFlag::Header),
+            2 => Some(GroupFlag::Child),
+            _ => None,
+        }
+    }
+}
+
 impl TrackDefinition {
     fn gen_tables(&mut self, ui: &mut egui::Ui) {
         let table = TableBuilder::new(ui)
@@ -68,20 +348,453 @@ impl TrackDefinition {
             .cell_layout(Layout::left_to_right(Align::Center))
             .column(Size::remainder().at_least(60.0));
 
+        let mut rows: Vec<(usize, Rect)> = vec![];
+        let selected = self.selected;
+        let dragging = &mut self.dragging;
+        let cups = match self.list {
+            CupList::Racing => &mut self.cups,
+            CupList::Battle => &mut self.battle_cups,
+        };
+        let mut clicked = None;
         table.body(|mut body| {
-            self.cups.iter_mut().enumerate().for_each(|(i, x)| {
+            cups.iter_mut().enumerate().for_each(|(i, x)| {
                 body.row(17.0, |mut row| {
                     row.col(|ui| {
-                        if ui
-                            .add_sized(ui.available_size(), egui::SelectableLabel::new(i == self.selected, &x.name))
-                            .clicked()
-                        {
-                            self.selected = i;
+                        let response = ui
+                            .add_sized(ui.available_size(), egui::SelectableLabel::new(i == selected, &x.name))
+                            .interact(Sense::drag())
+                            .on_hover_text("Drag to reorder the cup, or drop a track here to move it into the cup");
+                        if response.clicked() {
+                            clicked = Some(i);
                         }
+                        if response.drag_started() {
+                            *dragging = Some(DragItem::Cup(i));
+                        }
+                        rows.push((i, response.rect));
                     });
                 })
             })
-        })
+        });
+        if let Some(i) = clicked {
+            self.selected = i;
+            self.highlight = None;
+        }
+
+        if let Some(item) = self.dragging {
+            if let Some(&(target, rect)) = rows.iter().find(|(_, rect)| pointer_in(ui, *rect)) {
+                ui.painter()
+                    .rect_stroke(rect, 0.0, ui.visuals().selection.stroke);
+                if ui.input().pointer.any_released() {
+                    match item {
+                        DragItem::Cup(from) => self.move_cup(from, target),
+                        DragItem::Track(cup, slot) => self.move_track_to_cup(cup, slot, target),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Editor of the name template, with the fields and colours to insert
+    fn view_template(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Name template");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.template.0)
+                    .hint_text(template::DEFAULT_TEMPLATE)
+                    .desired_width(300.0),
+            )
+            .on_hover_text("Track names in the game and the definition file.\nEmpty brackets and extra spaces are removed.");
+            ui.menu_button("Insert", |ui| {
+                ui.label("Fields");
+                for v in template::FIELDS {
+                    if ui.button(v).clicked() {
+                        self.template.0.push_str(&format!("{{{}}}", v));
+                        ui.close_menu();
+                    }
+                }
+                ui.separator();
+                ui.label("Colours");
+                for v in &template::COLOURS {
+                    if ui.button(egui::RichText::new(v.name).color(v.preview)).clicked() {
+                        self.template.0.push_str(&format!("{{{}}}", v.name));
+                        ui.close_menu();
+                    }
+                }
+            });
+            if let Err(e) = self.template.check() {
+                ui.colored_label(ui.visuals().warn_fg_color, "⚠").on_hover_text(e);
+            }
+        });
+        ui.add_space(4.0);
+    }
+
+    /// Track list of the selected cup with reordering controls
+    fn view_selected(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        let selected = self.selected;
+        let list = self.list;
+        if self.list_ref().is_empty() {
+            ui.label(format!("There are no {} cups. Add one in the cup list.", list.as_str().to_lowercase()));
+            return;
+        }
+        let cup_names: Vec<String> = self.list_ref().iter().map(|v| v.name.clone()).collect();
+        let mut command: Option<TrackCommand> = None;
+        let mut rows: Vec<(usize, Rect)> = vec![];
+
+        let format = self.mode.icon_format;
+        let highlight = self.highlight;
+        let template = self.template.clone();
+        let cup = &mut self.list_mut()[selected];
+        let count = cup.trackset.len();
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                cup.view(ctx, ui);
+                cup.view_icon_preview(ctx, ui, format);
+            });
+            let groups = cup.groups();
+            let tracks = &mut cup.trackset;
+            let mut row = |ui: &mut egui::Ui, i: usize, track: &mut Track| {
+                let rect = ui
+                    .horizontal(|ui| {
+                        ui.vertical(|ui| {
+                            if ui
+                                .add(egui::Label::new("☰").sense(Sense::drag()))
+                                .on_hover_text("Drag to reorder, or drop on a cup in the list")
+                                .drag_started()
+                            {
+                                command = Some(TrackCommand::Drag(i));
+                            }
+                            if ui
+                                .add_enabled(i > 0, egui::Button::new("⬆"))
+                                .on_hover_text("Move up")
+                                .clicked()
+                            {
+                                command = Some(TrackCommand::Move(i, i - 1));
+                            }
+                            if ui
+                                .add_enabled(i + 1 < count, egui::Button::new("⬇"))
+                                .on_hover_text("Move down")
+                                .clicked()
+                            {
+                                command = Some(TrackCommand::Move(i, i + 1));
+                            }
+                            ui.menu_button("➡", |ui| {
+                                for (n, name) in cup_names.iter().enumerate() {
+                                    if n != selected && ui.button(name).clicked() {
+                                        command = Some(TrackCommand::MoveToCup(i, n));
+                                        ui.close_menu();
+                                    }
+                                }
+                            })
+                            .response
+                            .on_hover_text("Move to cup…");
+                            if ui
+                                .add_enabled(count > 1, egui::Button::new("🗑"))
+                                .on_hover_text("Remove the track from the cup")
+                                .clicked()
+                            {
+                                command = Some(TrackCommand::Remove(i));
+                            }
+                            if track.flag == GroupFlag::Header
+                                && ui
+                                    .button("➕")
+                                    .on_hover_text("Add a track to the group")
+                                    .clicked()
+                            {
+                                command = Some(TrackCommand::AddChild(i));
+                            }
+                        });
+                        track.view_in(ctx, ui, list, &template);
+                    })
+                    .response
+                    .rect;
+                if highlight == Some(i) {
+                    ui.painter()
+                        .rect_stroke(rect, 2.0, egui::Stroke::new(2.0, ui.visuals().warn_fg_color));
+                }
+                rows.push((i, rect));
+            };
+            for range in groups {
+                if tracks[range.start].flag != GroupFlag::Header {
+                    row(ui, range.start, &mut tracks[range.start]);
+                    continue;
+                }
+                let title = format!(
+                    "🎲 {} (group of {} tracks)",
+                    tracks[range.start].name,
+                    range.len()
+                );
+                egui::CollapsingHeader::new(title)
+                    .id_source(("track_group", list.as_str(), selected, range.start))
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        for i in range.clone() {
+                            row(ui, i, &mut tracks[i]);
+                        }
+                    })
+                    .header_response
+                    .on_hover_text("LE-CODE picks one track of the group at random");
+            }
+            if ui
+                .button("Add track")
+                .on_hover_text(format!(
+                    "{} cups have {} tracks in the game",
+                    list.as_str(),
+                    list.tracks_per_cup()
+                ))
+                .clicked()
+            {
+                command = Some(TrackCommand::Add);
+            }
+        });
+
+        if let Some(DragItem::Track(cup, from)) = self.dragging {
+            if let Some(&(target, rect)) = rows.iter().find(|(_, rect)| pointer_in(ui, *rect)) {
+                ui.painter()
+                    .rect_stroke(rect, 0.0, ui.visuals().selection.stroke);
+                if cup == selected && ui.input().pointer.any_released() {
+                    command = Some(TrackCommand::Move(from, target));
+                }
+            }
+        }
+
+        match command {
+            Some(TrackCommand::Drag(i)) => self.dragging = Some(DragItem::Track(selected, i)),
+            Some(TrackCommand::Move(from, to)) => self.move_track(selected, from, to),
+            Some(TrackCommand::MoveToCup(i, cup)) => self.move_track_to_cup(selected, i, cup),
+            Some(TrackCommand::Add) => {
+                let track = Track::default_in(list);
+                self.list_mut()[selected].trackset.push(track);
+                self.renumber();
+            }
+            Some(TrackCommand::AddChild(i)) => {
+                let cup = &mut self.list_mut()[selected];
+                if let Some(range) = cup.groups().into_iter().find(|v| v.start == i) {
+                    let header = &cup.trackset[i];
+                    let track = Track {
+                        property: header.property,
+                        music: header.music,
+                        flag: GroupFlag::Child,
+                        ..Default::default()
+                    };
+                    cup.trackset.insert(range.end, track);
+                    self.renumber();
+                }
+            }
+            Some(TrackCommand::Remove(i)) => {
+                let trackset = &mut self.list_mut()[selected].trackset;
+                let removed = trackset.remove(i);
+                // The next member leads the group instead
+                if removed.flag == GroupFlag::Header {
+                    if let Some(next) = trackset.get_mut(i).filter(|v| v.flag == GroupFlag::Child) {
+                        next.flag = GroupFlag::Header;
+                    }
+                }
+                self.renumber();
+            }
+            None => {}
+        }
+    }
+
+    /// Cups of the list being edited
+    pub fn list_ref(&self) -> &Vec<Cup> {
+        self.list_of(self.list)
+    }
+
+    pub fn list_mut(&mut self) -> &mut Vec<Cup> {
+        self.list_of_mut(self.list)
+    }
+
+    pub fn list_of(&self, list: CupList) -> &Vec<Cup> {
+        match list {
+            CupList::Racing => &self.cups,
+            CupList::Battle => &self.battle_cups,
+        }
+    }
+
+    pub fn list_of_mut(&mut self, list: CupList) -> &mut Vec<Cup> {
+        match list {
+            CupList::Racing => &mut self.cups,
+            CupList::Battle => &mut self.battle_cups,
+        }
+    }
+
+    /// Switch the list being edited
+    pub fn select_list(&mut self, list: CupList) {
+        if self.list != list {
+            self.list = list;
+            self.selected = 0;
+            self.dragging = None;
+            self.highlight = None;
+        }
+    }
+
+    /// Select the `cup`th cup of `list`, outlining its `track`th track if given
+    pub fn show_cup(&mut self, list: CupList, cup: usize, track: Option<usize>) {
+        self.select_list(list);
+        if cup < self.list_ref().len() {
+            self.selected = cup;
+        }
+        self.highlight = track;
+    }
+
+    /// Group members must follow a header, and a header needs members
+    pub fn check_groups(&self) -> Vec<String> {
+        let mut problems: Vec<String> = vec![];
+        for cup in self.cups.iter().chain(&self.battle_cups) {
+            for range in cup.groups() {
+                let first = &cup.trackset[range.start];
+                if first.flag == GroupFlag::Child {
+                    problems.push(format!(
+                        "\"{}\" in {} is a group member without a group header before it.",
+                        first.name, cup.name
+                    ));
+                } else if first.flag == GroupFlag::Header && range.len() == 1 {
+                    problems.push(format!("Group \"{}\" in {} has no members.", first.name, cup.name));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Names of the tracks LE-CODE picks from for `pool`, one for each slot.
+    /// Groups count once, by the name of their header.
+    pub fn random_pool(&self, pool: RandomPool) -> Vec<String> {
+        let original = || {
+            Id::VALUES
+                .into_iter()
+                .filter(|v| CupList::Racing.accepts(*v))
+                .map(|v| v.as_str().to_string())
+        };
+        let custom = |new_only: bool| -> Vec<String> {
+            self.cups
+                .iter()
+                .flat_map(|cup| cup.groups().into_iter().map(move |v| &cup.trackset[v.start]))
+                .filter(|v| !new_only || v.new)
+                .map(|v| self.template.to_plain(v, None))
+                .collect()
+        };
+        match pool {
+            RandomPool::All => original().chain(custom(false)).collect(),
+            RandomPool::Original => original().collect(),
+            RandomPool::Custom => custom(false),
+            RandomPool::New => custom(true),
+        }
+    }
+
+    /// Problems which LE-CODE can't build, for each list
+    pub fn check_limits(&self, limits: &LeCodeLimits) -> Vec<String> {
+        let mut problems: Vec<String> = vec![];
+        if self.cups.is_empty() {
+            problems.push("At least one racing cup is needed.".to_string());
+        }
+        for (list, cups, max) in [
+            (CupList::Racing, &self.cups, limits.racing_cups),
+            (CupList::Battle, &self.battle_cups, limits.battle_cups),
+        ] {
+            if cups.len() > max {
+                problems.push(format!(
+                    "{} {} cups are more than the {} the target LE-CODE supports.",
+                    cups.len(),
+                    list.as_str().to_lowercase(),
+                    max
+                ));
+            }
+            for cup in cups {
+                let slots = cup.groups();
+                if slots.len() != list.tracks_per_cup() {
+                    problems.push(format!(
+                        "{} cup \"{}\" has {} tracks, but needs {}.",
+                        list.as_str(),
+                        cup.name,
+                        slots.len(),
+                        list.tracks_per_cup()
+                    ));
+                }
+                for track in cup.trackset.iter().filter(|v| !list.accepts(v.property)) {
+                    problems.push(format!(
+                        "\"{}\" in {} can't use {} as property in a {} cup.",
+                        track.name,
+                        cup.name,
+                        track.property.as_str(),
+                        list.as_str().to_lowercase()
+                    ));
+                }
+            }
+        }
+        problems.append(&mut self.check_groups());
+        let tracks: usize = self.cups.iter().chain(&self.battle_cups).map(|v| v.trackset.len()).sum();
+        if FIRST_CUSTOM_SLOT + tracks > limits.slots {
+            problems.push(format!(
+                "{} tracks are more than the {} slots the target LE-CODE has for custom tracks.",
+                tracks,
+                limits.slots.saturating_sub(FIRST_CUSTOM_SLOT)
+            ));
+        }
+        problems
+    }
+
+    pub fn move_cup(&mut self, from: usize, to: usize) {
+        let cups = self.list_mut();
+        if from == to || from >= cups.len() || to >= cups.len() {
+            return;
+        }
+        let cup = cups.remove(from);
+        cups.insert(to, cup);
+        let selected_is_moved = self.selected == from;
+        if selected_is_moved {
+            self.selected = to;
+        } else if from < self.selected && self.selected <= to {
+            self.selected -= 1;
+        } else if to <= self.selected && self.selected < from {
+            self.selected += 1;
+        }
+        self.renumber();
+    }
+
+    /// Reorder a track inside a cup
+    pub fn move_track(&mut self, cup: usize, from: usize, to: usize) {
+        let trackset = &mut self.list_mut()[cup].trackset;
+        if from == to || from >= trackset.len() || to >= trackset.len() {
+            return;
+        }
+        let track = trackset.remove(from);
+        trackset.insert(to, track);
+        self.renumber();
+    }
+
+    /// Move a track to the end of another cup in the same list
+    pub fn move_track_to_cup(&mut self, cup: usize, slot: usize, target: usize) {
+        let cups = self.list_mut();
+        if cup == target || target >= cups.len() || slot >= cups[cup].trackset.len() {
+            return;
+        }
+        let track = cups[cup].trackset.remove(slot);
+        cups[target].trackset.push(track);
+        self.renumber();
+    }
+
+    /// Assign `Track::id` from the position, counting racing cups first and then battle cups
+    pub fn renumber(&mut self) {
+        let tracks = self.cups.iter_mut().chain(self.battle_cups.iter_mut()).flat_map(|v| v.trackset.iter_mut());
+        for (i, track) in tracks.enumerate() {
+            track.id = i;
+        }
+    }
+}
+
+enum TrackCommand {
+    Drag(usize),
+    Move(usize, usize),
+    MoveToCup(usize, usize),
+    Add,
+    AddChild(usize),
+    Remove(usize),
+}
+
+fn pointer_in(ui: &egui::Ui, rect: Rect) -> bool {
+    match ui.ctx().pointer_hover_pos() {
+        Some(pos) => rect.contains(pos),
+        None => false,
     }
 }
 
@@ -96,6 +809,13 @@ impl MainView for TrackDefApp {
             .resizable(false)
             .show(ctx, |ui| {
                 ui.heading("Cup Listing");
+                ui.horizontal(|ui| {
+                    for v in CupList::VALUES {
+                        if ui.selectable_label(self.editor.list == v, v.as_str()).clicked() {
+                            self.editor.select_list(v);
+                        }
+                    }
+                });
                 ui.horizontal(|ui| {
                     egui::ComboBox::from_id_source("cup_special_settings")
                         .selected_text("Cup Setup")
@@ -108,6 +828,14 @@ impl MainView for TrackDefApp {
                             });
                             ui.toggle_value(&mut self.editor.mode.wiimm_cup, "Wiimm Cup")
                                 .on_hover_text("Allow to add the randomize cup");
+                            if ui.button("Wiimm Cup Setup…").on_hover_text("Random slots, name and icon of the Wiimm cup").clicked() {
+                                self.wiimm.open = true;
+                            }
+                            ui.separator();
+                            ui.label("Icon format");
+                            for v in TextureFormat::VALUES {
+                                ui.selectable_value(&mut self.editor.mode.icon_format, v, v.as_str());
+                            }
                         });
                     ui.with_layout(Layout::right_to_left(egui::Align::Min), |ui| {
                         if ui
@@ -116,7 +844,9 @@ impl MainView for TrackDefApp {
                             .clicked()
                         {
                             println!("Cup:Delete");
-                            if self.editor.cups.len() <= 1 {
+                            // Only the battle list can be empty
+                            let min = if self.editor.list == CupList::Racing { 1 } else { 0 };
+                            if self.editor.list_ref().len() <= min {
                                 return;
                             }
                             let selected = self.editor.selected;
@@ -125,20 +855,55 @@ impl MainView for TrackDefApp {
                             } else {
                                 self.editor.selected - 1
                             };
-                            self.editor.cups.remove(selected);
+                            self.editor.list_mut().remove(selected);
+                            self.editor.renumber();
                         }
                         if ui.button("Add").on_hover_text("Add a new cup").clicked() {
-                            self.editor.cups.push(Cup::default(self.editor.cups.len()));
+                            let cup = match self.editor.list {
+                                CupList::Racing => Cup::default(self.editor.cups.len()),
+                                CupList::Battle => Cup::battle(self.editor.battle_cups.len()),
+                            };
+                            self.editor.list_mut().push(cup);
+                            self.editor.renumber();
+                        }
+                        if ui.button("Icons").on_hover_text("Generate cup icons").clicked() {
+                            self.generator.open = true;
+                        }
+                        if ui.button("Names").on_hover_text("Translate cup and track names").clicked() {
+                            self.translations.open = true;
+                        }
+                        if ui.button("Preview").on_hover_text("Show the cups as the game lays them out").clicked() {
+                            self.preview.open = true;
                         }
                     });
                 });
                 ui.separator();
+                let problems = self.editor.check_limits(&profile::profile(self.lecode_build).limits);
+                if !problems.is_empty() {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!("⚠ {} problems with LE-CODE limits", problems.len()),
+                    )
+                    .on_hover_text(problems.join("\n"));
+                    ui.separator();
+                }
                 self.editor.gen_tables(ui);
             });
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading(self.name());
-            self.editor.cups[self.editor.selected].view(ctx, ui);
+            self.editor.view_template(ui);
+            self.editor.view_selected(ctx, ui);
         });
+
+        self.generator.ui(ctx, &mut self.editor);
+        self.translations.ui(ctx, &mut self.editor);
+        self.wiimm.ui(ctx, &mut self.editor);
+        self.preview.ui(ctx, &mut self.editor);
+        self.browser.ui(ctx, &mut self.editor);
+
+        if ctx.input().pointer.any_released() {
+            self.editor.dragging = None;
+        }
     }
 }
 
@@ -146,40 +911,130 @@ impl Default for TrackDefinition {
     fn default() -> Self {
         Self {
             selected: 0,
+            list: CupList::Racing,
             cups: vec![Cup::default(0)],
-            mode: CupSettings {
-                nintendo: true,
-                nin_swap: true,
-                wiimm_cup: true,
-            },
+            battle_cups: vec![],
+            template: Default::default(),
+            wiimm: Default::default(),
+            dragging: None,
+            highlight: None,
+            mode: Default::default(),
+        }
+    }
+}
+
+impl Default for CupSettings {
+    fn default() -> Self {
+        Self {
+            nintendo: true,
+            nin_swap: true,
+            wiimm_cup: true,
+            icon_format: TextureFormat::Cmpr,
+        }
+    }
+}
+
+impl Default for WiimmCup {
+    fn default() -> Self {
+        Self {
+            name: "Wiimm Cup".to_string(),
+            icon: Default::default(),
+            slots: RandomPool::VALUES
+                .into_iter()
+                .map(|pool| RandomSlot {
+                    pool,
+                    shown: true,
+                    name: "".to_string(),
+                })
+                .collect(),
         }
     }
 }
 
 impl Cup {
-    fn default(n: usize) -> Self {
+    /// Racing cup with four tracks, numbered as the `n`th cup
+    pub fn default(n: usize) -> Self {
+        let count = CupList::Racing.tracks_per_cup();
         Self {
             icon: Default::default(),
-            trackset: vec![
-                Track {
-                    id: (n * 4),
-                    ..Default::default()
-                },
-                Track {
-                    id: (n * 4) + 1,
-                    ..Default::default()
-                },
-                Track {
-                    id: (n * 4) + 2,
-                    ..Default::default()
-                },
-                Track {
-                    id: (n * 4) + 3,
+            trackset: (0..count)
+                .map(|i| Track {
+                    id: (n * count) + i,
                     ..Default::default()
-                },
-            ],
+                })
+                .collect(),
             name: format!("Cup {}", n),
+            names: BTreeMap::new(),
+        }
+    }
+
+    /// Name in `language`, or the default name without a translation
+    pub fn name_in(&self, language: Language) -> &str {
+        self.names.get(&language).unwrap_or(&self.name)
+    }
+
+    /// Positions of the tracks in each slot of the cup.
+    /// A group is a header with the members after it. Members without a header have a slot each.
+    pub fn groups(&self) -> Vec<Range<usize>> {
+        let mut groups: Vec<Range<usize>> = vec![];
+        for (i, track) in self.trackset.iter().enumerate() {
+            match groups.last_mut() {
+                Some(last)
+                    if track.flag == GroupFlag::Child && self.trackset[last.start].flag == GroupFlag::Header =>
+                {
+                    last.end = i + 1;
+                }
+                _ => groups.push(i..i + 1),
+            }
+        }
+        groups
+    }
+
+    /// Battle cup with five arenas
+    pub fn battle(n: usize) -> Self {
+        let count = CupList::Battle.tracks_per_cup();
+        Self {
+            icon: Default::default(),
+            trackset: (0..count).map(|_| Track::default_in(CupList::Battle)).collect(),
+            name: format!("Battle Cup {}", n),
+            names: BTreeMap::new(),
+        }
+    }
+}
+
+impl Cup {
+    /// Show the icon as it looks in the game after encoding into `format`
+    fn view_icon_preview(&self, ctx: &egui::Context, ui: &mut egui::Ui, format: TextureFormat) {
+        if self.icon.image.is_empty() {
+            return;
         }
+        let mut hasher = DefaultHasher::new();
+        self.icon.image.hash(&mut hasher);
+        let id = egui::Id::new(("icon_preview", hasher.finish(), format.as_str()));
+
+        // Encoding takes a while, so keep the result until the icon or the format changes
+        let cached = ctx.memory().data.get_temp::<Arc<RetainedImage>>(id);
+        let texture = match cached {
+            Some(v) => v,
+            None => {
+                let quantized = quantize(&self.icon.to_rgba(), format);
+                let texture = Arc::new(RetainedImage::from_color_image(
+                    "icon_preview",
+                    egui::ColorImage::from_rgba_unmultiplied(
+                        [quantized.width() as usize, quantized.height() as usize],
+                        quantized.as_raw(),
+                    ),
+                ));
+                ctx.memory().data.insert_temp(id, texture.clone());
+                texture
+            }
+        };
+
+        ui.vertical(|ui| {
+            ui.image(texture.texture_id(ctx), [78.0, 78.0])
+                .on_hover_text("The icon after encoding for the game");
+            ui.label(format!("In-game ({})", format.as_str()));
+        });
     }
 }
 
@@ -191,33 +1046,49 @@ impl ElementView for Cup {
             RetainedImage::from_image_bytes(&self.icon.filename, &self.icon.image).unwrap()
         };
 
-        ui.group(|ui| {
-            ui.horizontal(|ui| {
-                if ui
-                    .add(egui::ImageButton::new(texture.texture_id(ctx), [78.0, 78.0]))
-                    .on_hover_text("Select a image to set as cup icon")
-                    .context_menu(|ui| {
-                        if ui.button("Select a image").clicked() {
-                            self.open_image();
+        ui.horizontal(|ui| {
+            if ui
+                .add(egui::ImageButton::new(texture.texture_id(ctx), [78.0, 78.0]))
+                .on_hover_text("Select a image to set as cup icon")
+                .context_menu(|ui| {
+                    if ui.button("Select a image").clicked() {
+                        self.open_image();
+                    }
+                    if ui.button("Remove image").clicked() {
+                        self.icon.clear();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    let mut changed = false;
+                    ui.add_enabled_ui(!self.icon.source.is_empty(), |ui| {
+                        ui.label("Resize");
+                        for v in IconFit::VALUES {
+                            changed |= ui.selectable_value(&mut self.icon.fit, v, v.as_str()).changed();
                         }
-                        if ui.button("Remove image").clicked() {
-                            self.icon.filename = "".to_string();
-                            self.icon.image = vec![];
+                        ui.separator();
+                        ui.label("Filter");
+                        for v in IconFilter::VALUES {
+                            changed |= ui.selectable_value(&mut self.icon.filter, v, v.as_str()).changed();
                         }
                     })
-                    .clicked()
-                {
-                    self.open_image();
-                }
-                ui.add(
-                    egui::TextEdit::singleline(&mut self.name)
-                        .desired_width(300.0)
-                        .hint_text("Cup name"),
-                );
-            });
-            for track in &mut self.trackset {
-                track.view(ctx, ui);
+                    .response
+                    .on_disabled_hover_text("Select the image again to change how it's resized");
+                    ui.separator();
+                    ui.checkbox(&mut self.icon.keep_source, "Keep the original image in the project")
+                        .on_hover_text("Allows to resize again later, but makes the project file larger");
+                    if changed {
+                        let _ = self.icon.update();
+                    }
+                })
+                .clicked()
+            {
+                self.open_image();
             }
+            ui.add(
+                egui::TextEdit::singleline(&mut self.name)
+                    .desired_width(300.0)
+                    .hint_text("Cup name"),
+            );
         });
     }
 }
@@ -227,34 +1098,81 @@ impl Default for Track {
         Self {
             id: 0,
             name: "".to_string(),
+            names: BTreeMap::new(),
+            prefix: "".to_string(),
+            version: "".to_string(),
             property: Id::MarioCircuit,
             music: Id::MarioCircuit,
             flag: GroupFlag::None,
             filename: "".to_string(),
             author: "".to_string(),
             new: false,
+            thumbnail: vec![],
         }
     }
 }
 
-impl ElementView for Track {
-    fn view(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui) {
+impl Track {
+    /// Name in `language`, or the default name without a translation
+    pub fn name_in(&self, language: Language) -> &str {
+        self.names.get(&language).unwrap_or(&self.name)
+    }
+
+    /// Empty track with a property allowed in `list`
+    pub fn default_in(list: CupList) -> Self {
+        match list {
+            CupList::Racing => Default::default(),
+            CupList::Battle => Self {
+                property: Id::BlockPlaza,
+                music: Id::BlockPlaza,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Track editor offering only the properties allowed in `list`, with the name made by `template`
+    pub fn view_in(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, list: CupList, template: &NameTemplate) {
         ui.group(|ui| {
             ui.vertical(|ui| {
                 ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.prefix)
+                            .hint_text("Prefix")
+                            .desired_width(60.0),
+                    )
+                    .on_hover_text("Put before the name by the name template, like Wii");
                     ui.add(egui::TextEdit::singleline(&mut self.name).hint_text("Track name"));
-                    ui.checkbox(&mut self.new, "New").on_hover_text(format!("Mark as new track\nIt will include in the option {} in Wiimm Cup", r#""Random: New Track""#));
+                    ui.checkbox(&mut self.new, "New").on_hover_text(format!("Mark as new track\nIt will include in the option \"{}\" in Wiimm Cup", RandomPool::New.default_name()));
+                    egui::ComboBox::new(format!("group_{}", self.id), "")
+                        .selected_text(self.flag.as_str())
+                        .show_ui(ui, |ui| {
+                            for v in GroupFlag::VALUES {
+                                ui.selectable_value(&mut self.flag, v, v.as_str());
+                            }
+                        })
+                        .response
+                        .on_hover_text("Tracks of a group share one slot, and LE-CODE picks one of them at random.\nMembers follow their header.");
+                });
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.author).hint_text("Author name"));
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.version)
+                            .hint_text("Version")
+                            .desired_width(60.0),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.weak("In-game");
+                    let font = egui::TextStyle::Body.resolve(ui.style());
+                    ui.label(template.preview(self, None, font, ui.visuals().text_color()))
+                        .on_hover_text("The name made by the name template, as written into the game and the definition file");
                 });
-                ui.add(egui::TextEdit::singleline(&mut self.author).hint_text("Author name"));
                 ui.with_layout(Layout::left_to_right(egui::Align::Min), |ui| {
                     egui::ComboBox::new(format!("property_{}", &mut self.id), "Property")
                         .width(180.0)
                         .selected_text(format!("{}", &mut self.property.as_str()))
                         .show_ui(ui, |ui| {
-                            for v in Id::VALUES {
-                                if v == Id::GalaxyArena {
-                                    continue;
-                                }
+                            for v in Id::VALUES.into_iter().filter(|v| list.accepts(*v)) {
                                 ui.selectable_value(&mut self.property, v, v.as_str());
                             }
                         });
@@ -281,12 +1199,39 @@ impl ElementView for Track {
                     {
                         self.open_file();
                     }
+                    let thumbnail = if self.thumbnail.is_empty() {
+                        None
+                    } else {
+                        RetainedImage::from_image_bytes("thumbnail", &self.thumbnail).ok()
+                    };
+                    let response = match &thumbnail {
+                        Some(v) => ui.add(egui::ImageButton::new(v.texture_id(ctx), [18.0, 18.0])),
+                        None => ui.button("🖼"),
+                    };
+                    if response
+                        .on_hover_text("Select a thumbnail for generated cup icons")
+                        .context_menu(|ui| {
+                            if ui.button("Remove thumbnail").clicked() {
+                                self.thumbnail = vec![];
+                                ui.close_menu();
+                            }
+                        })
+                        .clicked()
+                    {
+                        self.open_thumbnail();
+                    }
                 });
             });
         });
     }
 }
 
+impl ElementView for Track {
+    fn view(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        self.view_in(ctx, ui, CupList::Racing, &Default::default());
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub enum Id {
@@ -431,6 +1376,92 @@ impl Id {
         }
     }
 
+    /// One of the ten battle arenas. `GalaxyArena` is only used as music.
+    pub fn is_arena(&self) -> bool {
+        (*self as usize) >= 32 && *self != Id::GalaxyArena
+    }
+
+    /// Inverse of `Id as u8`
+    pub fn from_u8(n: u8) -> Option<Self> {
+        Self::VALUES.get(n as usize).copied()
+    }
+
+    /// Track by its property slot in `_as_id`
+    pub fn from_property_slot(slot: u8) -> Option<Self> {
+        Self::VALUES.into_iter().find(|v| v._as_id().1 == slot)
+    }
+
+    /// Track by its music ID in `_as_id`
+    pub fn from_music_slot(slot: u8) -> Option<Self> {
+        Self::VALUES.into_iter().find(|v| v._as_id().0 == slot)
+    }
+
+    /// Name of the slot in LE-CODE definitions, `T11` to `T84` for tracks and `A11` to `A25` for arenas
+    pub fn slot_name(&self) -> Option<String> {
+        let n = *self as usize;
+        match self {
+            Id::GalaxyArena => None,
+            _ if n < 32 => Some(format!("T{}{}", n / 4 + 1, n % 4 + 1)),
+            _ => Some(format!("A{}{}", (n - 32) / 5 + 1, (n - 32) % 5 + 1)),
+        }
+    }
+
+    /// Inverse of `slot_name`
+    pub fn from_slot_name(name: &str) -> Option<Self> {
+        Self::VALUES
+            .into_iter()
+            .find(|v| v.slot_name().map_or(false, |s| s.eq_ignore_ascii_case(name)))
+    }
+
+    /// File name in Race/Course without extension
+    pub fn course_file(&self) -> &'static str {
+        match self {
+            Id::LuigiCircuit => "beginner_course",
+            Id::MooMooMeadow => "farm_course",
+            Id::MushroomGorge => "kinoko_course",
+            Id::ToadsFactory => "factory_course",
+            Id::MarioCircuit => "castle_course",
+            Id::CoconutMall => "shopping_course",
+            Id::DKSummit => "boardcross_course",
+            Id::WariosGoldMine => "truck_course",
+            Id::DaisyCircuit => "senior_course",
+            Id::KoopaCape => "water_course",
+            Id::MapleTreeway => "treehouse_course",
+            Id::GrumbleVolcano => "volcano_course",
+            Id::DryDryRuins => "desert_course",
+            Id::MoonviewHighway => "ridgehighway_course",
+            Id::BowsersCastle => "koopa_course",
+            Id::RainbowRoad => "rainbow_course",
+            Id::PeachBeachGCN => "old_peach_gc",
+            Id::YoshiFallsDS => "old_falls_ds",
+            Id::GhostValleySNES => "old_obake_sfc",
+            Id::MarioRacewayN64 => "old_mario_64",
+            Id::SherbetLandN64 => "old_sherbet_64",
+            Id::ShyGuyBeachGBA => "old_heyho_gba",
+            Id::DelfinoSquareDS => "old_town_ds",
+            Id::WaluigiStadiumGCN => "old_waluigi_gc",
+            Id::DesertHillsDS => "old_desert_ds",
+            Id::BowserCastleGBA => "old_koopa_gba",
+            Id::JungleParkwayN64 => "old_donkey_64",
+            Id::MarioCircuitGCN => "old_mario_gc",
+            Id::MarioCircuitSNES => "old_mario_sfc",
+            Id::PeachGardenDS => "old_garden_ds",
+            Id::DKMountainGCN => "old_donkey_gc",
+            Id::BowserCastleN64 => "old_koopa_64",
+            Id::BlockPlaza => "block_battle",
+            Id::DelfinoPier => "venice_battle",
+            Id::FunkyStadium => "skate_battle",
+            Id::ChompWheel => "casino_battle",
+            Id::ThwompDesert => "sand_battle",
+            Id::BattleCourseSNES => "old_battle4_sfc",
+            Id::BattleCourseGBA => "old_battle3_gba",
+            Id::SkyscraperN64 => "old_matenro_64",
+            Id::CookieLandGCN => "old_CookieLand_gc",
+            Id::TwilightHouseDS => "old_House_ds",
+            Id::GalaxyArena => "ring_mission",
+        }
+    }
+
     /// Plan to use for finally building distribution
     pub fn _as_id(&self) -> (u8, u8) {
         match self {
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use crate::apps::{
+    tracks::{CupList, TrackDefinition},
+    Language, Region, TranslationApp,
+};
+use egui::{Context, TextEdit};
+use egui_extras::{Size, TableBuilder};
+
+impl Default for TranslationApp {
+    fn default() -> Self {
+        Self {
+            open: false,
+            shown: Region::PAL.languages().to_vec(),
+        }
+    }
+}
+
+/// Row of the table: a cup, or a track of the cup
+#[derive(Clone, Copy)]
+struct Row {
+    list: CupList,
+    cup: usize,
+    track: Option<usize>,
+}
+
+impl TranslationApp {
+    pub fn ui(&mut self, ctx: &Context, editor: &mut TrackDefinition) {
+        let mut open = self.open;
+        egui::Window::new("Translations")
+            .open(&mut open)
+            .collapsible(false)
+            .default_size([800.0, 480.0])
+            .show(ctx, |ui| {
+                ui.label("Names left empty use the default name.");
+                ui.horizontal_wrapped(|ui| {
+                    ui.label("Languages");
+                    for language in Language::VALUES {
+                        let mut shown = self.shown.contains(&language);
+                        if ui.checkbox(&mut shown, language.as_str()).changed() {
+                            if shown {
+                                self.shown.push(language);
+                                self.shown.sort();
+                            } else {
+                                self.shown.retain(|v| *v != language);
+                            }
+                        }
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::horizontal().show(ui, |ui| self.gen_table(ui, editor));
+            });
+        self.open = open;
+    }
+
+    fn gen_table(&mut self, ui: &mut egui::Ui, editor: &mut TrackDefinition) {
+        let mut rows: Vec<Row> = vec![];
+        for (list, cups) in [(CupList::Racing, &editor.cups), (CupList::Battle, &editor.battle_cups)] {
+            for (cup, v) in cups.iter().enumerate() {
+                rows.push(Row { list, cup, track: None });
+                rows.extend((0..v.trackset.len()).map(|i| Row { list, cup, track: Some(i) }));
+            }
+        }
+
+        let mut table = TableBuilder::new(ui)
+            .striped(true)
+            .column(Size::initial(200.0).at_least(100.0));
+        for _ in &self.shown {
+            table = table.column(Size::initial(160.0).at_least(80.0));
+        }
+        let mut copy: Option<(Language, Language)> = None;
+        table
+            .header(20.0, |mut header| {
+                header.col(|ui| {
+                    ui.strong("Default");
+                });
+                for &language in &self.shown {
+                    header.col(|ui| {
+                        ui.menu_button(language.as_str(), |ui| {
+                            ui.label("Copy names from");
+                            for from in Language::VALUES.into_iter().filter(|v| *v != language) {
+                                if ui.button(from.as_str()).clicked() {
+                                    copy = Some((from, language));
+                                    ui.close_menu();
+                                }
+                            }
+                            ui.separator();
+                            if ui.button("Clear").on_hover_text("Use the default names").clicked() {
+                                for_each_names(editor, |names, _| {
+                                    names.remove(&language);
+                                });
+                                ui.close_menu();
+                            }
+                        });
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(20.0, rows.len(), |i, mut row| {
+                    let r = rows[i];
+                    let cup = &mut editor.list_of_mut(r.list)[r.cup];
+                    let (name, names) = match r.track {
+                        Some(n) => {
+                            let track = &mut cup.trackset[n];
+                            (&mut track.name, &mut track.names)
+                        }
+                        None => (&mut cup.name, &mut cup.names),
+                    };
+                    row.col(|ui| {
+                        let hint = if r.track.is_some() { "Track name" } else { "Cup name" };
+                        if r.track.is_some() {
+                            ui.add_space(12.0);
+                        }
+                        ui.add(TextEdit::singleline(name).hint_text(hint));
+                    });
+                    for language in &self.shown {
+                        row.col(|ui| {
+                            let mut text = names.get(language).cloned().unwrap_or_default();
+                            if ui.add(TextEdit::singleline(&mut text).hint_text(name.as_str())).changed() {
+                                if text.is_empty() {
+                                    names.remove(language);
+                                } else {
+                                    names.insert(*language, text);
+                                }
+                            }
+                        });
+                    }
+                });
+            });
+
+        if let Some((from, to)) = copy {
+            for_each_names(editor, |names, name| {
+                match names.get(&from).cloned() {
+                    Some(v) if v != name => names.insert(to, v),
+                    _ => names.remove(&to),
+                };
+            });
+        }
+    }
+}
+
+/// Run `f` with the translations and the default name of every cup and track
+fn for_each_names(editor: &mut TrackDefinition, mut f: impl FnMut(&mut BTreeMap<Language, String>, &str)) {
+    for cup in editor.cups.iter_mut().chain(editor.battle_cups.iter_mut()) {
+        f(&mut cup.names, &cup.name);
+        for track in cup.trackset.iter_mut() {
+            f(&mut track.names, &track.name);
+        }
+    }
+}
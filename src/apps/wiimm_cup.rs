@@ -0,0 +1,85 @@
+use crate::apps::{
+    tracks::{RandomPool, TrackDefinition},
+    WiimmCupApp,
+};
+use egui::{Context, TextEdit};
+use egui_extras::RetainedImage;
+
+impl WiimmCupApp {
+    pub fn ui(&mut self, ctx: &Context, editor: &mut TrackDefinition) {
+        let mut open = self.open;
+        egui::Window::new("Wiimm Cup")
+            .open(&mut open)
+            .collapsible(false)
+            .default_size([480.0, 480.0])
+            .show(ctx, |ui| {
+                ui.checkbox(&mut editor.mode.wiimm_cup, "Show the Wiimm cup")
+                    .on_hover_text("A cup of random slots after the other racing cups");
+                ui.weak(
+                    "LE-CODE decides which random slots the cup has and which tracks they pick from. \
+                     Only the names of the cup and the slots and the cup icon are written into the build.",
+                );
+                ui.add_enabled_ui(editor.mode.wiimm_cup, |ui| {
+                    self.view_cup(ctx, ui, editor);
+                    ui.separator();
+                    egui::ScrollArea::vertical().show(ui, |ui| self.view_slots(ui, editor));
+                });
+            });
+        self.open = open;
+    }
+
+    fn view_cup(&mut self, ctx: &Context, ui: &mut egui::Ui, editor: &mut TrackDefinition) {
+        let cup = &mut editor.wiimm;
+        // An image which can't be read shows as no icon
+        let texture = Some(&cup.icon.image)
+            .filter(|v| !v.is_empty())
+            .and_then(|v| RetainedImage::from_image_bytes(&cup.icon.filename, v).ok())
+            .unwrap_or_else(|| {
+                RetainedImage::from_color_image("None.png", egui::ColorImage::from_rgba_unmultiplied([1; 2], &[0, 0, 0, 0]))
+            });
+        ui.horizontal(|ui| {
+            if ui
+                .add(egui::ImageButton::new(texture.texture_id(ctx), [78.0, 78.0]))
+                .on_hover_text("Select a image to set as cup icon")
+                .context_menu(|ui| {
+                    if ui.button("Remove image").clicked() {
+                        cup.icon.clear();
+                        ui.close_menu();
+                    }
+                })
+                .clicked()
+            {
+                cup.icon.open_image();
+            }
+            ui.add(TextEdit::singleline(&mut cup.name).hint_text("Cup name"));
+        });
+    }
+
+    fn view_slots(&mut self, ui: &mut egui::Ui, editor: &mut TrackDefinition) {
+        let pools: Vec<Vec<String>> = editor.wiimm.slots.iter().map(|v| editor.random_pool(v.pool)).collect();
+        let shown = editor.wiimm.slots.iter().filter(|v| v.shown).count();
+        for (slot, tracks) in editor.wiimm.slots.iter_mut().zip(&pools) {
+            ui.horizontal(|ui| {
+                // The cup needs at least one slot
+                ui.add_enabled(!slot.shown || shown > 1, egui::Checkbox::new(&mut slot.shown, ""))
+                    .on_hover_text("Show the slot in the preview and write its name");
+                ui.add(TextEdit::singleline(&mut slot.name).hint_text(slot.pool.default_name()));
+            });
+            egui::CollapsingHeader::new(format!("{} tracks", tracks.len()))
+                .id_source(("random_pool", slot.pool.as_str()))
+                .show(ui, |ui| {
+                    if tracks.is_empty() {
+                        let hint = match slot.pool {
+                            RandomPool::New => "Mark tracks as new to add them",
+                            _ => "Add tracks to the racing cups",
+                        };
+                        ui.weak(hint);
+                    }
+                    for name in tracks {
+                        ui.label(name);
+                    }
+                });
+            ui.add_space(4.0);
+        }
+    }
+}
@@ -0,0 +1,408 @@
+const YAZ0_MAGIC: &'static [u8; 4] = b"Yaz0";
+const U8_MAGIC: u32 = 0x55AA382D;
+const U8_ROOT_OFFSET: usize = 0x20;
+const U8_NODE_SIZE: usize = 12;
+/// Yaz0 can refer back up to this many bytes
+const YAZ0_WINDOW: usize = 0x1000;
+const YAZ0_MAX_MATCH: usize = 0x111;
+/// Candidates compared per position, trading compression ratio for speed
+const YAZ0_MAX_TRIES: usize = 64;
+const HASH_BITS: usize = 16;
+
+/// U8 archive, the container inside SZS files
+pub struct U8Archive {
+    /// Children of the unnamed root directory
+    pub nodes: Vec<U8Node>,
+}
+
+pub enum U8Node {
+    File { name: String, data: Vec<u8> },
+    Dir { name: String, children: Vec<U8Node> },
+}
+
+impl U8Node {
+    fn name(&self) -> &str {
+        match self {
+            U8Node::File { name, .. } => name,
+            U8Node::Dir { name, .. } => name,
+        }
+    }
+}
+
+impl U8Archive {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < U8_ROOT_OFFSET || as_u32_be(&data[0..4]) != U8_MAGIC {
+            return Err("Not a U8 archive.".to_string());
+        }
+        let root = as_u32_be(&data[4..8]) as usize;
+        if root + U8_NODE_SIZE > data.len() {
+            return Err("U8 archive is broken.".to_string());
+        }
+        let count = as_u32_be(&data[root + 8..root + 12]) as usize;
+        let strings = root + count * U8_NODE_SIZE;
+        if count == 0 || strings > data.len() {
+            return Err("U8 archive is broken.".to_string());
+        }
+
+        let (nodes, _) = parse_dir(data, root, strings, 1, count)?;
+        Ok(Self { nodes })
+    }
+
+    /// File at `path`, separated by slashes
+    pub fn get_file(&self, path: &str) -> Option<&Vec<u8>> {
+        let mut nodes = &self.nodes;
+        let mut parts = path.split('/').filter(|v| !v.is_empty()).peekable();
+        while let Some(part) = parts.next() {
+            let node = nodes.iter().find(|v| v.name() == part)?;
+            match node {
+                U8Node::File { data, .. } if parts.peek().is_none() => return Some(data),
+                U8Node::Dir { children, .. } => nodes = children,
+                U8Node::File { .. } => return None,
+            }
+        }
+        None
+    }
+
+    /// Add or replace a file. `path` is separated by slashes, and missing folders are created.
+    pub fn set_file(&mut self, path: &str, data: Vec<u8>) {
+        let mut parts: Vec<&str> = path.split('/').filter(|v| !v.is_empty()).collect();
+        let name = match parts.pop() {
+            Some(v) => v.to_string(),
+            None => return,
+        };
+
+        let mut dir = &mut self.nodes;
+        for part in parts {
+            let index = match dir.iter().position(|v| matches!(v, U8Node::Dir { .. }) && v.name() == part) {
+                Some(v) => v,
+                None => {
+                    dir.push(U8Node::Dir {
+                        name: part.to_string(),
+                        children: vec![],
+                    });
+                    dir.len() - 1
+                }
+            };
+            dir = match &mut dir[index] {
+                U8Node::Dir { children, .. } => children,
+                U8Node::File { .. } => unreachable!(),
+            };
+        }
+
+        dir.retain(|v| v.name() != name);
+        dir.push(U8Node::File { name, data });
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        // Root directory first, then the children in depth-first order
+        let mut nodes: Vec<[u32; 4]> = vec![[1, 0, 0, 0]];
+        let mut names: Vec<u8> = vec![0];
+        let mut files: Vec<(usize, &[u8])> = vec![];
+        flatten(&self.nodes, 0, &mut nodes, &mut names, &mut files);
+        nodes[0][3] = nodes.len() as u32;
+
+        let header_size = nodes.len() * U8_NODE_SIZE + names.len();
+        let data_offset = align(U8_ROOT_OFFSET + header_size, 0x20);
+        let mut data: Vec<u8> = vec![];
+        for (index, file) in files {
+            nodes[index][2] = (data_offset + data.len()) as u32;
+            data.extend_from_slice(file);
+            data.resize(align(data.len(), 0x20), 0);
+        }
+
+        let mut u8: Vec<u8> = vec![];
+        u8.extend_from_slice(&U8_MAGIC.to_be_bytes());
+        u8.extend_from_slice(&(U8_ROOT_OFFSET as u32).to_be_bytes());
+        u8.extend_from_slice(&(header_size as u32).to_be_bytes());
+        u8.extend_from_slice(&(data_offset as u32).to_be_bytes());
+        u8.resize(U8_ROOT_OFFSET, 0);
+        for [kind, name, offset, size] in nodes {
+            u8.extend_from_slice(&((kind << 24) | name).to_be_bytes());
+            u8.extend_from_slice(&offset.to_be_bytes());
+            u8.extend_from_slice(&size.to_be_bytes());
+        }
+        u8.append(&mut names);
+        u8.resize(data_offset, 0);
+        u8.append(&mut data);
+        u8
+    }
+}
+
+/// Parse nodes `start..end` into a tree. Returns the nodes and the index after them.
+fn parse_dir(data: &[u8], root: usize, strings: usize, start: usize, end: usize) -> Result<(Vec<U8Node>, usize), String> {
+    let mut nodes: Vec<U8Node> = vec![];
+    let mut i = start;
+    while i < end {
+        let node = &data[root + i * U8_NODE_SIZE..root + (i + 1) * U8_NODE_SIZE];
+        let name = read_name(data, strings + (as_u32_be(&node[0..4]) & 0xFFFFFF) as usize)?;
+        let offset = as_u32_be(&node[4..8]) as usize;
+        let size = as_u32_be(&node[8..12]) as usize;
+
+        if node[0] == 1 {
+            if size <= i || size > end {
+                return Err("U8 archive is broken.".to_string());
+            }
+            let (children, next) = parse_dir(data, root, strings, i + 1, size)?;
+            nodes.push(U8Node::Dir { name, children });
+            i = next;
+        } else {
+            if offset + size > data.len() {
+                return Err("U8 archive is broken.".to_string());
+            }
+            nodes.push(U8Node::File {
+                name,
+                data: data[offset..offset + size].to_vec(),
+            });
+            i += 1;
+        }
+    }
+    Ok((nodes, i))
+}
+
+fn read_name(data: &[u8], offset: usize) -> Result<String, String> {
+    let bytes = data.get(offset..).ok_or("U8 archive is broken.".to_string())?;
+    let len = bytes.iter().position(|v| *v == 0).unwrap_or(bytes.len());
+    Ok(String::from_utf8_lossy(&bytes[..len]).to_string())
+}
+
+/// Append nodes as [type, name offset, data offset or parent, size or end]
+fn flatten<'a>(
+    children: &'a [U8Node],
+    parent: usize,
+    nodes: &mut Vec<[u32; 4]>,
+    names: &mut Vec<u8>,
+    files: &mut Vec<(usize, &'a [u8])>,
+) {
+    for child in children {
+        let name = names.len() as u32;
+        names.extend_from_slice(child.name().as_bytes());
+        names.push(0);
+
+        let index = nodes.len();
+        match child {
+            U8Node::File { data, .. } => {
+                nodes.push([0, name, 0, data.len() as u32]);
+                files.push((index, data));
+            }
+            U8Node::Dir { children, .. } => {
+                nodes.push([1, name, parent as u32, 0]);
+                flatten(children, index, nodes, names, files);
+                nodes[index][3] = nodes.len() as u32;
+            }
+        }
+    }
+}
+
+/// Read an SZS file, which is a U8 archive usually compressed with Yaz0
+pub fn read_szs(data: &[u8]) -> Result<U8Archive, String> {
+    if data.starts_with(YAZ0_MAGIC) {
+        U8Archive::parse(&yaz0_decode(data)?)
+    } else {
+        U8Archive::parse(data)
+    }
+}
+
+pub fn write_szs(archive: &U8Archive) -> Vec<u8> {
+    yaz0_encode(&archive.encode())
+}
+
+pub fn yaz0_decode(data: &[u8]) -> Result<Vec<u8>, String> {
+    const ERROR: &'static str = "Yaz0 data is broken.";
+    if data.len() < 16 || !data.starts_with(YAZ0_MAGIC) {
+        return Err("Not Yaz0 compressed data.".to_string());
+    }
+    let size = as_u32_be(&data[4..8]) as usize;
+    let mut out: Vec<u8> = Vec::with_capacity(size);
+    let mut src = 16;
+    let mut next = || -> Result<u8, String> {
+        let v = *data.get(src).ok_or(ERROR.to_string())?;
+        src += 1;
+        Ok(v)
+    };
+
+    while out.len() < size {
+        let flags = next()?;
+        for bit in 0..8 {
+            if out.len() >= size {
+                break;
+            }
+            if flags & (0x80 >> bit) != 0 {
+                out.push(next()?);
+                continue;
+            }
+            let b1 = next()? as usize;
+            let b2 = next()? as usize;
+            let distance = (((b1 & 0x0F) << 8) | b2) + 1;
+            let len = match b1 >> 4 {
+                0 => next()? as usize + 0x12,
+                n => n + 2,
+            };
+            if distance > out.len() {
+                return Err(ERROR.to_string());
+            }
+            for _ in 0..len {
+                out.push(out[out.len() - distance]);
+            }
+        }
+    }
+    out.truncate(size);
+    Ok(out)
+}
+
+pub fn yaz0_encode(data: &[u8]) -> Vec<u8> {
+    let mut out: Vec<u8> = YAZ0_MAGIC.to_vec();
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(&[0; 8]);
+
+    // Hash chains of 3 byte sequences to find earlier matches
+    let mut head: Vec<usize> = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev: Vec<usize> = vec![usize::MAX; data.len()];
+
+    let mut pos = 0;
+    let mut flag = 0;
+    let mut bit = 8;
+    while pos < data.len() {
+        if bit == 8 {
+            flag = out.len();
+            out.push(0);
+            bit = 0;
+        }
+
+        let (len, distance) = find_match(data, pos, &head, &prev);
+        if len >= 3 {
+            let d = distance - 1;
+            if len >= 0x12 {
+                out.extend_from_slice(&[(d >> 8) as u8, d as u8, (len - 0x12) as u8]);
+            } else {
+                out.extend_from_slice(&[(((len - 2) << 4) | (d >> 8)) as u8, d as u8]);
+            }
+            for i in pos..pos + len {
+                insert(data, i, &mut head, &mut prev);
+            }
+            pos += len;
+        } else {
+            out[flag] |= 0x80 >> bit;
+            out.push(data[pos]);
+            insert(data, pos, &mut head, &mut prev);
+            pos += 1;
+        }
+        bit += 1;
+    }
+
+    out
+}
+
+fn hash(data: &[u8], i: usize) -> usize {
+    let v = ((data[i] as u32) << 16) | ((data[i + 1] as u32) << 8) | (data[i + 2] as u32);
+    (v.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+fn insert(data: &[u8], i: usize, head: &mut [usize], prev: &mut [usize]) {
+    if i + 3 <= data.len() {
+        let h = hash(data, i);
+        prev[i] = head[h];
+        head[h] = i;
+    }
+}
+
+/// Longest earlier match at `pos` as (length, distance)
+fn find_match(data: &[u8], pos: usize, head: &[usize], prev: &[usize]) -> (usize, usize) {
+    if pos + 3 > data.len() {
+        return (0, 0);
+    }
+    let max = YAZ0_MAX_MATCH.min(data.len() - pos);
+    let mut best = (0, 0);
+    let mut candidate = head[hash(data, pos)];
+    for _ in 0..YAZ0_MAX_TRIES {
+        if candidate == usize::MAX || pos - candidate > YAZ0_WINDOW {
+            break;
+        }
+        let len = (0..max).take_while(|&i| data[candidate + i] == data[pos + i]).count();
+        if len > best.0 {
+            best = (len, pos - candidate);
+            if len == max {
+                break;
+            }
+        }
+        candidate = prev[candidate];
+    }
+    best
+}
+
+fn align(n: usize, to: usize) -> usize {
+    (n + to - 1) / to * to
+}
+
+fn as_u32_be(array: &[u8]) -> u32 {
+    ((array[0] as u32) << 24) + ((array[1] as u32) << 16) + ((array[2] as u32) << 8) + ((array[3] as u32) << 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> U8Archive {
+        let mut archive = U8Archive { nodes: vec![] };
+        archive.set_file("./course.kmp", b"RKMD".repeat(100));
+        archive.set_file("./message/Common.bmg", vec![1, 2, 3]);
+        archive.set_file("./message/Menu.bmg", vec![]);
+        archive.set_file("./ui/button/timg/tt_cup.tpl", (0..=255).collect());
+        archive
+    }
+
+    #[test]
+    fn u8_round_trip() {
+        let archive = U8Archive::parse(&sample().encode()).unwrap();
+        assert_eq!(archive.get_file("./course.kmp"), Some(&b"RKMD".repeat(100)));
+        assert_eq!(archive.get_file("./message/Common.bmg"), Some(&vec![1, 2, 3]));
+        assert_eq!(archive.get_file("./message/Menu.bmg"), Some(&vec![]));
+        assert_eq!(archive.get_file("./ui/button/timg/tt_cup.tpl").unwrap().len(), 256);
+        assert!(archive.get_file("./message").is_none());
+        assert!(archive.get_file("./message/Race.bmg").is_none());
+        assert_eq!(archive.encode(), sample().encode());
+    }
+
+    #[test]
+    fn replace_file() {
+        let mut archive = sample();
+        archive.set_file("./message/Common.bmg", vec![4]);
+        let archive = U8Archive::parse(&archive.encode()).unwrap();
+        assert_eq!(archive.get_file("./message/Common.bmg"), Some(&vec![4]));
+        // Files of SZS archives are in a "." folder
+        let message = match &archive.nodes[..] {
+            [U8Node::Dir { children, .. }] => &children[1],
+            _ => panic!("the root has more than the \".\" folder"),
+        };
+        match message {
+            U8Node::Dir { children, .. } => assert_eq!(children.len(), 2),
+            U8Node::File { .. } => panic!("message is a file"),
+        }
+    }
+
+    #[test]
+    fn yaz0_round_trip() {
+        let repeated = b"Mario Kart Wii ".repeat(1000);
+        let noise: Vec<u8> = (0..100000u32).map(|v| (v.wrapping_mul(2654435761) >> 24) as u8).collect();
+        for data in [vec![], vec![7], repeated.clone(), noise] {
+            assert_eq!(yaz0_decode(&yaz0_encode(&data)).unwrap(), data);
+        }
+        assert!(yaz0_encode(&repeated).len() < repeated.len() / 10);
+        assert_eq!(read_szs(&write_szs(&sample())).unwrap().encode(), sample().encode());
+    }
+
+    #[test]
+    fn yaz0_decode_stream() {
+        // 3 literals, then 6 bytes from 3 back, then 0x12 + 2 bytes from 1 back
+        let mut data = YAZ0_MAGIC.to_vec();
+        data.extend_from_slice(&29u32.to_be_bytes());
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&[0xE0, b'a', b'b', b'c', 0x40, 0x02, 0x00, 0x00, 0x02]);
+        let mut expected = b"abcabcabc".to_vec();
+        expected.resize(29, b'c');
+        assert_eq!(yaz0_decode(&data).unwrap(), expected);
+
+        data.truncate(data.len() - 1);
+        assert!(yaz0_decode(&data).is_err());
+        assert!(yaz0_decode(b"Yaz0").is_err());
+    }
+}
@@ -0,0 +1,484 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use egui::Color32;
+use image::RgbaImage;
+
+use crate::{
+    apps::{
+        source::{COURSE_DIR, REL_DIR, SCENE_UI_DIR},
+        tracks::{Cup, CupList, RandomPool, Track, ICON_SIZE},
+        CodeStruct, Language, Region,
+    },
+    Distro,
+};
+
+use super::{
+    archive::{read_szs, write_szs, U8Node},
+    game::GameFiles,
+    export::track_flags,
+    icon::{Background, IconContent, IconTemplate},
+    lecode::{merge_bmg, write_settings, write_tables, LeBinary, MID_CUP_NAME, MID_TRACK_NAME},
+    menu::{nintendo_tracks, MenuCupKind},
+    profile::profile,
+    texture::encode_tpl,
+};
+
+/// LE-CODE reserves the slots below this for Nintendo and special tracks
+pub const FIRST_CUSTOM_SLOT: usize = 0x44;
+/// Slots of the random picks of the Wiimm cup, in the order of `RandomPool`
+pub const FIRST_RANDOM_SLOT: usize = 0x40;
+/// Archives in Scene/UI which show cup icons
+const ICON_ARCHIVES: [&'static str; 3] = ["Channel.szs", "MenuMulti.szs", "MenuSingle.szs"];
+/// Where LE-CODE reads the cup icons inside those archives
+const ICON_FILES: [&'static str; 2] = ["button/timg/ct_icons.tpl", "control/timg/ct_icons.tpl"];
+/// Messages with the cup and track names in the archives of each language
+const MESSAGE_FILE: &'static str = "message/Common.bmg";
+/// Folders of the files which differ by region
+const CODES_DIR: &'static str = "codes";
+const RIIVOLUTION_DIR: &'static str = "riivolution";
+const GCT_HEADER: [u8; 8] = [0x00, 0xD0, 0xC0, 0xDE, 0x00, 0xD0, 0xC0, 0xDE];
+const GCT_FOOTER: [u8; 8] = [0xF0, 0, 0, 0, 0, 0, 0, 0];
+
+pub struct BuildReport {
+    pub nintendo_tracks: usize,
+    pub custom_tracks: usize,
+    /// Scene/UI archives with the cup icons
+    pub ui_archives: usize,
+    /// Scene/UI archives of each language with the names
+    pub message_archives: usize,
+    /// Regions which files are written for
+    pub regions: Vec<Region>,
+    /// Files which could not be copied
+    pub missing: Vec<String>,
+    /// Files which are written without some of their content, or not written
+    pub warnings: Vec<String>,
+}
+
+impl BuildReport {
+    pub fn summary(&self) -> String {
+        let mut s = format!(
+            "Build finished.\n{} original tracks and {} custom tracks are copied.",
+            self.nintendo_tracks, self.custom_tracks
+        );
+        s.push_str(&format!("\nCup icons are written into {} Scene/UI archives.", self.ui_archives));
+        s.push_str(&format!("\nNames are written into {} Scene/UI archives.", self.message_archives));
+        let regions: Vec<&str> = self.regions.iter().map(|v| v.as_str()).collect();
+        s.push_str(&format!("\nRegions: {}", regions.join(", ")));
+        if !self.warnings.is_empty() {
+            s.push_str(&format!("\n{} warnings:", self.warnings.len()));
+            for v in &self.warnings {
+                s.push_str(&format!("\n{}", v));
+            }
+        }
+        if !self.missing.is_empty() {
+            s.push_str(&format!("\n{} files are missing:", self.missing.len()));
+            for v in &self.missing {
+                s.push_str(&format!("\n{}", v));
+            }
+        }
+        s
+    }
+}
+
+impl Distro {
+    /// Write the distribution files into `out`
+    pub fn build_distribution(&self, out: &Path) -> Result<BuildReport, String> {
+        let problems = self.tracks.editor.check_limits(&profile(self.settings.lecode_build).limits);
+        if !problems.is_empty() {
+            return Err(format!("The cups can't be built with LE-CODE:\n{}", problems.join("\n")));
+        }
+        if self.settings.regions.is_empty() {
+            return Err("Select at least one region in Build > Regions.".to_string());
+        }
+
+        let course_dir = out.join(COURSE_DIR);
+        fs::create_dir_all(&course_dir).map_err(|e| format!("Failed to create output folder: {}", e))?;
+
+        let mut report = BuildReport {
+            nintendo_tracks: 0,
+            custom_tracks: 0,
+            ui_archives: 0,
+            message_archives: 0,
+            regions: vec![],
+            missing: vec![],
+            warnings: vec![],
+        };
+
+        // LE-CODE always needs the original tracks, even if the Nintendo cups are hidden
+        let mut files = self.source.open_files()?;
+        for path in files.list_dir(COURSE_DIR) {
+            if !path.to_lowercase().ends_with(".szs") {
+                continue;
+            }
+            let data = files.read_file(&path)?;
+            let name = path.rsplit('/').next().unwrap();
+            fs::write(course_dir.join(name), data).map_err(|e| format!("Failed to write {}: {}", name, e))?;
+            report.nintendo_tracks += 1;
+        }
+
+        // Custom arenas follow the racing tracks
+        for (i, track) in self.custom_tracks().enumerate() {
+            let dest = course_dir.join(format!("{:03x}.szs", FIRST_CUSTOM_SLOT + i));
+            if track.filename.is_empty() || fs::copy(&track.filename, dest).is_err() {
+                report.missing.push(if track.filename.is_empty() {
+                    format!("{} (no file)", track.name)
+                } else {
+                    track.filename.clone()
+                });
+                continue;
+            }
+            report.custom_tracks += 1;
+        }
+
+        let ui_dir = out.join(SCENE_UI_DIR);
+        fs::create_dir_all(&ui_dir).map_err(|e| format!("Failed to create output folder: {}", e))?;
+        let tpl = encode_tpl(&self.cup_icon_sheet(), self.tracks.editor.mode.icon_format);
+        for name in ICON_ARCHIVES {
+            let path = format!("{}/{}", SCENE_UI_DIR, name);
+            let mut archive = match files.read_file(&path).and_then(|v| read_szs(&v)) {
+                Ok(v) => v,
+                Err(_) => {
+                    report.missing.push(path);
+                    continue;
+                }
+            };
+            // Nintendo archives put everything under a folder named "."
+            let root = match &archive.nodes[..] {
+                [U8Node::Dir { name, .. }] if name == "." => "./",
+                _ => "",
+            };
+            for icon in ICON_FILES {
+                archive.set_file(&format!("{}{}", root, icon), tpl.clone());
+            }
+            fs::write(ui_dir.join(name), write_szs(&archive)).map_err(|e| format!("Failed to write {}: {}", name, e))?;
+            report.ui_archives += 1;
+        }
+
+        for region in Region::VALUES.into_iter().filter(|v| self.settings.regions.contains(v)) {
+            self.build_region(region, out, files.as_mut(), &mut report)?;
+            report.regions.push(region);
+        }
+
+        // Riivolution reads the files from the folder of the same name on the SD card
+        let root = out.file_name().and_then(|v| v.to_str()).unwrap_or("distribution");
+        let riivolution_dir = out.join(RIIVOLUTION_DIR);
+        fs::create_dir_all(&riivolution_dir).map_err(|e| format!("Failed to create output folder: {}", e))?;
+        fs::write(riivolution_dir.join(format!("{}.xml", root)), self.to_riivolution(root))
+            .map_err(|e| format!("Failed to write the Riivolution XML: {}", e))?;
+
+        Ok(report)
+    }
+
+    /// LE-CODE binary, cheat codes and messages of one region
+    fn build_region(
+        &self,
+        region: Region,
+        out: &Path,
+        files: &mut dyn GameFiles,
+        report: &mut BuildReport,
+    ) -> Result<(), String> {
+        let create = |dir: &Path| fs::create_dir_all(dir).map_err(|e| format!("Failed to create output folder: {}", e));
+
+        let bin_name = format!("lecode-{}.bin", region.lecode_name());
+        let bin = if self.source.lecode_path.is_empty() {
+            files.read_file(&format!("{}/{}", REL_DIR, bin_name))
+        } else {
+            fs::read(Path::new(&self.source.lecode_path).join(&bin_name)).map_err(|e| e.to_string())
+        };
+        match bin {
+            Ok(mut data) => {
+                let layout = &profile(self.settings.lecode_build).layout;
+                // A binary of another build is copied as it is rather than patched at the wrong offsets
+                let mut patched = data.clone();
+                let written = write_settings(&mut patched, &self.settings, layout)
+                    .and_then(|_| LeBinary::parse(&patched, layout))
+                    .and_then(|v| write_tables(&mut patched, &self.lecode_tables(&v), layout));
+                match written {
+                    Ok(_) => data = patched,
+                    Err(e) => report.warnings.push(format!(
+                        "{}: {} It is copied without the settings and cups.",
+                        bin_name, e
+                    )),
+                }
+                create(&out.join(REL_DIR))?;
+                fs::write(out.join(REL_DIR).join(&bin_name), data)
+                    .map_err(|e| format!("Failed to write {}: {}", bin_name, e))?;
+            }
+            Err(_) => report.missing.push(bin_name),
+        }
+
+        if self.codes.enabled() {
+            let gct_name = format!("{}01.gct", region.game_id());
+            match encode_gct(&self.codes.codes, region, &mut report.warnings) {
+                Some(gct) => {
+                    create(&out.join(CODES_DIR))?;
+                    fs::write(out.join(CODES_DIR).join(&gct_name), gct)
+                        .map_err(|e| format!("Failed to write {}: {}", gct_name, e))?;
+                }
+                None => report
+                    .warnings
+                    .push(format!("{} has no complete cheat codes, so {} is not written.", region.as_str(), gct_name)),
+            }
+        }
+
+        for &language in region.languages() {
+            self.build_messages(language, region, out, files, report)?;
+        }
+        Ok(())
+    }
+
+    /// Put the names in `language` into the Scene/UI archives of the language, like Menu_E.szs.
+    /// A source of another region doesn't have them, which is reported.
+    fn build_messages(
+        &self,
+        language: Language,
+        region: Region,
+        out: &Path,
+        files: &mut dyn GameFiles,
+        report: &mut BuildReport,
+    ) -> Result<(), String> {
+        let messages = self.message_table(language);
+        let suffix = format!("_{}.szs", language.suffix()).to_lowercase();
+        let archives: Vec<String> = files
+            .list_dir(SCENE_UI_DIR)
+            .into_iter()
+            .filter(|v| v.to_lowercase().ends_with(&suffix))
+            .collect();
+
+        let mut written = 0;
+        for path in archives {
+            let mut archive = match files.read_file(&path).and_then(|v| read_szs(&v)) {
+                Ok(v) => v,
+                Err(_) => {
+                    report.missing.push(path);
+                    continue;
+                }
+            };
+            let root = match &archive.nodes[..] {
+                [U8Node::Dir { name, .. }] if name == "." => "./",
+                _ => "",
+            };
+            let bmg_path = format!("{}{}", root, MESSAGE_FILE);
+            let bmg = match archive.get_file(&bmg_path) {
+                Some(v) => merge_bmg(v, &messages).map_err(|e| format!("{}: {}", path, e))?,
+                None => continue,
+            };
+            archive.set_file(&bmg_path, bmg);
+            let name = path.rsplit('/').next().unwrap();
+            fs::write(out.join(SCENE_UI_DIR).join(name), write_szs(&archive))
+                .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+            written += 1;
+        }
+        report.message_archives += written;
+
+        if written == 0 {
+            report.warnings.push(format!(
+                "The source has no Scene/UI archives in {}, so the {} names are not written.",
+                language.as_str(),
+                region.as_str()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Cup and track names in `language` by message ID, in the slots the build copies the tracks to
+    pub fn message_table(&self, language: Language) -> BTreeMap<u32, String> {
+        let editor = &self.tracks.editor;
+        let mut messages = BTreeMap::new();
+        for (i, (list, cup)) in editor.lecode_cups().into_iter().enumerate() {
+            let name = match cup.kind {
+                MenuCupKind::Custom(n) => editor.list_of(list)[n].name_in(language).to_string(),
+                MenuCupKind::Wiimm => cup.name,
+                // The game has the names of its own cups
+                MenuCupKind::Nintendo(_) => continue,
+            };
+            messages.insert(MID_CUP_NAME + i as u32, name);
+        }
+        for (i, track) in self.custom_tracks().enumerate() {
+            let name = editor.template.to_bmg(track, Some(language));
+            messages.insert(MID_TRACK_NAME + (FIRST_CUSTOM_SLOT + i) as u32, name);
+        }
+        // The random slots of the Wiimm cup are below the custom tracks
+        if editor.mode.wiimm_cup {
+            for slot in editor.wiimm.slots.iter().filter(|v| v.shown) {
+                messages.insert(MID_TRACK_NAME + slot.pool.slot() as u32, slot.name().to_string());
+            }
+        }
+        messages
+    }
+
+    /// Icons of all cups stacked vertically in one image, as LE-CODE reads them.
+    /// They are in the order of the message IDs, with a placeholder for each Nintendo cup.
+    pub fn cup_icon_sheet(&self) -> RgbaImage {
+        let cups = self.tracks.editor.lecode_cups();
+        let mut sheet = RgbaImage::new(ICON_SIZE, ICON_SIZE * cups.len() as u32);
+        for (i, (_, cup)) in cups.into_iter().enumerate() {
+            let icon = match cup.icon {
+                Some(v) => v.to_rgba(),
+                None => placeholder_icon(&cup.name),
+            };
+            image::imageops::replace(&mut sheet, &icon, 0, (ICON_SIZE as usize * i) as i64);
+        }
+        sheet
+    }
+
+    /// Cup and slot tables of LE-CODE, in the order of `lecode_cups`.
+    /// The slots below `FIRST_CUSTOM_SLOT` keep their property and music from `original`.
+    fn lecode_tables(&self, original: &LeBinary) -> LeBinary {
+        let editor = &self.tracks.editor;
+        // Slot of the first track of each group, by list and cup
+        let mut next = FIRST_CUSTOM_SLOT as u32;
+        let mut group_slots = |cups: &[Cup]| -> Vec<Vec<u32>> {
+            cups.iter()
+                .map(|cup| {
+                    let slots = cup.groups().into_iter().map(|v| next + v.start as u32).collect();
+                    next += cup.trackset.len() as u32;
+                    slots
+                })
+                .collect()
+        };
+        let racing = group_slots(&editor.cups);
+        let battle = group_slots(&editor.battle_cups);
+
+        let (mut racing_cups, mut battle_cups) = (vec![], vec![]);
+        for (list, cup) in editor.lecode_cups() {
+            let slots: Vec<u32> = match (cup.kind, list) {
+                (MenuCupKind::Nintendo(n), _) => nintendo_tracks(list, n).iter().map(|v| v._as_id().1 as u32).collect(),
+                (MenuCupKind::Custom(n), CupList::Racing) => racing[n].clone(),
+                (MenuCupKind::Custom(n), CupList::Battle) => battle[n].clone(),
+                (MenuCupKind::Wiimm, _) => RandomPool::VALUES.iter().map(|v| v.slot() as u32).collect(),
+            };
+            // `check_limits` makes sure every cup has the size of its list
+            match list {
+                CupList::Racing => racing_cups.extend(<[u32; 4]>::try_from(slots).ok()),
+                CupList::Battle => battle_cups.extend(<[u32; 5]>::try_from(slots).ok()),
+            }
+        }
+
+        let mut le = LeBinary {
+            settings: self.settings.clone(),
+            racing_cups,
+            battle_cups,
+            property: original.property.clone(),
+            music: original.music.clone(),
+            flags: original.flags.clone(),
+        };
+        for table in [&mut le.property, &mut le.music, &mut le.flags] {
+            table.resize(FIRST_CUSTOM_SLOT, 0);
+        }
+        for track in self.custom_tracks() {
+            le.property.push(track.property._as_id().1);
+            le.music.push(track.music._as_id().0);
+            le.flags.push(track_flags(track) as u8);
+        }
+        le
+    }
+
+    /// Racing tracks, then arenas, in the order of their slots from `FIRST_CUSTOM_SLOT`
+    fn custom_tracks(&self) -> impl Iterator<Item = &Track> {
+        let editor = &self.tracks.editor;
+        editor.cups.iter().chain(&editor.battle_cups).flat_map(|v| v.trackset.iter())
+    }
+}
+
+/// Grey icon with the name of a Nintendo cup, which LE-CODE shows in place of its original icon
+fn placeholder_icon(name: &str) -> RgbaImage {
+    let template = IconTemplate {
+        background: Background::Solid,
+        top: Color32::from_gray(0x60),
+        content: IconContent::Label,
+        label: name.to_string(),
+        ..Default::default()
+    };
+    template.render(&Cup::default(0), 0)
+}
+
+/// Why a cheat code is left out of the GCT of `region`.
+/// Codes without any body are placeholders and have no problem.
+pub fn code_problem(code: &CodeStruct, region: Region) -> Option<&'static str> {
+    let digits: Vec<char> = code.body(region).chars().filter(|v| !v.is_whitespace()).collect();
+    if digits.is_empty() {
+        if Region::VALUES.iter().any(|v| !code.body(*v).trim().is_empty()) {
+            return Some("has no code");
+        }
+        return None;
+    }
+    if digits.iter().any(|v| !v.is_ascii_hexdigit()) || digits.len() % 16 != 0 {
+        return Some("is not made of complete lines");
+    }
+    None
+}
+
+/// GCT of the complete codes of `region`, or `None` when there is none
+fn encode_gct(codes: &[CodeStruct], region: Region, warnings: &mut Vec<String>) -> Option<Vec<u8>> {
+    let mut gct = GCT_HEADER.to_vec();
+    for code in codes {
+        if let Some(problem) = code_problem(code, region) {
+            let name = if code.name.is_empty() { "(no name)" } else { &code.name };
+            warnings.push(format!("Cheat code {} {} for {} and is left out.", name, problem, region.as_str()));
+            continue;
+        }
+        let digits: Vec<char> = code.body(region).chars().filter(|v| !v.is_whitespace()).collect();
+        for pair in digits.chunks(2) {
+            let byte: String = pair.iter().collect();
+            gct.push(u8::from_str_radix(&byte, 16).unwrap());
+        }
+    }
+    if gct.len() == GCT_HEADER.len() {
+        return None;
+    }
+    gct.extend_from_slice(&GCT_FOOTER);
+    Some(gct)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apps::tracks::GroupFlag, helpers::import::{FLAG_CHILD, FLAG_HEADER}};
+
+    /// Tables of a binary with nothing in the custom slots
+    fn original() -> LeBinary {
+        LeBinary {
+            settings: Default::default(),
+            racing_cups: vec![],
+            battle_cups: vec![],
+            property: (0..FIRST_CUSTOM_SLOT as u8).collect(),
+            music: vec![0x75; FIRST_CUSTOM_SLOT],
+            flags: vec![0; FIRST_CUSTOM_SLOT],
+        }
+    }
+
+    #[test]
+    fn arena_cups() {
+        let mut dist = Distro::default();
+        let editor = &mut dist.tracks.editor;
+        // A group of 2 tracks fills the second slot of the racing cup
+        let track = editor.cups[0].trackset[1].clone();
+        editor.cups[0].trackset.insert(1, track);
+        editor.cups[0].trackset[1].flag = GroupFlag::Header;
+        editor.cups[0].trackset[2].flag = GroupFlag::Child;
+        editor.battle_cups = vec![Cup::battle(0), Cup::battle(1)];
+        assert!(editor.check_limits(&profile(dist.settings.lecode_build).limits).is_empty());
+
+        let le = dist.lecode_tables(&original());
+        let custom = FIRST_CUSTOM_SLOT as u32;
+        assert_eq!(le.racing_cups.len(), dist.tracks.editor.menu_cups(CupList::Racing).len());
+        assert!(le.racing_cups.contains(&[custom, custom + 1, custom + 3, custom + 4]));
+        assert_eq!(le.battle_cups, [[0, 1, 2, 3, 4], [5, 6, 7, 8, 9]].map(|v| v.map(|i| custom + 5 + i)));
+        assert_eq!(le.property.len(), FIRST_CUSTOM_SLOT + 15);
+        assert_eq!(le.property[..FIRST_CUSTOM_SLOT], original().property);
+        let arena = &dist.tracks.editor.battle_cups[0].trackset[0];
+        assert_eq!(le.property[FIRST_CUSTOM_SLOT + 5], arena.property._as_id().1);
+        assert_eq!(le.flags[FIRST_CUSTOM_SLOT + 1..FIRST_CUSTOM_SLOT + 3], [FLAG_HEADER as u8, FLAG_CHILD as u8]);
+    }
+
+    #[test]
+    fn original_arenas() {
+        let dist = Distro::default();
+        let le = dist.lecode_tables(&original());
+        let arenas: Vec<u32> = nintendo_tracks(CupList::Battle, 1).iter().map(|v| v._as_id().1 as u32).collect();
+        assert_eq!(le.battle_cups.len(), 2);
+        assert_eq!(le.battle_cups[1].to_vec(), arenas);
+        assert_eq!(le.property.len(), FIRST_CUSTOM_SLOT + 4);
+    }
+}
@@ -0,0 +1,525 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use aes::{
+    cipher::{generic_array::GenericArray, BlockDecrypt, KeyInit},
+    Aes128,
+};
+
+const WII_MAGIC: u32 = 0x5D1C9EA3;
+const WBFS_MAGIC: &'static [u8; 4] = b"WBFS";
+const PARTITION_TABLE: u64 = 0x40000;
+const CLUSTER_SIZE: u64 = 0x8000;
+const CLUSTER_DATA_SIZE: u64 = 0x7C00;
+const CLUSTER_HASH_SIZE: usize = 0x400;
+// Dual layer discs have 143432 * 2 Wii sectors
+const WII_SECTORS_PER_DISC: u64 = 143432 * 2;
+/// Which common key encrypts the title key: 0 for most discs, 1 for Korean discs
+const TICKET_COMMON_KEY_INDEX: usize = 0x1f1;
+
+/// Reader for a Mario Kart Wii disc image.
+/// Plain ISO and single-file WBFS containers are supported.
+/// Only the data partition is read, and clusters are decrypted on demand.
+pub struct DiscImage {
+    file: File,
+    container: Container,
+    /// Absolute offset to the encrypted partition data
+    data_offset: u64,
+    title_key: [u8; 16],
+    /// Game ID stored in boot.bin of the partition (e.g. RMCP)
+    pub game_id: String,
+    fst: Vec<FstEntry>,
+}
+
+enum Container {
+    Iso,
+    Wbfs {
+        /// WBFS sector size as a power of two
+        sector_shift: u8,
+        /// Physical WBFS sector for each disc sector, 0 if not stored
+        wlba: Vec<u16>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct FstEntry {
+    /// Full path from the partition root, without the leading slash
+    pub path: String,
+    pub offset: u64,
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+impl DiscImage {
+    pub fn open(path: &Path, common_key_path: &Path) -> Result<Self, String> {
+        let common_key = read_common_key(common_key_path)?;
+        let mut file = File::open(path).map_err(|e| format!("Failed to open disc image: {}", e))?;
+
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic)
+            .map_err(|e| format!("Failed to read disc image: {}", e))?;
+        let container = if &magic == WBFS_MAGIC {
+            read_wbfs_header(&mut file)?
+        } else {
+            Container::Iso
+        };
+
+        let mut disc = Self {
+            file,
+            container,
+            data_offset: 0,
+            title_key: [0; 16],
+            game_id: String::new(),
+            fst: vec![],
+        };
+
+        let header = disc.read_raw(0, 0x20)?;
+        if as_u32_be(&header[0x18..0x1c]) != WII_MAGIC {
+            return Err("The selected file is not a Wii disc image.".to_string());
+        }
+
+        let partition = disc.find_data_partition()?;
+        let ticket = disc.read_raw(partition, 0x2c0)?;
+        let mut title_key = [0u8; 16];
+        title_key.copy_from_slice(&ticket[0x1bf..0x1cf]);
+        let mut iv = [0u8; 16];
+        iv[..8].copy_from_slice(&ticket[0x1dc..0x1e4]);
+        decrypt_cbc(&common_key, &iv, &mut title_key);
+
+        disc.title_key = title_key;
+        disc.data_offset = partition + ((as_u32_be(&ticket[0x2b8..0x2bc]) as u64) << 2);
+
+        let boot = disc.read_partition(0, 0x440)?;
+        if &boot[0x18..0x1c] != &WII_MAGIC.to_be_bytes() {
+            return Err(format!(
+                "Failed to decrypt the partition. The disc needs the {}, check the selected key file.",
+                common_key_name(ticket[TICKET_COMMON_KEY_INDEX])
+            ));
+        }
+        disc.game_id = String::from_utf8_lossy(&boot[0..4]).to_string();
+
+        let fst_offset = (as_u32_be(&boot[0x424..0x428]) as u64) << 2;
+        let fst_size = (as_u32_be(&boot[0x428..0x42c]) as u64) << 2;
+        let fst = disc.read_partition(fst_offset, fst_size as usize)?;
+        disc.fst = parse_fst(&fst)?;
+
+        Ok(disc)
+    }
+
+    /// Read a whole file from the data partition.
+    /// `path` is relative to the partition root, e.g. `Race/Course/castle_course.szs`
+    pub fn read_file(&mut self, path: &str) -> Result<Vec<u8>, String> {
+        let entry = match self
+            .fst
+            .iter()
+            .find(|v| !v.is_dir && v.path.eq_ignore_ascii_case(path))
+        {
+            Some(v) => v.clone(),
+            None => return Err(format!("{} is not found in the disc image", path)),
+        };
+        self.read_partition(entry.offset, entry.size as usize)
+    }
+
+    /// List files directly inside `dir` (e.g. `Race/Course`)
+    pub fn list_dir(&self, dir: &str) -> Vec<FstEntry> {
+        let prefix = format!("{}/", dir.trim_end_matches('/'));
+        self.fst
+            .iter()
+            .filter(|v| {
+                !v.is_dir
+                    && v.path.len() > prefix.len()
+                    && v.path[..prefix.len()].eq_ignore_ascii_case(&prefix)
+                    && !v.path[prefix.len()..].contains('/')
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Read decrypted bytes at the given offset of the partition data
+    fn read_partition(&mut self, offset: u64, size: usize) -> Result<Vec<u8>, String> {
+        let mut out: Vec<u8> = Vec::with_capacity(size);
+        let mut cluster = offset / CLUSTER_DATA_SIZE;
+        let mut inner = (offset % CLUSTER_DATA_SIZE) as usize;
+
+        while out.len() < size {
+            let data = self.read_cluster(cluster)?;
+            let take = (data.len() - inner).min(size - out.len());
+            out.extend_from_slice(&data[inner..inner + take]);
+            inner = 0;
+            cluster += 1;
+        }
+
+        Ok(out)
+    }
+
+    fn read_cluster(&mut self, cluster: u64) -> Result<Vec<u8>, String> {
+        let raw = self.read_raw(self.data_offset + cluster * CLUSTER_SIZE, CLUSTER_SIZE as usize)?;
+        let mut iv = [0u8; 16];
+        iv.copy_from_slice(&raw[0x3d0..0x3e0]);
+        let mut data = raw[CLUSTER_HASH_SIZE..].to_vec();
+        decrypt_cbc(&self.title_key, &iv, &mut data);
+        Ok(data)
+    }
+
+    /// Read bytes at the given offset of the disc, resolving the WBFS sector table if necessary
+    fn read_raw(&mut self, offset: u64, size: usize) -> Result<Vec<u8>, String> {
+        let mut out = vec![0u8; size];
+
+        match &self.container {
+            Container::Iso => {
+                self.file
+                    .seek(SeekFrom::Start(offset))
+                    .and_then(|_| self.file.read_exact(&mut out))
+                    .map_err(|e| format!("Failed to read disc image: {}", e))?;
+            }
+            Container::Wbfs { sector_shift, wlba } => {
+                let sector_size = 1u64 << sector_shift;
+                let mut done = 0usize;
+                while done < size {
+                    let pos = offset + done as u64;
+                    let sector = (pos >> sector_shift) as usize;
+                    let inner = pos & (sector_size - 1);
+                    let take = ((sector_size - inner) as usize).min(size - done);
+                    // Unused sectors are not stored and read as zeros
+                    if let Some(&lba) = wlba.get(sector).filter(|v| **v != 0) {
+                        let physical = ((lba as u64) << sector_shift) + inner;
+                        self.file
+                            .seek(SeekFrom::Start(physical))
+                            .and_then(|_| self.file.read_exact(&mut out[done..done + take]))
+                            .map_err(|e| format!("Failed to read disc image: {}", e))?;
+                    }
+                    done += take;
+                }
+            }
+        }
+
+        Ok(out)
+    }
+
+    fn find_data_partition(&mut self) -> Result<u64, String> {
+        let table = self.read_raw(PARTITION_TABLE, 0x20)?;
+        for group in 0..4 {
+            let count = as_u32_be(&table[group * 8..group * 8 + 4]) as usize;
+            let info = (as_u32_be(&table[group * 8 + 4..group * 8 + 8]) as u64) << 2;
+            if count == 0 {
+                continue;
+            }
+            let entries = self.read_raw(info, count * 8)?;
+            for i in 0..count {
+                let offset = (as_u32_be(&entries[i * 8..i * 8 + 4]) as u64) << 2;
+                // Type 0 is the game data partition
+                if as_u32_be(&entries[i * 8 + 4..i * 8 + 8]) == 0 {
+                    return Ok(offset);
+                }
+            }
+        }
+        Err("The data partition is not found in the disc image.".to_string())
+    }
+}
+
+fn read_wbfs_header(file: &mut File) -> Result<Container, String> {
+    let mut head = [0u8; 12];
+    file.seek(SeekFrom::Start(0))
+        .and_then(|_| file.read_exact(&mut head))
+        .map_err(|e| format!("Failed to read WBFS header: {}", e))?;
+
+    let hd_sector_shift = head[8];
+    let sector_shift = head[9];
+    if sector_shift < 15 || hd_sector_shift < 9 {
+        return Err("The WBFS header is broken.".to_string());
+    }
+
+    // The disc table follows the header; only the first disc slot is read
+    let mut disc_table = [0u8; 1];
+    file.seek(SeekFrom::Start(12))
+        .and_then(|_| file.read_exact(&mut disc_table))
+        .map_err(|e| format!("Failed to read WBFS header: {}", e))?;
+    if disc_table[0] == 0 {
+        return Err("The WBFS file contains no disc.".to_string());
+    }
+
+    let sectors = (WII_SECTORS_PER_DISC >> (sector_shift - 15)) as usize;
+    let mut table = vec![0u8; sectors * 2];
+    file.seek(SeekFrom::Start((1u64 << hd_sector_shift) + 0x100))
+        .and_then(|_| file.read_exact(&mut table))
+        .map_err(|e| format!("Failed to read WBFS sector table: {}", e))?;
+
+    Ok(Container::Wbfs {
+        sector_shift,
+        wlba: table.chunks(2).map(|v| as_u16_be(v)).collect(),
+    })
+}
+
+fn parse_fst(fst: &[u8]) -> Result<Vec<FstEntry>, String> {
+    if fst.len() < 12 {
+        return Err("The file system table is broken.".to_string());
+    }
+    let count = as_u32_be(&fst[8..12]) as usize;
+    if fst.len() < count * 12 {
+        return Err("The file system table is broken.".to_string());
+    }
+    let strings = &fst[count * 12..];
+
+    let name_at = |offset: usize| -> Result<String, String> {
+        let name = strings.get(offset..).ok_or("The file system table is broken.")?;
+        let end = name.iter().position(|v| *v == 0).unwrap_or(name.len());
+        Ok(String::from_utf8_lossy(&name[..end]).to_string())
+    };
+
+    let mut entries: Vec<FstEntry> = Vec::with_capacity(count);
+    // Stack of (directory path, index of the entry after its last child)
+    let mut dirs: Vec<(String, usize)> = vec![(String::new(), count)];
+    for i in 1..count {
+        while dirs.len() > 1 && dirs.last().unwrap().1 <= i {
+            dirs.pop();
+        }
+        let e = &fst[i * 12..i * 12 + 12];
+        let is_dir = e[0] != 0;
+        let name = name_at(((e[1] as usize) << 16) | ((e[2] as usize) << 8) | e[3] as usize)?;
+        let parent = &dirs.last().unwrap().0;
+        let path = if parent.is_empty() { name } else { format!("{}/{}", parent, name) };
+
+        if is_dir {
+            dirs.push((path.clone(), as_u32_be(&e[8..12]) as usize));
+            entries.push(FstEntry {
+                path,
+                offset: 0,
+                size: 0,
+                is_dir,
+            });
+        } else {
+            entries.push(FstEntry {
+                path,
+                offset: (as_u32_be(&e[4..8]) as u64) << 2,
+                size: as_u32_be(&e[8..12]) as u64,
+                is_dir,
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+fn common_key_name(index: u8) -> String {
+    match index {
+        0 => "common key".to_string(),
+        1 => "Korean common key".to_string(),
+        n => format!("common key {}", n),
+    }
+}
+
+/// Common key can be stored as 16 raw bytes (e.g. `common-key.bin`) or as 32 hexadecimal characters
+fn read_common_key(path: &Path) -> Result<[u8; 16], String> {
+    let data = std::fs::read(path).map_err(|e| format!("Failed to read common key file: {}", e))?;
+    let mut key = [0u8; 16];
+
+    if data.len() == 16 {
+        key.copy_from_slice(&data);
+        return Ok(key);
+    }
+
+    let text = String::from_utf8_lossy(&data);
+    let hex: String = text.chars().filter(|v| v.is_ascii_hexdigit()).collect();
+    if hex.len() != 32 {
+        return Err("The common key file must contain 16 bytes of key.".to_string());
+    }
+    for (i, v) in key.iter_mut().enumerate() {
+        *v = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+    }
+    Ok(key)
+}
+
+fn decrypt_cbc(key: &[u8; 16], iv: &[u8; 16], data: &mut [u8]) {
+    let cipher = Aes128::new(GenericArray::from_slice(key));
+    let mut prev = *iv;
+
+    for block in data.chunks_exact_mut(16) {
+        let mut next = [0u8; 16];
+        next.copy_from_slice(block);
+        cipher.decrypt_block(GenericArray::from_mut_slice(block));
+        for (b, p) in block.iter_mut().zip(prev.iter()) {
+            *b ^= p;
+        }
+        prev = next;
+    }
+}
+
+fn as_u32_be(array: &[u8]) -> u32 {
+    ((array[0] as u32) << 24) + ((array[1] as u32) << 16) + ((array[2] as u32) << 8) + ((array[3] as u32) << 0)
+}
+
+fn as_u16_be(array: &[u8]) -> u16 {
+    ((array[0] as u16) << 8) + ((array[1] as u16) << 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::cipher::BlockEncrypt;
+
+    const COMMON_KEY: [u8; 16] = *b"test common key!";
+    const TITLE_KEY: [u8; 16] = *b"test title key!!";
+    const PARTITION: u64 = 0x50000;
+    /// Offset of the encrypted data from the partition
+    const DATA: u64 = 0x20000;
+    const FST: u64 = 0x440;
+    /// File which starts in the first cluster and ends in the second
+    const FILE_OFFSET: u64 = 0x7b00;
+    const FILE_SIZE: usize = 0x200;
+
+    fn encrypt_cbc(key: &[u8; 16], iv: &[u8; 16], data: &mut [u8]) {
+        let cipher = Aes128::new(GenericArray::from_slice(key));
+        let mut prev = *iv;
+        for block in data.chunks_exact_mut(16) {
+            for (b, p) in block.iter_mut().zip(prev.iter()) {
+                *b ^= p;
+            }
+            cipher.encrypt_block(GenericArray::from_mut_slice(block));
+            prev.copy_from_slice(block);
+        }
+    }
+
+    fn put(data: &mut [u8], at: u64, bytes: &[u8]) {
+        data[at as usize..at as usize + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Root, `Race`, `Race/Course` and `Race/Course/castle_course.szs`
+    fn fst() -> Vec<u8> {
+        let entries: [(u32, u32, u32, u32); 4] = [
+            (1, 0, 0, 4),
+            (1, 0, 0, 4),
+            (1, 5, 1, 4),
+            (0, 12, (FILE_OFFSET >> 2) as u32, FILE_SIZE as u32),
+        ];
+        let mut fst: Vec<u8> = vec![];
+        for (kind, name, offset, size) in entries {
+            fst.extend_from_slice(&((kind << 24) | name).to_be_bytes());
+            fst.extend_from_slice(&offset.to_be_bytes());
+            fst.extend_from_slice(&size.to_be_bytes());
+        }
+        fst.extend_from_slice(b"Race\0Course\0castle_course.szs\0");
+        fst
+    }
+
+    fn file_content() -> Vec<u8> {
+        (0..FILE_SIZE).map(|v| v as u8).collect()
+    }
+
+    /// ISO image with one data partition of two clusters
+    fn iso(key_index: u8) -> Vec<u8> {
+        let mut iso = vec![0u8; (PARTITION + DATA + 2 * CLUSTER_SIZE) as usize];
+        put(&mut iso, 0, b"RMCP01");
+        put(&mut iso, 0x18, &WII_MAGIC.to_be_bytes());
+        put(&mut iso, PARTITION_TABLE, &[0, 0, 0, 1]);
+        put(&mut iso, PARTITION_TABLE + 4, &((PARTITION_TABLE + 0x20) as u32 >> 2).to_be_bytes());
+        put(&mut iso, PARTITION_TABLE + 0x20, &((PARTITION >> 2) as u32).to_be_bytes());
+
+        // Ticket
+        let mut iv = [0u8; 16];
+        iv[..8].copy_from_slice(b"RMCPTEST");
+        let mut title_key = TITLE_KEY;
+        encrypt_cbc(&COMMON_KEY, &iv, &mut title_key);
+        put(&mut iso, PARTITION + 0x1bf, &title_key);
+        put(&mut iso, PARTITION + 0x1dc, &iv[..8]);
+        iso[(PARTITION as usize) + TICKET_COMMON_KEY_INDEX] = key_index;
+        put(&mut iso, PARTITION + 0x2b8, &((DATA >> 2) as u32).to_be_bytes());
+
+        let mut plain = vec![0u8; 2 * CLUSTER_DATA_SIZE as usize];
+        put(&mut plain, 0, b"RMCP01");
+        put(&mut plain, 0x18, &WII_MAGIC.to_be_bytes());
+        put(&mut plain, 0x424, &((FST >> 2) as u32).to_be_bytes());
+        put(&mut plain, 0x428, &((fst().len() as u32 + 3) >> 2).to_be_bytes());
+        put(&mut plain, FST, &fst());
+        put(&mut plain, FILE_OFFSET, &file_content());
+        for (i, data) in plain.chunks_exact_mut(CLUSTER_DATA_SIZE as usize).enumerate() {
+            let cluster = PARTITION + DATA + i as u64 * CLUSTER_SIZE;
+            let iv = [i as u8 + 1; 16];
+            put(&mut iso, cluster + 0x3d0, &iv);
+            encrypt_cbc(&TITLE_KEY, &iv, data);
+            put(&mut iso, cluster + CLUSTER_HASH_SIZE as u64, data);
+        }
+        iso
+    }
+
+    /// WBFS file of `iso` with 32 KiB sectors, stored in reverse order after the sector table
+    fn wbfs(iso: &[u8]) -> Vec<u8> {
+        let shift = 15;
+        let table = (1 << 9) + 0x100;
+        let sectors = WII_SECTORS_PER_DISC as usize;
+        let first = (table + sectors * 2 + (1 << shift) - 1) >> shift;
+        let used: Vec<usize> = (0..iso.len() >> shift)
+            .filter(|v| iso[v << shift..(v + 1) << shift].iter().any(|v| *v != 0))
+            .collect();
+
+        let mut wbfs = vec![0u8; (first + used.len()) << shift];
+        put(&mut wbfs, 0, WBFS_MAGIC);
+        wbfs[8] = 9;
+        wbfs[9] = shift as u8;
+        wbfs[12] = 1;
+        for (i, sector) in used.iter().rev().enumerate() {
+            let lba = first + i;
+            put(&mut wbfs, (table + sector * 2) as u64, &(lba as u16).to_be_bytes());
+            put(&mut wbfs, (lba << shift) as u64, &iso[sector << shift..(sector + 1) << shift]);
+        }
+        wbfs
+    }
+
+    /// Write `data` and the common key into temporary files named after `name`
+    fn open(name: &str, data: &[u8]) -> Result<DiscImage, String> {
+        let dir = std::env::temp_dir();
+        let image = dir.join(format!("mkw-distro-tool-{}", name));
+        let key = dir.join(format!("mkw-distro-tool-{}.key", name));
+        std::fs::write(&image, data).unwrap();
+        std::fs::write(&key, COMMON_KEY).unwrap();
+        let disc = DiscImage::open(&image, &key);
+        std::fs::remove_file(&image).ok();
+        std::fs::remove_file(&key).ok();
+        disc
+    }
+
+    #[test]
+    fn file_system_table() {
+        let entries = parse_fst(&fst()).unwrap();
+        let paths: Vec<&str> = entries.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, ["Race", "Race/Course", "Race/Course/castle_course.szs"]);
+        assert!(entries[1].is_dir && !entries[2].is_dir);
+        assert_eq!((entries[2].offset, entries[2].size), (FILE_OFFSET, FILE_SIZE as u64));
+
+        let mut broken = fst();
+        broken[2 * 12 + 3] = 0xff;
+        assert!(parse_fst(&broken).is_err());
+        assert!(parse_fst(&fst()[..30]).is_err());
+    }
+
+    #[test]
+    fn decrypt_iso() {
+        let mut disc = open("test.iso", &iso(0)).unwrap();
+        assert_eq!(disc.game_id, "RMCP");
+        let files: Vec<String> = disc.list_dir("Race/Course").into_iter().map(|v| v.path).collect();
+        assert_eq!(files, ["Race/Course/castle_course.szs"]);
+        assert_eq!(disc.read_file("race/course/castle_course.szs").unwrap(), file_content());
+        assert!(disc.read_file("Race/Course/beginner_course.szs").is_err());
+    }
+
+    #[test]
+    fn wbfs_sectors() {
+        let iso = iso(0);
+        let mut disc = open("test.wbfs", &wbfs(&iso)).unwrap();
+        assert_eq!(disc.read_raw(0, iso.len()).unwrap(), iso);
+        assert_eq!(disc.read_file("Race/Course/castle_course.szs").unwrap(), file_content());
+    }
+
+    #[test]
+    fn name_the_needed_key() {
+        // The title key is encrypted with the given key, but the ticket asks for the Korean key
+        let mut data = iso(1);
+        data[(PARTITION + 0x1bf) as usize] ^= 1;
+        match open("korean.iso", &data) {
+            Err(e) => assert!(e.contains("Korean common key"), "{}", e),
+            Ok(_) => panic!("a wrong key decrypts the disc"),
+        }
+    }
+}
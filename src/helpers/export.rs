@@ -0,0 +1,178 @@
+use std::{fs, path::Path};
+
+use crate::{
+    apps::{
+        settings::{engine_label, EngineProbMode},
+        source::{COURSE_DIR, REL_DIR, SCENE_UI_DIR},
+        tracks::{GroupFlag, Id, Track, TrackDefinition},
+        Region, SettingApp,
+    },
+    Distro,
+};
+
+use super::{
+    import::{FLAG_CHILD, FLAG_HEADER, FLAG_NEW},
+    lpar, profile,
+};
+
+impl TrackDefinition {
+    /// Racing cups as LE-DEF text, which `parse_track_definition` reads back
+    pub fn to_le_def(&self) -> String {
+        let mut s = String::from("#LE-DEF\n\n[RACING-TRACK-LIST]\n%LE-FLAGS = 1\n");
+        s.push_str(&format!("%WIIMM-CUP = {}\n", self.mode.wiimm_cup as u8));
+        s.push_str(match (self.mode.nintendo, self.mode.nin_swap) {
+            (false, _) => "N N$NONE\n",
+            (true, false) => "N N$SHOW\n",
+            (true, true) => "N N$SWAP\n",
+        });
+
+        for cup in &self.cups {
+            s.push_str(&format!("\nC {}\n", quote(&cup.name)));
+            for track in &cup.trackset {
+                s.push_str(&track_line(track, &self.template.to_plain(track, None)));
+            }
+        }
+        s
+    }
+
+    pub fn export_le_def(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_le_def()).map_err(|e| format!("Failed to write the definition: {}", e))
+    }
+}
+
+/// `T music; property; flags; file; name; ""` with the file name next to the definition
+fn track_line(track: &Track, name: &str) -> String {
+    let flags = track_flags(track);
+    let file = Path::new(&track.filename)
+        .file_stem()
+        .map(|v| v.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!(
+        "T {}; {}; {}; {}; {}; \"\"\n",
+        slot(track.music, track.music._as_id().0),
+        slot(track.property, track.property._as_id().1),
+        flag_letters(flags),
+        quote(&file),
+        quote(name)
+    )
+}
+
+/// Flags of the track in LE-DEF and in the slot table of LE-CODE
+pub fn track_flags(track: &Track) -> u32 {
+    let flags = if track.new { FLAG_NEW } else { 0 };
+    flags
+        | match track.flag {
+            GroupFlag::None => 0,
+            GroupFlag::Header => FLAG_HEADER,
+            GroupFlag::Child => FLAG_CHILD,
+        }
+}
+
+/// Slot name like `T11`, or the number for slots without a name
+fn slot(id: Id, number: u8) -> String {
+    id.slot_name().unwrap_or_else(|| format!("{:#04x}", number))
+}
+
+fn flag_letters(flags: u32) -> String {
+    let letters: String = [(FLAG_NEW, 'N'), (FLAG_HEADER, 'H'), (FLAG_CHILD, 'G')]
+        .into_iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, c)| c)
+        .collect();
+    if letters.is_empty() {
+        "-".to_string()
+    } else {
+        letters
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl SettingApp {
+    /// LPAR text for `wlect patch --lpar` with the parameters the target LE-CODE build reads, and ENGINE in the classes of the 200cc setting
+    pub fn to_lpar(&self) -> String {
+        let p = &self.engine_probs;
+        let classes: Vec<&str> = [EngineProbMode::Low, EngineProbMode::Mid, EngineProbMode::High]
+            .into_iter()
+            .map(|v| engine_label(self.toggle_200cc, v))
+            .collect();
+
+        let target = profile::profile(self.lecode_build);
+        let mut s = format!("#LPAR\n\n# For {}\n\n[LECODE-PARAMETERS]\n\n", target.name);
+        for key in lpar::KEYS.into_iter().filter(|v| target.supports(v)) {
+            let value = match key.name {
+                "ENABLE-200CC" => (self.toggle_200cc as u8).to_string(),
+                "ENGINE" => {
+                    s.push_str(&format!("# {}\n", classes.join(", ")));
+                    format!("{},{},{}", p.low, p.mid, p.high)
+                }
+                "PERF-MONITOR" => (self.toggle_perf as u8).to_string(),
+                "CUSTOM-TT" => (self.toggle_ct_tt as u8).to_string(),
+                "TT-200CC" => (self.toggle_tt_200cc as u8).to_string(),
+                "XPFLAGS" => (self.toggle_custom_presence as u8).to_string(),
+                "BLOCK-TRACK" => self.prevent_selection_online.to_string(),
+                "SPEEDOMETER" => (self.toggle_som as u8).to_string(),
+                "DEBUG" => (self.toggle_debug as u8).to_string(),
+                "ITEM-CHEAT" => (self.item_cheat as u8).to_string(),
+                "CHEAT-MODE" => (self.cheat_mode as u8).to_string(),
+                "DRAG-BLUE-SHELL" => (self.toggle_drag_blue as u8).to_string(),
+                "THCLOUD-TIME" => self.time_cloud.to_string(),
+                "BT-WORLDWIDE" => (self.toggle_bt_worldwide as u8).to_string(),
+                "VS-WORLDWIDE" => (self.toggle_vs_worldwide as u8).to_string(),
+                "BT-REGION" => self.bt_region.to_string(),
+                "VS-REGION" => self.vs_region.to_string(),
+                "BLOCK-TEXTURES" => (self.toggle_block_textures as u8).to_string(),
+                "SPEED-LIMIT" => self.speed_limit.to_string(),
+                _ => continue,
+            };
+            // Values line up at the second tab stop
+            let tabs = if key.name.len() < 8 { "\t\t" } else { "\t" };
+            s.push_str(&format!("{}{}= {}\n", key.name, tabs, value));
+        }
+        s
+    }
+
+    pub fn export_lpar(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_lpar()).map_err(|e| format!("Failed to write the settings: {}", e))
+    }
+}
+
+impl Distro {
+    /// Riivolution XML which loads a build from the folder `root` on the SD card
+    pub fn to_riivolution(&self, root: &str) -> String {
+        let regions: Vec<Region> = Region::VALUES
+            .into_iter()
+            .filter(|v| self.settings.regions.contains(v))
+            .collect();
+
+        let mut s = String::from("<wiidisc version=\"1\">\n\t<id game=\"RMC\">\n");
+        for region in &regions {
+            s.push_str(&format!("\t\t<region type=\"{}\" />\n", region.riivolution_type()));
+        }
+        s.push_str("\t</id>\n\t<options>\n");
+        s.push_str(&format!("\t\t<section name=\"{}\">\n", root));
+        s.push_str(&format!("\t\t\t<option name=\"{}\">\n", root));
+        s.push_str(&format!("\t\t\t\t<choice name=\"Enabled\">\n\t\t\t\t\t<patch id=\"{}\" />\n\t\t\t\t</choice>\n", root));
+        s.push_str("\t\t\t</option>\n\t\t</section>\n\t</options>\n");
+
+        s.push_str(&format!("\t<patch id=\"{}\">\n", root));
+        for dir in [COURSE_DIR, SCENE_UI_DIR] {
+            s.push_str(&format!(
+                "\t\t<folder external=\"/{}/{}\" disc=\"/{}\" create=\"true\" />\n",
+                root, dir, dir
+            ));
+        }
+        // Each disc reads only the binary of its region
+        for region in &regions {
+            let bin = format!("{}/lecode-{}.bin", REL_DIR, region.lecode_name());
+            s.push_str(&format!(
+                "\t\t<file external=\"/{}/{}\" disc=\"/{}\" create=\"true\" />\n",
+                root, bin, bin
+            ));
+        }
+        s.push_str("\t</patch>\n</wiidisc>\n");
+        s
+    }
+}
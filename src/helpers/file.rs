@@ -1,48 +1,151 @@
-use std::{fs::File, io::Write, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
 
 use crate::{
-    apps::tracks::{Cup, Track},
+    apps::tracks::{Cup, Icon, IconFilter, IconFit, Track, ICON_SIZE, THUMBNAIL_SIZE},
     Distro,
 };
 
-use super::parser::decode_image;
+use super::parser::resize_image;
 
 const IMAGE_FORMAT: &'static [&'static str] = &["png", "jpg", "jpeg", "gif", "bmp"];
+/// Records which recovery file belongs to which project, so it can be found on the next startup
+const RECOVERY_MARKER: &'static str = "recovery.txt";
+
+pub const DIALOG_PROJECT: &'static str = "project";
+pub const DIALOG_IMAGE: &'static str = "image";
+pub const DIALOG_TRACK: &'static str = "track";
+pub const DIALOG_SOURCE: &'static str = "source";
+pub const DIALOG_BUILD: &'static str = "build";
+
+/// Last folders used in file dialogs, keyed by purpose
+static LAST_DIRS: Mutex<BTreeMap<String, PathBuf>> = Mutex::new(BTreeMap::new());
 
 impl Distro {
+    /// Write into a temporary file first and replace the target,
+    /// so that a failure while writing never destroys the previous save
     pub fn save_project(&self, path: &PathBuf) -> Result<(), std::io::Error> {
-        let mut f = File::create(path).unwrap();
         let b = self.encode();
-        f.write_all(&b[..])
+        let mut temp = path.clone().into_os_string();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+
+        let mut f = File::create(&temp)?;
+        f.write_all(&b[..])?;
+        f.sync_all()?;
+        drop(f);
+        fs::rename(&temp, path)
+    }
+
+    /// Recovery file next to the project, or in the application data folder for unsaved projects
+    pub fn recovery_path(&self) -> Option<PathBuf> {
+        match &self.path {
+            Some(path) => {
+                let mut p = path.clone().into_os_string();
+                p.push(".recovery");
+                Some(PathBuf::from(p))
+            }
+            None => app_data_dir().map(|v| v.join("unsaved.mkprj.recovery")),
+        }
+    }
+
+    /// Save the current state into the recovery file
+    pub fn autosave(&self) -> Result<(), std::io::Error> {
+        let recovery = match self.recovery_path() {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        if let Some(dir) = recovery.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        self.save_project(&recovery)?;
+
+        if let Some(dir) = app_data_dir() {
+            fs::create_dir_all(&dir)?;
+            let project = match &self.path {
+                Some(v) => v.to_string_lossy().to_string(),
+                None => String::new(),
+            };
+            fs::write(dir.join(RECOVERY_MARKER), format!("{}\n{}", recovery.to_string_lossy(), project))?;
+        }
+        Ok(())
+    }
+
+    /// Delete the recovery file of the current project, after it's saved or discarded
+    pub fn remove_recovery(&self) {
+        if let Some(recovery) = self.recovery_path() {
+            let _ = fs::remove_file(recovery);
+        }
+        if let Some(dir) = app_data_dir() {
+            let _ = fs::remove_file(dir.join(RECOVERY_MARKER));
+        }
+    }
+
+    /// Restore the project from a recovery file.
+    /// The restored state is unsaved, and belongs to `project` if it was saved before.
+    /// A recovery file which can't be opened is moved aside, where the autosave doesn't replace it.
+    pub fn restore_recovery(&mut self, recovery: &PathBuf, project: Option<PathBuf>) {
+        if self.open_project(recovery) {
+            self.path = project;
+            self.history.mark_modified();
+            let _ = fs::remove_file(recovery);
+            return;
+        }
+        let mut kept = recovery.clone().into_os_string();
+        kept.push(".failed");
+        if fs::rename(recovery, &kept).is_ok() {
+            self.err_msg
+                .push_str(&format!("\nThe recovery file is kept as {}.", kept.to_string_lossy()));
+        }
     }
 
-    pub fn open_project(&mut self, path: &PathBuf) {
+    /// Returns false if the file could not be opened
+    pub fn open_project(&mut self, path: &PathBuf) -> bool {
         match self.decode(path) {
             Ok(v) => {
                 self.path = v.path;
                 self.codes = v.codes;
                 self.settings = v.settings;
                 self.tracks = v.tracks;
+                self.reset_history();
+                true
             }
             Err(err) => {
-                self.err_msg = err;
-                self.confirm_dialog = true;
+                self.show_message("Error", err.to_string());
+                false
             }
-        };
+        }
     }
 }
 
 impl Cup {
     pub fn open_image(&mut self) {
-        match rfd::FileDialog::new()
+        self.icon.open_image();
+    }
+}
+
+impl Icon {
+    pub fn open_image(&mut self) {
+        match file_dialog(DIALOG_IMAGE)
             .add_filter("Image file", IMAGE_FORMAT)
             .pick_file()
         {
             Some(path) => {
-                match decode_image(path.clone()) {
+                remember_dir(DIALOG_IMAGE, &path);
+                let source = match fs::read(&path) {
+                    Ok(v) => v,
+                    Err(_) => return,
+                };
+                match resize_image(&source, ICON_SIZE, self.fit, self.filter) {
                     Ok(v) => {
-                        self.icon.filename = format!("{}", path.file_name().unwrap().to_str().unwrap());
-                        self.icon.image = v;
+                        self.filename = format!("{}", path.file_name().unwrap().to_str().unwrap());
+                        self.image = v;
+                        self.source = source;
                     }
                     Err(_) => {}
                 };
@@ -54,13 +157,85 @@ impl Cup {
 
 impl Track {
     pub fn open_file(&mut self) -> Result<(), &'static str> {
-        match rfd::FileDialog::new()
+        match file_dialog(DIALOG_TRACK)
             .add_filter("*.szs", &["szs"])
             .pick_file()
         {
-            Some(path) => self.filename = format!("{}", path.to_str().unwrap()),
+            Some(path) => {
+                remember_dir(DIALOG_TRACK, &path);
+                self.filename = format!("{}", path.to_str().unwrap())
+            }
             None => return Err("Failed to get szs file"),
         }
         Ok(())
     }
+
+    pub fn open_thumbnail(&mut self) {
+        if let Some(path) = file_dialog(DIALOG_IMAGE)
+            .add_filter("Image file", IMAGE_FORMAT)
+            .pick_file()
+        {
+            remember_dir(DIALOG_IMAGE, &path);
+            let thumbnail = fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|v| resize_image(&v, THUMBNAIL_SIZE, IconFit::Crop, IconFilter::Lanczos3));
+            if let Ok(v) = thumbnail {
+                self.thumbnail = v;
+            }
+        }
+    }
+}
+
+/// File dialog starting in the folder last used for `kind`
+pub fn file_dialog(kind: &str) -> rfd::FileDialog {
+    let dialog = rfd::FileDialog::new();
+    match LAST_DIRS.lock().unwrap().get(kind) {
+        Some(dir) => dialog.set_directory(dir),
+        None => dialog,
+    }
+}
+
+/// Remember the folder of the picked path for the next dialog of `kind`
+pub fn remember_dir(kind: &str, path: &Path) {
+    let dir = if path.is_dir() { Some(path) } else { path.parent() };
+    if let Some(dir) = dir {
+        LAST_DIRS
+            .lock()
+            .unwrap()
+            .insert(kind.to_string(), dir.to_path_buf());
+    }
+}
+
+pub fn last_dirs() -> BTreeMap<String, PathBuf> {
+    LAST_DIRS.lock().unwrap().clone()
+}
+
+pub fn set_last_dirs(dirs: BTreeMap<String, PathBuf>) {
+    *LAST_DIRS.lock().unwrap() = dirs;
+}
+
+/// Recovery file and its project left by a session which did not exit properly
+pub fn find_recovery() -> Option<(PathBuf, Option<PathBuf>)> {
+    let marker = fs::read_to_string(app_data_dir()?.join(RECOVERY_MARKER)).ok()?;
+    let mut lines = marker.lines();
+    let recovery = PathBuf::from(lines.next()?);
+    if !recovery.is_file() {
+        return None;
+    }
+    let project = lines.next().filter(|v| !v.is_empty()).map(PathBuf::from);
+    Some((recovery, project))
+}
+
+/// Per-user folder to store application files
+pub fn app_data_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|v| PathBuf::from(v).join("Library").join("Application Support"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|v| PathBuf::from(v).join(".local").join("share")))
+    };
+    base.map(|v| v.join(crate::APP_NAME))
 }
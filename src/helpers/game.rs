@@ -0,0 +1,85 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::disc::DiscImage;
+
+/// Read access to the files of the game, regardless of where they are stored
+pub trait GameFiles {
+    /// Game ID in sys/boot.bin (e.g. RMCP)
+    fn game_id(&self) -> &str;
+    /// Read a whole file. `path` is relative to the `files` root, e.g. `Race/Course/castle_course.szs`
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>, String>;
+    /// Paths of files directly inside `dir`, relative to the `files` root
+    fn list_dir(&mut self, dir: &str) -> Vec<String>;
+}
+
+impl GameFiles for DiscImage {
+    fn game_id(&self) -> &str {
+        &self.game_id
+    }
+
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>, String> {
+        DiscImage::read_file(self, path)
+    }
+
+    fn list_dir(&mut self, dir: &str) -> Vec<String> {
+        DiscImage::list_dir(self, dir).into_iter().map(|v| v.path).collect()
+    }
+}
+
+/// Game extracted by Dolphin or Wiimms ISO Tools.
+/// Both the `DATA` folder and the `files` folder inside it are accepted.
+pub struct ExtractedGame {
+    files: PathBuf,
+    game_id: String,
+}
+
+impl ExtractedGame {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let (files, sys) = if path.join("files").is_dir() {
+            (path.join("files"), path.join("sys"))
+        } else if path.join("DATA").join("files").is_dir() {
+            (path.join("DATA").join("files"), path.join("DATA").join("sys"))
+        } else {
+            (path.to_path_buf(), path.join("..").join("sys"))
+        };
+
+        if !files.join("Race").is_dir() {
+            return Err("The selected folder does not contain extracted game files.".to_string());
+        }
+
+        let boot = fs::read(sys.join("boot.bin")).map_err(|_| "sys/boot.bin is not found next to the files folder.".to_string())?;
+        if boot.len() < 4 {
+            return Err("sys/boot.bin is broken.".to_string());
+        }
+
+        Ok(Self {
+            files,
+            game_id: String::from_utf8_lossy(&boot[0..4]).to_string(),
+        })
+    }
+}
+
+impl GameFiles for ExtractedGame {
+    fn game_id(&self) -> &str {
+        &self.game_id
+    }
+
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>, String> {
+        fs::read(self.files.join(path)).map_err(|e| format!("Failed to read {}: {}", path, e))
+    }
+
+    fn list_dir(&mut self, dir: &str) -> Vec<String> {
+        let entries = match fs::read_dir(self.files.join(dir)) {
+            Ok(v) => v,
+            Err(_) => return vec![],
+        };
+        entries
+            .filter_map(|v| v.ok())
+            .filter(|v| v.file_type().map(|t| t.is_file()).unwrap_or(false))
+            .map(|v| format!("{}/{}", dir.trim_end_matches('/'), v.file_name().to_string_lossy()))
+            .collect()
+    }
+}
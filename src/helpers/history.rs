@@ -0,0 +1,242 @@
+use egui::Context;
+
+use super::template::NameTemplate;
+use crate::apps::{
+    tracks::{Cup, CupSettings, WiimmCup},
+    CheatCodeApp, CodeStruct, SettingApp, TrackDefApp,
+};
+
+/// Maximum number of commands kept for undo
+const HISTORY_LIMIT: usize = 100;
+/// Serial which no command gets, for a saved state that was trimmed off
+const UNREACHABLE_SERIAL: u64 = u64::MAX;
+
+/// Editable part of `TrackDefApp`, without UI state like selection
+#[derive(Clone, PartialEq)]
+pub struct TrackState {
+    pub mode: CupSettings,
+    pub cups: Vec<Cup>,
+    pub battle_cups: Vec<Cup>,
+    pub template: NameTemplate,
+    pub wiimm: WiimmCup,
+}
+
+/// Editable part of `CheatCodeApp`, without UI state like selection
+#[derive(Clone, PartialEq)]
+pub struct CodeState {
+    pub enabled: bool,
+    pub codes: Vec<CodeStruct>,
+}
+
+/// One finished edit in any editor, holding the state before and after it
+pub enum Command {
+    Tracks { before: TrackState, after: TrackState },
+    Settings { before: SettingApp, after: SettingApp },
+    Codes { before: CodeState, after: CodeState },
+}
+
+struct Entry {
+    /// Serial number to tell whether the state is saved
+    serial: u64,
+    command: Command,
+}
+
+/// Undo/redo stack over `TrackDefApp`, `SettingApp` and `CheatCodeApp`.
+/// Changes are detected by comparing the editors with the last recorded state,
+/// and committed once the pointer is released and no text field is focused,
+/// so that one slider drag or one text input becomes one command.
+pub struct History {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+    last_tracks: TrackState,
+    last_settings: SettingApp,
+    last_codes: CodeState,
+    next_serial: u64,
+    /// Serial of the newest command when saved or opened
+    saved: Option<u64>,
+    /// Editors differ from the last recorded state, but the edit is not finished yet
+    pending: bool,
+    /// Set when the state is restored from somewhere other than the project file
+    modified: bool,
+}
+
+impl TrackState {
+    pub fn of(app: &TrackDefApp) -> Self {
+        Self {
+            mode: app.editor.mode.clone(),
+            cups: app.editor.cups.clone(),
+            battle_cups: app.editor.battle_cups.clone(),
+            template: app.editor.template.clone(),
+            wiimm: app.editor.wiimm.clone(),
+        }
+    }
+
+    fn apply(&self, app: &mut TrackDefApp) {
+        app.editor.mode = self.mode.clone();
+        app.editor.cups = self.cups.clone();
+        app.editor.battle_cups = self.battle_cups.clone();
+        app.editor.template = self.template.clone();
+        app.editor.wiimm = self.wiimm.clone();
+        let len = app.editor.list_ref().len();
+        if app.editor.selected >= len {
+            app.editor.selected = len.saturating_sub(1);
+        }
+    }
+
+    fn differs(&self, app: &TrackDefApp) -> bool {
+        self.mode != app.editor.mode
+            || self.cups != app.editor.cups
+            || self.battle_cups != app.editor.battle_cups
+            || self.template != app.editor.template
+            || self.wiimm != app.editor.wiimm
+    }
+}
+
+impl CodeState {
+    pub fn of(app: &CheatCodeApp) -> Self {
+        Self {
+            enabled: app.enabled,
+            codes: app.codes.to_vec(),
+        }
+    }
+
+    fn apply(&self, app: &mut CheatCodeApp) {
+        app.enabled = self.enabled;
+        app.codes = Box::new(self.codes.clone());
+        if app.selected_code >= app.codes.len() {
+            app.selected_code = app.codes.len() - 1;
+        }
+    }
+
+    fn differs(&self, app: &CheatCodeApp) -> bool {
+        self.enabled != app.enabled || self.codes[..] != app.codes[..]
+    }
+}
+
+impl History {
+    pub fn new(tracks: &TrackDefApp, settings: &SettingApp, codes: &CheatCodeApp) -> Self {
+        Self {
+            undo: vec![],
+            redo: vec![],
+            last_tracks: TrackState::of(tracks),
+            last_settings: settings.clone(),
+            last_codes: CodeState::of(codes),
+            next_serial: 0,
+            saved: None,
+            pending: false,
+            modified: false,
+        }
+    }
+
+    /// Compare the editors with the last recorded state and push commands for finished edits
+    pub fn record(&mut self, ctx: &Context, tracks: &TrackDefApp, settings: &SettingApp, codes: &CheatCodeApp) {
+        let tracks_changed = self.last_tracks.differs(tracks);
+        let settings_changed = self.last_settings != *settings;
+        let codes_changed = self.last_codes.differs(codes);
+        self.pending = tracks_changed || settings_changed || codes_changed;
+
+        if !self.pending || ctx.input().pointer.any_down() || ctx.memory().focus().is_some() {
+            return;
+        }
+
+        if tracks_changed {
+            let after = TrackState::of(tracks);
+            let before = std::mem::replace(&mut self.last_tracks, after.clone());
+            self.push(Command::Tracks { before, after });
+        }
+        if settings_changed {
+            let after = settings.clone();
+            let before = std::mem::replace(&mut self.last_settings, after.clone());
+            self.push(Command::Settings { before, after });
+        }
+        if codes_changed {
+            let after = CodeState::of(codes);
+            let before = std::mem::replace(&mut self.last_codes, after.clone());
+            self.push(Command::Codes { before, after });
+        }
+        self.pending = false;
+    }
+
+    fn push(&mut self, command: Command) {
+        self.undo.push(Entry {
+            serial: self.next_serial,
+            command,
+        });
+        self.next_serial += 1;
+        self.redo.clear();
+        if self.undo.len() > HISTORY_LIMIT {
+            let trimmed = self.undo.remove(0);
+            self.saved = match self.saved {
+                // The saved state is now the oldest one which can be reached
+                Some(v) if v == trimmed.serial => None,
+                // The saved state was before the trimmed command and can't be reached anymore
+                None => Some(UNREACHABLE_SERIAL),
+                v => v,
+            };
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self, tracks: &mut TrackDefApp, settings: &mut SettingApp, codes: &mut CheatCodeApp) {
+        if let Some(entry) = self.undo.pop() {
+            match &entry.command {
+                Command::Tracks { before, .. } => {
+                    before.apply(tracks);
+                    self.last_tracks = before.clone();
+                }
+                Command::Settings { before, .. } => {
+                    *settings = before.clone();
+                    self.last_settings = before.clone();
+                }
+                Command::Codes { before, .. } => {
+                    before.apply(codes);
+                    self.last_codes = before.clone();
+                }
+            }
+            self.redo.push(entry);
+        }
+    }
+
+    pub fn redo(&mut self, tracks: &mut TrackDefApp, settings: &mut SettingApp, codes: &mut CheatCodeApp) {
+        if let Some(entry) = self.redo.pop() {
+            match &entry.command {
+                Command::Tracks { after, .. } => {
+                    after.apply(tracks);
+                    self.last_tracks = after.clone();
+                }
+                Command::Settings { after, .. } => {
+                    *settings = after.clone();
+                    self.last_settings = after.clone();
+                }
+                Command::Codes { after, .. } => {
+                    after.apply(codes);
+                    self.last_codes = after.clone();
+                }
+            }
+            self.undo.push(entry);
+        }
+    }
+
+    /// Mark the current state as saved
+    pub fn mark_saved(&mut self) {
+        self.saved = self.undo.last().map(|v| v.serial);
+        self.modified = false;
+    }
+
+    /// Treat the current state as unsaved, even without any command
+    pub fn mark_modified(&mut self) {
+        self.modified = true;
+    }
+
+    /// Whether there are changes since the last save or open
+    pub fn is_dirty(&self) -> bool {
+        self.modified || self.pending || self.saved != self.undo.last().map(|v| v.serial)
+    }
+}
@@ -0,0 +1,195 @@
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use egui::Color32;
+use image::{imageops, Rgba, RgbaImage};
+
+use crate::apps::tracks::{Cup, ICON_SIZE};
+
+/// One of the fonts bundled with egui
+const FONT: &'static str = "Ubuntu-Light";
+const MAX_TEXT_SIZE: f32 = 96.0;
+const TEXT_MARGIN: f32 = 8.0;
+/// Offset of the shadow behind the text, to keep it readable on any background
+const SHADOW_OFFSET: i32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+pub enum Background {
+    Solid,
+    Gradient,
+}
+
+impl Background {
+    pub const VALUES: [Self; 2] = [Background::Solid, Background::Gradient];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Background::Solid => "Solid colour",
+            Background::Gradient => "Gradient",
+        }
+    }
+}
+
+/// What is drawn over the background
+#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+pub enum IconContent {
+    /// Position of the cup in the list
+    Number,
+    /// A short text
+    Label,
+    /// Thumbnails of the first four tracks in 2x2
+    Collage,
+}
+
+impl IconContent {
+    pub const VALUES: [Self; 3] = [IconContent::Number, IconContent::Label, IconContent::Collage];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IconContent::Number => "Cup number",
+            IconContent::Label => "Label",
+            IconContent::Collage => "Track thumbnails",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct IconTemplate {
+    pub background: Background,
+    /// Colour of a solid background, or the top of a gradient
+    pub top: Color32,
+    pub bottom: Color32,
+    pub content: IconContent,
+    /// Text of `IconContent::Label`. The cup name is used if it's empty.
+    pub label: String,
+    pub text_color: Color32,
+}
+
+impl Default for IconTemplate {
+    fn default() -> Self {
+        Self {
+            background: Background::Gradient,
+            top: Color32::from_rgb(0x30, 0x7A, 0xE0),
+            bottom: Color32::from_rgb(0x10, 0x20, 0x60),
+            content: IconContent::Number,
+            label: String::new(),
+            text_color: Color32::WHITE,
+        }
+    }
+}
+
+impl IconTemplate {
+    /// Compose the icon of `cup`, which is the `n`th cup counting from 1
+    pub fn render(&self, cup: &Cup, n: usize) -> RgbaImage {
+        let mut icon = RgbaImage::from_fn(ICON_SIZE, ICON_SIZE, |_, y| match self.background {
+            Background::Solid => to_rgba(self.top),
+            Background::Gradient => {
+                let t = y as f32 / (ICON_SIZE - 1) as f32;
+                let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+                Rgba([
+                    mix(self.top.r(), self.bottom.r()),
+                    mix(self.top.g(), self.bottom.g()),
+                    mix(self.top.b(), self.bottom.b()),
+                    0xFF,
+                ])
+            }
+        });
+
+        match self.content {
+            IconContent::Number => draw_text(&mut icon, &n.to_string(), self.text_color),
+            IconContent::Label => {
+                let label = if self.label.trim().is_empty() { &cup.name } else { &self.label };
+                draw_text(&mut icon, label.trim(), self.text_color);
+            }
+            IconContent::Collage => {
+                let cell = ICON_SIZE / 2;
+                for (i, track) in cup.trackset.iter().take(4).enumerate() {
+                    let thumbnail = match image::load_from_memory(&track.thumbnail) {
+                        Ok(v) => v.resize_exact(cell, cell, imageops::FilterType::Triangle).to_rgba8(),
+                        Err(_) => continue,
+                    };
+                    let (x, y) = ((i as u32 % 2) * cell, (i as u32 / 2) * cell);
+                    imageops::overlay(&mut icon, &thumbnail, x as i64, y as i64);
+                }
+            }
+        }
+
+        icon
+    }
+}
+
+fn to_rgba(c: Color32) -> Rgba<u8> {
+    Rgba([c.r(), c.g(), c.b(), c.a()])
+}
+
+/// Draw one line of text in the centre, as large as it fits
+fn draw_text(icon: &mut RgbaImage, text: &str, color: Color32) {
+    let fonts = egui::FontDefinitions::default();
+    let font = match fonts.font_data.get(FONT).and_then(|v| FontRef::try_from_slice(&v.font).ok()) {
+        Some(v) => v,
+        None => return,
+    };
+
+    let width = |size: f32| {
+        let scaled = font.as_scaled(PxScale::from(size));
+        let mut last = None;
+        let mut x = 0.0;
+        for c in text.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(last) = last {
+                x += scaled.kern(last, id);
+            }
+            x += scaled.h_advance(id);
+            last = Some(id);
+        }
+        x
+    };
+    let room = ICON_SIZE as f32 - TEXT_MARGIN * 2.0;
+    let measured = width(MAX_TEXT_SIZE);
+    let size = if measured > room {
+        MAX_TEXT_SIZE * room / measured
+    } else {
+        MAX_TEXT_SIZE
+    };
+
+    let scale = PxScale::from(size);
+    let scaled = font.as_scaled(scale);
+    let left = (ICON_SIZE as f32 - width(size)) / 2.0;
+    let baseline = (ICON_SIZE as f32 + scaled.ascent() + scaled.descent()) / 2.0;
+
+    let shadow = Color32::from_black_alpha(0xA0);
+    for (offset, color) in [(SHADOW_OFFSET, shadow), (0, color)] {
+        let mut last = None;
+        let mut x = left;
+        for c in text.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(last) = last {
+                x += scaled.kern(last, id);
+            }
+            let glyph = id.with_scale_and_position(scale, point(x, baseline));
+            x += scaled.h_advance(id);
+            last = Some(id);
+
+            let outlined = match font.outline_glyph(glyph) {
+                Some(v) => v,
+                None => continue,
+            };
+            let bounds = outlined.px_bounds();
+            outlined.draw(|gx, gy, coverage| {
+                let px = bounds.min.x as i32 + gx as i32 + offset;
+                let py = bounds.min.y as i32 + gy as i32 + offset;
+                if px < 0 || py < 0 || px >= ICON_SIZE as i32 || py >= ICON_SIZE as i32 {
+                    return;
+                }
+                blend(icon.get_pixel_mut(px as u32, py as u32), color, coverage);
+            });
+        }
+    }
+}
+
+fn blend(p: &mut Rgba<u8>, color: Color32, coverage: f32) {
+    let alpha = coverage.clamp(0.0, 1.0) * color.a() as f32 / 255.0;
+    let src = [color.r(), color.g(), color.b()];
+    for i in 0..3 {
+        p.0[i] = (p.0[i] as f32 * (1.0 - alpha) + src[i] as f32 * alpha).round() as u8;
+    }
+    p.0[3] = (p.0[3] as f32 + (255.0 - p.0[3] as f32) * alpha).round() as u8;
+}
@@ -0,0 +1,655 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::{
+    apps::{
+        source::COURSE_DIR,
+        tracks::{Cup, CupList, GroupFlag, Id, Track, TrackDefinition},
+        SettingApp,
+    },
+    Distro,
+};
+
+use super::{
+    archive::{read_szs, U8Node},
+    build::FIRST_CUSTOM_SLOT,
+    lecode::{detect_profile, parse_bmg, LeBinary, MID_CUP_NAME, MID_TRACK_NAME},
+    profile::profile,
+};
+
+/// Result of an import, with everything that could not be taken over as it is
+pub struct ImportReport {
+    pub tracks: usize,
+    pub cups: usize,
+    pub problems: Vec<String>,
+}
+
+impl ImportReport {
+    fn new() -> Self {
+        Self {
+            tracks: 0,
+            cups: 0,
+            problems: vec![],
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let mut s = format!("{} tracks in {} cups are imported.", self.tracks, self.cups);
+        if !self.problems.is_empty() {
+            s.push_str(&format!("\n{} items need attention:", self.problems.len()));
+            for v in &self.problems {
+                s.push_str(&format!("\n{}", v));
+            }
+        }
+        s
+    }
+}
+
+/// Split a file name like `Name (Author) [v1.2]` into name, author and version.
+/// Author and version are optional, but brackets must be balanced and the name not empty.
+pub fn parse_track_name(stem: &str) -> Option<(String, String, String)> {
+    let mut rest = stem.trim();
+
+    let mut version = String::new();
+    if rest.ends_with(']') {
+        let open = rest.rfind('[')?;
+        version = rest[open + 1..rest.len() - 1].trim().to_string();
+        rest = rest[..open].trim_end();
+    }
+
+    let mut author = String::new();
+    if rest.ends_with(')') {
+        let open = rest.rfind('(')?;
+        author = rest[open + 1..rest.len() - 1].trim().to_string();
+        rest = rest[..open].trim_end();
+    }
+
+    if rest.is_empty() || rest.contains(|c| matches!(c, '(' | ')' | '[' | ']')) {
+        return None;
+    }
+    Some((rest.to_string(), author, version))
+}
+
+impl TrackDefinition {
+    /// Add every SZS file in `dir` as a track, filling new cups of 4 tracks in file name order
+    pub fn import_track_folder(&mut self, dir: &Path) -> Result<ImportReport, String> {
+        let mut files: Vec<_> = fs::read_dir(dir)
+            .map_err(|e| format!("Failed to read the folder: {}", e))?
+            .filter_map(|v| v.ok())
+            .map(|v| v.path())
+            .filter(|v| v.is_file() && v.extension().map_or(false, |e| e.eq_ignore_ascii_case("szs")))
+            .collect();
+        if files.is_empty() {
+            return Err("The selected folder has no SZS files.".to_string());
+        }
+        files.sort_by_key(|v| v.to_string_lossy().to_lowercase());
+
+        // A new project starts with one empty cup, which is replaced
+        if self.cups.len() == 1 && self.cups[0] == Cup::default(0) {
+            self.cups.clear();
+        }
+
+        let mut report = ImportReport::new();
+        for chunk in files.chunks(4) {
+            let mut cup = Cup::default(self.cups.len());
+            for (track, path) in cup.trackset.iter_mut().zip(chunk) {
+                let stem = path.file_stem().unwrap().to_string_lossy().to_string();
+                match parse_track_name(&stem) {
+                    Some((name, author, version)) => {
+                        track.name = name;
+                        track.author = author;
+                        track.version = version;
+                    }
+                    None => {
+                        report
+                            .problems
+                            .push(format!("{}: not in the \"Name (Author) [vX]\" format", stem));
+                        track.name = stem;
+                    }
+                }
+                track.filename = format!("{}", path.to_string_lossy());
+                report.tracks += 1;
+            }
+            self.cups.push(cup);
+            report.cups += 1;
+        }
+        self.renumber();
+        Ok(report)
+    }
+}
+
+/// Flags of track lines in LE-DEF
+pub const FLAG_NEW: u32 = 0x01;
+pub const FLAG_HEADER: u32 = 0x02;
+pub const FLAG_CHILD: u32 = 0x04;
+
+/// Parse CT-DEF or LE-DEF text into cups.
+/// Track files are looked up in `dir`, the folder of the definition file.
+pub fn parse_track_definition(text: &str, dir: &Path) -> (TrackDefinition, ImportReport) {
+    let mut report = ImportReport::new();
+    let mut def = TrackDefinition {
+        cups: vec![],
+        ..Default::default()
+    };
+    // Explicit cup names wait here until the cup gets its first track
+    let mut cup_name: Option<String> = None;
+    let mut slot = 4;
+    let mut section = String::from("RACING-TRACK-LIST");
+    // `%LE-FLAGS = 1` puts the flags after the slots, which CT-DEF doesn't have
+    let mut le_flags: Option<bool> = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let at = |msg: &str| format!("Line {}: {}", i + 1, msg);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line[1..line.len() - 1].trim().to_uppercase();
+            if section != "RACING-TRACK-LIST" {
+                report.problems.push(at(&format!("section [{}] is not supported and skipped", section)));
+            }
+            continue;
+        }
+        if section != "RACING-TRACK-LIST" {
+            continue;
+        }
+
+        let (kind, args) = line.split_at(line.find(|c: char| c.is_whitespace() || c == '=').unwrap_or(line.len()));
+        let args = args.trim();
+        match kind.to_uppercase().as_str() {
+            "N" => {
+                for token in args.split(|c: char| c == '|' || c.is_whitespace()).filter(|v| !v.is_empty()) {
+                    match token.to_uppercase().as_str() {
+                        "N$NONE" => def.mode.nintendo = false,
+                        "N$SHOW" => {
+                            def.mode.nintendo = true;
+                            def.mode.nin_swap = false;
+                        }
+                        "N$SWAP" => {
+                            def.mode.nintendo = true;
+                            def.mode.nin_swap = true;
+                        }
+                        _ => report.problems.push(at(&format!("{} is not supported", token))),
+                    }
+                }
+            }
+            "%WIIMM-CUP" => match parse_parameter(args) {
+                Some(v) => def.mode.wiimm_cup = v != 0,
+                None => report.problems.push(at("the value is not a number")),
+            },
+            "%LE-FLAGS" => match parse_parameter(args) {
+                Some(v) => le_flags = Some(v != 0),
+                None => report.problems.push(at("the value is not a number")),
+            },
+            "C" => {
+                let name = split_fields(args).into_iter().next().unwrap_or_default();
+                cup_name = Some(if name.is_empty() { format!("Cup {}", def.cups.len()) } else { name });
+                slot = 4;
+            }
+            "T" => {
+                let mut fields = split_fields(args);
+                // Without `%LE-FLAGS`, only LE-DEF lines have the 6th field
+                let has_flags = le_flags.unwrap_or(fields.len() >= 6);
+                if fields.len() < if has_flags { 5 } else { 4 } {
+                    report.problems.push(at("the track line has too few fields"));
+                    continue;
+                }
+                if !has_flags {
+                    fields.insert(2, "-".to_string());
+                }
+
+                let flags = parse_flags(&fields[2]);
+                let child = flags.map_or(false, |v| v & FLAG_CHILD != 0);
+                // Group members share the slot of their header
+                if (slot == 4 && !child) || cup_name.is_some() || def.cups.is_empty() {
+                    let mut cup = Cup::default(def.cups.len());
+                    cup.trackset.clear();
+                    if let Some(name) = cup_name.take() {
+                        cup.name = name;
+                    }
+                    def.cups.push(cup);
+                    slot = 0;
+                }
+                let trackset = &mut def.cups.last_mut().unwrap().trackset;
+                trackset.push(Track::default());
+                let track = trackset.last_mut().unwrap();
+                if !child {
+                    slot += 1;
+                }
+                report.tracks += 1;
+
+                match parse_slot(&fields[0], Id::from_music_slot) {
+                    Some(v) => track.music = v,
+                    None => report.problems.push(at(&format!("unknown music slot {}", fields[0]))),
+                }
+                match parse_slot(&fields[1], Id::from_property_slot) {
+                    Some(v) if v != Id::GalaxyArena => track.property = v,
+                    _ => report.problems.push(at(&format!("unknown property slot {}", fields[1]))),
+                }
+                match flags {
+                    Some(flags) => {
+                        track.new = flags & FLAG_NEW != 0;
+                        track.flag = if flags & FLAG_HEADER != 0 {
+                            GroupFlag::Header
+                        } else if flags & FLAG_CHILD != 0 {
+                            GroupFlag::Child
+                        } else {
+                            GroupFlag::None
+                        };
+                        if flags & !(FLAG_NEW | FLAG_HEADER | FLAG_CHILD) != 0 {
+                            report.problems.push(at(&format!("flags {} are partly not supported", fields[2])));
+                        }
+                    }
+                    None => report.problems.push(at(&format!("unknown flags {}", fields[2]))),
+                }
+
+                let file = dir.join(format!("{}.szs", fields[3]));
+                if !file.is_file() {
+                    report.problems.push(at(&format!("{}.szs is not found next to the definition", fields[3])));
+                }
+                track.filename = format!("{}", file.to_string_lossy());
+                track.name = fields[4].clone();
+            }
+            _ => report.problems.push(at(&format!("\"{}\" is not supported and skipped", line))),
+        }
+    }
+
+    if def.cups.is_empty() {
+        def.cups.push(Cup::default(0));
+    }
+    for (n, cup) in def.cups.iter_mut().enumerate() {
+        let empty = 4usize.saturating_sub(cup.groups().len());
+        if empty > 0 && report.tracks > 0 {
+            report.problems.push(format!("{}: {} slots are left empty", cup.name, empty));
+        }
+        cup.trackset.resize(cup.trackset.len() + empty, Track::default());
+        report.cups = n + 1;
+    }
+    def.renumber();
+    (def, report)
+}
+
+impl TrackDefinition {
+    /// Replace the cups with the ones in a CT-DEF or LE-DEF file
+    pub fn import_definition(&mut self, path: &Path) -> Result<ImportReport, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read the definition: {}", e))?;
+        if !text.lines().any(|v| v.trim_start().to_uppercase().starts_with("T ")) {
+            return Err("The file has no track lines. Is it a CT-DEF or LE-DEF file?".to_string());
+        }
+        let (def, report) = parse_track_definition(&text, path.parent().unwrap_or(Path::new(".")));
+        self.mode = def.mode;
+        self.cups = def.cups;
+        self.renumber();
+        self.list = CupList::Racing;
+        self.selected = 0;
+        Ok(report)
+    }
+}
+
+/// Split by `;` outside of quotes, and remove the quotes
+fn split_fields(s: &str) -> Vec<String> {
+    let mut fields: Vec<String> = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => {
+                if let Some(v) = chars.next() {
+                    field.push(v);
+                }
+            }
+            ';' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+fn parse_number(s: &str) -> Option<u32> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Value of `%KEY = value`
+fn parse_parameter(args: &str) -> Option<u32> {
+    parse_number(args.trim_start_matches('=').trim())
+}
+
+/// A slot is a number, or a name like `T11`
+fn parse_slot(s: &str, from_number: fn(u8) -> Option<Id>) -> Option<Id> {
+    match parse_number(s) {
+        Some(n) => u8::try_from(n).ok().and_then(from_number),
+        None => Id::from_slot_name(s.trim()),
+    }
+}
+
+/// Flags are a number, or letters like `NH`
+fn parse_flags(s: &str) -> Option<u32> {
+    let s = s.trim();
+    if s.is_empty() || s == "-" {
+        return Some(0);
+    }
+    if let Some(v) = parse_number(s) {
+        return Some(v);
+    }
+    s.chars().try_fold(0, |flags, c| match c.to_ascii_uppercase() {
+        'N' => Some(flags | FLAG_NEW),
+        'H' => Some(flags | FLAG_HEADER),
+        'G' => Some(flags | FLAG_CHILD),
+        '-' => Some(flags),
+        _ => None,
+    })
+}
+
+/// Where track names are looked up, relative to the pack folder
+const NAME_FILES: [&'static str; 3] = ["Common.szs", "Common.bmg", "Scene/UI/Common.szs"];
+/// Original tracks use the slots below this, and original arenas the next ten
+const FIRST_ARENA_SLOT: u32 = 0x20;
+const END_ARENA_SLOT: u32 = 0x2A;
+
+impl Distro {
+    /// Rebuild cups, track names and LPAR settings from a built pack.
+    /// `bin` is its lecode-*.bin, in the pack folder or in its `rel` folder.
+    pub fn import_lecode_pack(&mut self, bin: &Path) -> Result<ImportReport, String> {
+        let data = fs::read(bin).map_err(|e| format!("Failed to read the LE-CODE binary: {}", e))?;
+        let target = detect_profile(&data, self.settings.lecode_build)?;
+        let le = LeBinary::parse(&data, &target.layout)?;
+        let mut report = ImportReport::new();
+
+        let mut root = bin.parent().unwrap_or(Path::new("."));
+        if root.file_name().map_or(false, |v| v.eq_ignore_ascii_case("rel")) {
+            root = root.parent().unwrap_or(root);
+        }
+        let names = match NAME_FILES.iter().map(|v| root.join(v)).find(|v| v.is_file()) {
+            Some(path) => read_names(&path).unwrap_or_else(|err| {
+                report.problems.push(format!("{}: {}", path.to_string_lossy(), err));
+                BTreeMap::new()
+            }),
+            None => {
+                report.problems.push("No Common.szs or Common.bmg is found, names are left empty".to_string());
+                BTreeMap::new()
+            }
+        };
+
+        let mut def = TrackDefinition {
+            cups: vec![],
+            ..Default::default()
+        };
+        // Packs showing the Nintendo cups have them as the first 8 cups
+        let nintendo = le
+            .racing_cups
+            .iter()
+            .take(8)
+            .take_while(|cup| cup.iter().all(|v| *v < FIRST_ARENA_SLOT))
+            .count();
+        def.mode.nintendo = nintendo == 8;
+        let skip = if def.mode.nintendo { 8 } else { 0 };
+
+        for (n, slots) in le.racing_cups.iter().enumerate().skip(skip) {
+            let mut cup = Cup::default(def.cups.len());
+            if let Some(name) = names.get(&(MID_CUP_NAME + n as u32)) {
+                cup.name = name.clone();
+            }
+            cup.trackset = import_cup(slots, &le, &names, root, &mut report);
+            def.cups.push(cup);
+            report.cups += 1;
+        }
+        // The original battle cups are used when the list is left empty
+        let original = le
+            .battle_cups
+            .iter()
+            .all(|cup| cup.iter().all(|v| (FIRST_ARENA_SLOT..END_ARENA_SLOT).contains(v)));
+        if !original {
+            for (n, slots) in le.battle_cups.iter().enumerate() {
+                let mut cup = Cup::battle(n);
+                cup.trackset = import_cup(slots, &le, &names, root, &mut report);
+                def.battle_cups.push(cup);
+                report.cups += 1;
+            }
+        }
+        if def.cups.is_empty() {
+            def.cups.push(Cup::default(0));
+        }
+        def.renumber();
+
+        self.tracks.editor.mode.nintendo = def.mode.nintendo;
+        self.tracks.editor.cups = def.cups;
+        self.tracks.editor.battle_cups = def.battle_cups;
+        self.tracks.editor.list = CupList::Racing;
+        self.tracks.editor.selected = 0;
+        if target.build != profile(self.settings.lecode_build).build {
+            report
+                .problems
+                .push(format!("The binary is of {}, which is selected as the target", target.name));
+        }
+        self.settings = SettingApp {
+            lecode_build: target.build,
+            regions: self.settings.regions.clone(),
+            ..le.settings
+        };
+        Ok(report)
+    }
+}
+
+/// Tracks of a cup with `slots`, with the members of each group which follow the slot of its header
+fn import_cup(
+    slots: &[u32],
+    le: &LeBinary,
+    names: &BTreeMap<u32, String>,
+    root: &Path,
+    report: &mut ImportReport,
+) -> Vec<Track> {
+    let flags = |slot: u32| le.flags.get(slot as usize).copied().unwrap_or(0) as u32;
+    let mut tracks = vec![];
+    for &slot in slots {
+        let mut members = vec![slot];
+        if flags(slot) & FLAG_HEADER != 0 {
+            members.extend((slot + 1..).take_while(|v| flags(*v) & FLAG_CHILD != 0));
+        }
+        for slot in members {
+            let mut track = Track::default();
+            import_slot(&mut track, slot, le, names, root, report);
+            tracks.push(track);
+        }
+    }
+    tracks
+}
+
+/// Fill `track` from the tables of `slot` and find its file in Race/Course
+fn import_slot(
+    track: &mut Track,
+    slot: u32,
+    le: &LeBinary,
+    names: &BTreeMap<u32, String>,
+    root: &Path,
+    report: &mut ImportReport,
+) {
+    let at = slot as usize;
+    // Original tracks keep their Nintendo file names
+    let original = if slot < FIRST_CUSTOM_SLOT as u32 {
+        Id::from_property_slot(slot as u8)
+    } else {
+        None
+    };
+    if slot < FIRST_CUSTOM_SLOT as u32 {
+        report.problems.push(match original {
+            Some(v) => format!("Slot {:#x} is {}, which is imported as a custom track", slot, v.as_str()),
+            None => format!("Slot {:#x} is a special slot, which is imported as a custom track", slot),
+        });
+    }
+    match le.property.get(at).and_then(|v| Id::from_property_slot(*v)) {
+        Some(v) => track.property = v,
+        _ => report.problems.push(format!("Slot {:#x} has an unknown property", slot)),
+    }
+    match le.music.get(at).and_then(|v| Id::from_music_slot(*v)) {
+        Some(v) => track.music = v,
+        None => report.problems.push(format!("Slot {:#x} has unknown music", slot)),
+    }
+    let flags = le.flags.get(at).copied().unwrap_or(0) as u32;
+    track.new = flags & FLAG_NEW != 0;
+    track.flag = if flags & FLAG_HEADER != 0 {
+        GroupFlag::Header
+    } else if flags & FLAG_CHILD != 0 {
+        GroupFlag::Child
+    } else {
+        GroupFlag::None
+    };
+
+    track.name = match names.get(&(MID_TRACK_NAME + slot)) {
+        Some(v) => v.clone(),
+        None => format!("Slot {:#x}", slot),
+    };
+    let file_name = match original {
+        Some(v) => format!("{}.szs", v.course_file()),
+        None => format!("{:03x}.szs", slot),
+    };
+    let file = root.join(COURSE_DIR).join(&file_name);
+    if file.is_file() {
+        track.filename = format!("{}", file.to_string_lossy());
+    } else {
+        report.problems.push(format!("{}: {} is not found", track.name, file_name));
+    }
+    report.tracks += 1;
+}
+
+/// Messages of a BMG file, or of message/Common.bmg inside an SZS file
+fn read_names(path: &Path) -> Result<BTreeMap<u32, String>, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read: {}", e))?;
+    if path.extension().map_or(false, |v| v.eq_ignore_ascii_case("bmg")) {
+        return parse_bmg(&data);
+    }
+    let archive = read_szs(&data)?;
+    let mut nodes = &archive.nodes;
+    // Nintendo archives put everything under a folder named "."
+    if let [U8Node::Dir { name, children }] = &nodes[..] {
+        if name == "." {
+            nodes = children;
+        }
+    }
+    let message = nodes.iter().find_map(|v| match v {
+        U8Node::Dir { name, children } if name == "message" => Some(children),
+        _ => None,
+    });
+    let bmg = message.and_then(|v| {
+        v.iter().find_map(|v| match v {
+            U8Node::File { name, data } if name == "Common.bmg" => Some(data),
+            _ => None,
+        })
+    });
+    match bmg {
+        Some(v) => parse_bmg(v),
+        None => Err("message/Common.bmg is not found in the archive".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_groups() {
+        let slots = 0x50;
+        let mut flags = vec![0u8; slots];
+        flags[0x45] = FLAG_HEADER as u8;
+        flags[0x46] = FLAG_CHILD as u8;
+        flags[0x47] = (FLAG_CHILD | FLAG_NEW) as u8;
+        let le = LeBinary {
+            settings: Default::default(),
+            racing_cups: vec![[0x44, 0x45, 0x48, 0x49]],
+            battle_cups: vec![],
+            property: vec![0; slots],
+            music: vec![0; slots],
+            flags,
+        };
+        let mut report = ImportReport::new();
+        let tracks = import_cup(&le.racing_cups[0], &le, &BTreeMap::new(), Path::new("."), &mut report);
+        let names: Vec<&str> = tracks.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["Slot 0x44", "Slot 0x45", "Slot 0x46", "Slot 0x47", "Slot 0x48", "Slot 0x49"]);
+        assert!(tracks[1].flag == GroupFlag::Header && tracks[3].flag == GroupFlag::Child && tracks[3].new);
+        assert_eq!(report.tracks, 6);
+    }
+
+    #[test]
+    fn classic_ct_def() {
+        let text = r#"#CT-DEF
+[RACING-TRACK-LIST]
+N N$SHOW
+
+C "Retro Cup"
+T T11; T11; "luigi"; "Luigi Circuit"; ""
+T 0x77; 0x01; "moo"; "Moo Moo; Meadow"; "Moo Moo Meadow"
+T T13; T13; "gorge"; "Mushroom Gorge"
+T T14; T14; "factory"; "Toad's Factory"; ""
+"#;
+        let (def, report) = parse_track_definition(text, Path::new("."));
+        assert_eq!((report.cups, report.tracks), (1, 4));
+        assert!(def.mode.nintendo && !def.mode.nin_swap);
+        let cup = &def.cups[0];
+        assert_eq!(cup.name, "Retro Cup");
+        let names: Vec<&str> = cup.trackset.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["Luigi Circuit", "Moo Moo; Meadow", "Mushroom Gorge", "Toad's Factory"]);
+        assert!(cup.trackset[1].filename.ends_with("moo.szs"));
+        assert_eq!((cup.trackset[1].property, cup.trackset[1].music), (Id::MooMooMeadow, Id::MooMooMeadow));
+        assert!(cup.trackset.iter().all(|v| !v.new && v.flag == GroupFlag::None));
+        // Only the missing track files are reported
+        assert_eq!(report.problems.len(), 4, "{:?}", report.problems);
+        assert!(report.problems.iter().all(|v| v.contains("is not found")));
+    }
+
+    #[test]
+    fn le_flags() {
+        let text = "%LE-FLAGS = 1\nT T11; T11; H; \"a\"; \"A\"\nT T11; T11; G; \"b\"; \"B\"\n%LE-FLAGS = 0\nT T12; T12; \"c\"; \"C\"\n";
+        let (def, report) = parse_track_definition(text, Path::new("."));
+        let flags: Vec<GroupFlag> = def.cups[0].trackset.iter().map(|v| v.flag).collect();
+        assert_eq!(flags[..3], [GroupFlag::Header, GroupFlag::Child, GroupFlag::None]);
+        assert_eq!(def.cups[0].trackset[2].name, "C");
+        assert_eq!(report.tracks, 3);
+    }
+
+    #[test]
+    fn definition_round_trip() {
+        let mut def = TrackDefinition::default();
+        def.mode.nin_swap = true;
+        def.mode.wiimm_cup = true;
+        let cup = &mut def.cups[0];
+        cup.name = "First; Cup".to_string();
+        cup.trackset[0].name = "Track \"A\"".to_string();
+        cup.trackset[0].new = true;
+        cup.trackset[0].property = Id::MooMooMeadow;
+        cup.trackset[0].music = Id::LuigiCircuit;
+        // A group of 3 tracks takes the place of one
+        cup.trackset[1].flag = GroupFlag::Header;
+        cup.trackset[2].flag = GroupFlag::Child;
+        cup.trackset[3].flag = GroupFlag::Child;
+        cup.trackset[3].new = true;
+        cup.trackset.push(cup.trackset[0].clone());
+        cup.trackset.push(cup.trackset[0].clone());
+        for (i, track) in cup.trackset.iter_mut().enumerate().skip(1) {
+            track.name = format!("Track {}", i);
+        }
+        def.cups.push(Cup::default(1));
+        def.cups[1].name = "Second".to_string();
+        assert!(def.check_groups().is_empty());
+
+        let (read, report) = parse_track_definition(&def.to_le_def(), Path::new("."));
+        assert_eq!(report.cups, 2);
+        assert!(read.mode.nin_swap && read.mode.wiimm_cup);
+        assert_eq!(read.cups.len(), 2);
+        for (a, b) in def.cups.iter().zip(&read.cups) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.trackset.len(), b.trackset.len());
+            for (x, y) in a.trackset.iter().zip(&b.trackset) {
+                assert_eq!((&x.name, x.new, x.flag), (&y.name, y.new, y.flag));
+                assert_eq!((x.property, x.music), (y.property, y.music));
+            }
+        }
+        assert_eq!(read.cups[0].groups().len(), 4);
+    }
+}
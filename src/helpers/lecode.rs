@@ -0,0 +1,629 @@
+use std::collections::BTreeMap;
+
+use crate::apps::{EngineProbSet, SettingApp, SpeedometerMode};
+
+use super::profile::{profile, profiles, LeBinaryLayout, LeCodeProfile};
+
+const LE_BINARY_MAGIC: &'static [u8; 4] = b"LECT";
+const LE_PARAM_MAGIC: &'static [u8; 4] = b"LPAR";
+/// Size of the first LPAR version, which has no blue shell and cloud settings
+const LPAR_SIZE_V1: usize = 0x3C;
+/// Offset of the version in the header of lecode-*.bin
+const HEADER_VERSION: usize = 0x04;
+
+// Offsets in the LPAR block, after `le_lpar_t` in lib-lecode.h of Wiimm's SZS Tools.
+// The header only points to the block, at `LeBinaryLayout::param_offset`.
+// Not yet checked against a released lecode-*.bin.
+const LPAR_SIZE: usize = 0x08;
+/// Probabilities of 100cc, 150cc and mirror
+const LPAR_ENGINE: usize = 0x10;
+/// Probabilities of 150cc, 200cc and mirror, used with 200cc
+const LPAR_ENGINE_200CC: usize = 0x13;
+const LPAR_ENABLE_200CC: usize = 0x16;
+const LPAR_CUSTOM_TT: usize = 0x18;
+const LPAR_XPFLAGS: usize = 0x19;
+const LPAR_BLOCK_TRACK: usize = 0x1A;
+const LPAR_SPEEDOMETER: usize = 0x1B;
+/// Offsets of the tables from the start of LPAR
+const LPAR_CUP_PAR: usize = 0x20;
+const LPAR_CUP_TRACK: usize = 0x24;
+const LPAR_CUP_ARENA: usize = 0x28;
+const LPAR_PROPERTY: usize = 0x30;
+const LPAR_MUSIC: usize = 0x34;
+const LPAR_FLAGS: usize = 0x38;
+/// Only in LPAR blocks larger than `LPAR_SIZE_V1`
+const LPAR_DRAG_BLUE_SHELL: usize = 0x3C;
+const LPAR_THCLOUD_TIME: usize = 0x3E;
+const BMG_MAGIC: &'static [u8; 8] = b"MESGbmg1";
+const BMG_HEADER_SIZE: usize = 0x20;
+/// Text of BMG messages starts an escape sequence with this character
+const BMG_ESCAPE: u16 = 0x1A;
+
+/// Message IDs of track names, added to the slot
+pub const MID_TRACK_NAME: u32 = 0x7000;
+/// Message IDs of cup names, added to the cup index
+pub const MID_CUP_NAME: u32 = 0x9000;
+
+/// Tables of lecode-*.bin, as LE-CODE reads them from the LPAR block
+pub struct LeBinary {
+    pub settings: SettingApp,
+    /// Slots of each racing cup
+    pub racing_cups: Vec<[u32; 4]>,
+    /// Slots of each battle cup
+    pub battle_cups: Vec<[u32; 5]>,
+    /// Property, music and flags of each slot
+    pub property: Vec<u8>,
+    pub music: Vec<u8>,
+    pub flags: Vec<u8>,
+}
+
+impl LeBinary {
+    /// Read with the layout of the target LE-CODE build.
+    /// Parameters which the layout doesn't have keep their defaults.
+    pub fn parse(data: &[u8], layout: &LeBinaryLayout) -> Result<Self, String> {
+        const ERROR: &'static str = "The LPAR block of the LE-CODE binary is broken.";
+        let lpar = &data[find_lpar(data, layout)?..];
+
+        let toggle_200cc = lpar[LPAR_ENABLE_200CC] != 0;
+        let engine = if toggle_200cc { LPAR_ENGINE_200CC } else { LPAR_ENGINE };
+        let engine = &lpar[engine..engine + 3];
+        let mut settings = SettingApp {
+            toggle_200cc,
+            engine_probs: EngineProbSet {
+                low: engine[0] as i32,
+                mid: engine[1] as i32,
+                high: engine[2] as i32,
+            },
+            toggle_ct_tt: lpar[LPAR_CUSTOM_TT] != 0,
+            toggle_custom_presence: lpar[LPAR_XPFLAGS] != 0,
+            prevent_selection_online: lpar[LPAR_BLOCK_TRACK] as i8,
+            toggle_som: SpeedometerMode::from_usize(lpar[LPAR_SPEEDOMETER].into()).unwrap_or(SpeedometerMode::Two),
+            ..Default::default()
+        };
+        if layout.lpar_size > LPAR_SIZE_V1 {
+            settings.toggle_drag_blue = lpar[LPAR_DRAG_BLUE_SHELL] != 0;
+            settings.time_cloud = as_u16_be(&lpar[LPAR_THCLOUD_TIME..]) as i16;
+        }
+
+        // Tables are given by offsets from the start of LPAR
+        let table = |at: usize, len: usize| -> Result<&[u8], String> {
+            let offset = as_u32_be(&lpar[at..at + 4]) as usize;
+            lpar.get(offset..offset + len).ok_or(ERROR.to_string())
+        };
+        let cup_par = table(LPAR_CUP_PAR, 12)?;
+        let racing = as_u32_be(&cup_par[0..4]) as usize;
+        let battle = as_u32_be(&cup_par[4..8]) as usize;
+        let slots = as_u32_be(&cup_par[8..12]) as usize;
+
+        let racing_cups = table(LPAR_CUP_TRACK, racing * 16)?
+            .chunks_exact(16)
+            .map(|v| [0, 4, 8, 12].map(|i| as_u32_be(&v[i..])))
+            .collect();
+        let battle_cups = table(LPAR_CUP_ARENA, battle * 20)?
+            .chunks_exact(20)
+            .map(|v| [0, 4, 8, 12, 16].map(|i| as_u32_be(&v[i..])))
+            .collect();
+
+        Ok(Self {
+            settings,
+            racing_cups,
+            battle_cups,
+            property: table(LPAR_PROPERTY, slots)?.to_vec(),
+            music: table(LPAR_MUSIC, slots)?.to_vec(),
+            flags: table(LPAR_FLAGS, slots)?.to_vec(),
+        })
+    }
+}
+
+/// Write the parameters of `s` into the LPAR block of lecode-*.bin.
+/// Parameters outside the layout are left to the LPAR text.
+pub fn write_settings(data: &mut [u8], s: &SettingApp, layout: &LeBinaryLayout) -> Result<(), String> {
+    let start = find_lpar(data, layout)?;
+    let lpar = &mut data[start..];
+
+    let p = &s.engine_probs;
+    let engine = if s.toggle_200cc { LPAR_ENGINE_200CC } else { LPAR_ENGINE };
+    for (i, v) in [p.low, p.mid, p.high].into_iter().enumerate() {
+        lpar[engine + i] = v.clamp(0, 100) as u8;
+    }
+    lpar[LPAR_ENABLE_200CC] = s.toggle_200cc as u8;
+    lpar[LPAR_CUSTOM_TT] = s.toggle_ct_tt as u8;
+    lpar[LPAR_XPFLAGS] = s.toggle_custom_presence as u8;
+    lpar[LPAR_BLOCK_TRACK] = s.prevent_selection_online as u8;
+    lpar[LPAR_SPEEDOMETER] = s.toggle_som as u8;
+    if layout.lpar_size > LPAR_SIZE_V1 {
+        lpar[LPAR_DRAG_BLUE_SHELL] = s.toggle_drag_blue as u8;
+        lpar[LPAR_THCLOUD_TIME..LPAR_THCLOUD_TIME + 2].copy_from_slice(&s.time_cloud.to_be_bytes());
+    }
+    Ok(())
+}
+
+/// Write the cup and slot tables of `le` into lecode-*.bin.
+/// They are appended to the binary and LPAR is pointed to them, as they can be larger than the ones it had.
+/// LE-CODE picks the tracks of "Random: New Tracks" by the slot flags.
+pub fn write_tables(data: &mut Vec<u8>, le: &LeBinary, layout: &LeBinaryLayout) -> Result<(), String> {
+    let start = find_lpar(data, layout)?;
+    let slots = le.property.len();
+    if le.music.len() != slots || le.flags.len() != slots {
+        return Err("The slot tables differ in length.".to_string());
+    }
+    let words = |v: Vec<u32>| -> Vec<u8> { v.into_iter().flat_map(|v| v.to_be_bytes()).collect() };
+    let tables = [
+        (LPAR_CUP_PAR, words(vec![le.racing_cups.len() as u32, le.battle_cups.len() as u32, slots as u32])),
+        (LPAR_CUP_TRACK, words(le.racing_cups.concat())),
+        (LPAR_CUP_ARENA, words(le.battle_cups.concat())),
+        (LPAR_PROPERTY, le.property.clone()),
+        (LPAR_MUSIC, le.music.clone()),
+        (LPAR_FLAGS, le.flags.clone()),
+    ];
+    for (at, mut table) in tables {
+        data.resize((data.len() + 3) / 4 * 4, 0);
+        let offset = (data.len() - start) as u32;
+        data[start + at..start + at + 4].copy_from_slice(&offset.to_be_bytes());
+        data.append(&mut table);
+    }
+    Ok(())
+}
+
+/// Profile whose layout reads `data`: the one of `preferred` if it does, or else the newest one which does
+pub fn detect_profile(data: &[u8], preferred: u16) -> Result<&'static LeCodeProfile, String> {
+    let preferred = profile(preferred);
+    let error = match find_lpar(data, &preferred.layout) {
+        Ok(_) => return Ok(preferred),
+        Err(e) => e,
+    };
+    profiles()
+        .iter()
+        .rev()
+        .find(|v| find_lpar(data, &v.layout).is_ok())
+        .ok_or(error)
+}
+
+/// Offset of the LPAR block the header points to.
+/// Binaries whose header version or LPAR size differ from `layout` are refused, as they'd be read wrong.
+fn find_lpar(data: &[u8], layout: &LeBinaryLayout) -> Result<usize, String> {
+    const ERROR: &'static str = "The LPAR block of the LE-CODE binary is broken.";
+    if !data.starts_with(LE_BINARY_MAGIC) {
+        return Err("Not an LE-CODE binary.".to_string());
+    }
+    let version = data.get(HEADER_VERSION..HEADER_VERSION + 4).map(as_u32_be).ok_or(ERROR)?;
+    if version != layout.header_version {
+        return Err(format!(
+            "The LE-CODE binary has header version {}, but the selected LE-CODE build has version {}.",
+            version, layout.header_version
+        ));
+    }
+    let at = layout.param_offset;
+    let offset = data.get(at..at + 4).map(as_u32_be).ok_or(ERROR)? as usize;
+    if data.get(offset..offset + 4) != Some(LE_PARAM_MAGIC) {
+        return Err("The header of the LE-CODE binary doesn't point to an LPAR block.".to_string());
+    }
+    let size = as_u32_be(data.get(offset + LPAR_SIZE..offset + LPAR_SIZE + 4).ok_or(ERROR)?) as usize;
+    if size < LPAR_SIZE_V1 || offset + size > data.len() {
+        return Err(ERROR.to_string());
+    }
+    if size != layout.lpar_size {
+        return Err(format!(
+            "The LPAR block of the LE-CODE binary has {} bytes, but the one of the selected LE-CODE build has {}.",
+            size, layout.lpar_size
+        ));
+    }
+    Ok(offset)
+}
+
+/// Message of a BMG file as it's stored, to write back the messages which aren't changed
+pub struct BmgMessage {
+    /// Attributes after the text offset in INF1
+    pub attributes: Vec<u8>,
+    /// Text without the terminating zero, escape sequences included
+    pub text: Vec<u8>,
+}
+
+/// Messages of a BMG file by message ID.
+/// Escape sequences such as colours are removed from the text.
+pub fn parse_bmg(data: &[u8]) -> Result<BTreeMap<u32, String>, String> {
+    let (encoding, messages) = read_bmg(data)?;
+    Ok(messages
+        .into_iter()
+        .map(|(id, v)| (id, decode_bmg_text(&v.text, encoding)))
+        .collect())
+}
+
+/// Encoding and the stored messages of a BMG file
+fn read_bmg(data: &[u8]) -> Result<(u8, BTreeMap<u32, BmgMessage>), String> {
+    const ERROR: &'static str = "BMG file is broken.";
+    if !data.starts_with(BMG_MAGIC) || data.len() < BMG_HEADER_SIZE {
+        return Err("Not a BMG file.".to_string());
+    }
+    let encoding = data[0x10];
+
+    // Sections: INF1 has offsets into the text of DAT1, MID1 has the message IDs
+    let (mut inf, mut dat, mut mid) = (None, None, None);
+    let mut pos = BMG_HEADER_SIZE;
+    while pos + 8 <= data.len() {
+        let size = as_u32_be(&data[pos + 4..pos + 8]) as usize;
+        if size < 8 {
+            break;
+        }
+        let section = data.get(pos..pos + size).ok_or(ERROR)?;
+        match &section[0..4] {
+            b"INF1" => inf = Some(section),
+            b"DAT1" => dat = Some(&section[8..]),
+            b"MID1" => mid = Some(section),
+            _ => {}
+        }
+        pos += size;
+    }
+    let (inf, dat) = (inf.ok_or(ERROR)?, dat.ok_or(ERROR)?);
+    let count = as_u16_be(inf.get(8..10).ok_or(ERROR)?) as usize;
+    let entry = as_u16_be(&inf[10..12]) as usize;
+    if entry < 4 {
+        return Err(ERROR.to_string());
+    }
+
+    let mut messages = BTreeMap::new();
+    for i in 0..count {
+        let info = inf.get(16 + i * entry..16 + (i + 1) * entry).ok_or(ERROR)?;
+        let text = dat.get(as_u32_be(info) as usize..).ok_or(ERROR)?;
+        // Without MID1, messages are numbered in order
+        let id = match mid {
+            Some(v) => as_u32_be(v.get(16 + i * 4..20 + i * 4).ok_or(ERROR)?),
+            None => i as u32,
+        };
+        let message = BmgMessage {
+            attributes: info[4..].to_vec(),
+            text: text[..bmg_text_len(text, encoding)].to_vec(),
+        };
+        messages.insert(id, message);
+    }
+    Ok((encoding, messages))
+}
+
+/// Replace or add `messages` in a BMG file of UTF-16 text.
+/// The other messages are kept as they are, with their escape sequences.
+pub fn merge_bmg(original: &[u8], messages: &BTreeMap<u32, String>) -> Result<Vec<u8>, String> {
+    let (encoding, mut stored) = read_bmg(original)?;
+    if encoding != 2 {
+        return Err("Only BMG files of UTF-16 text can be changed.".to_string());
+    }
+    let attributes = stored.values().next().map(|v| v.attributes.len()).unwrap_or(4);
+    for (id, text) in messages {
+        let message = stored.entry(*id).or_insert_with(|| BmgMessage {
+            attributes: vec![0; attributes],
+            text: vec![],
+        });
+        message.text = encode_utf16(text);
+    }
+    Ok(write_bmg(&stored))
+}
+
+fn write_bmg(messages: &BTreeMap<u32, BmgMessage>) -> Vec<u8> {
+    let count = messages.len();
+    let attributes = messages.values().map(|v| v.attributes.len()).max().unwrap_or(4);
+
+    // Text starts with an empty message, so offset 0 is never a real one
+    let mut text: Vec<u8> = vec![0, 0];
+    let mut inf: Vec<u8> = vec![];
+    let mut mid: Vec<u8> = vec![];
+    for (id, message) in messages {
+        inf.extend_from_slice(&(text.len() as u32).to_be_bytes());
+        inf.extend_from_slice(&message.attributes);
+        inf.resize(inf.len() + attributes - message.attributes.len(), 0);
+        text.extend_from_slice(&message.text);
+        text.extend_from_slice(&[0, 0]);
+        mid.extend_from_slice(&id.to_be_bytes());
+    }
+
+    let mut sections: Vec<u8> = vec![];
+    let mut inf_head = vec![0u8; 8];
+    inf_head.extend_from_slice(&(count as u16).to_be_bytes());
+    inf_head.extend_from_slice(&((4 + attributes) as u16).to_be_bytes());
+    inf_head.extend_from_slice(&[0; 4]);
+    inf_head.append(&mut inf);
+    sections.append(&mut bmg_section(b"INF1", inf_head));
+    let mut dat = vec![0u8; 8];
+    dat.append(&mut text);
+    sections.append(&mut bmg_section(b"DAT1", dat));
+    let mut mid_head = vec![0u8; 8];
+    mid_head.extend_from_slice(&(count as u16).to_be_bytes());
+    mid_head.extend_from_slice(&[0x10, 0, 0, 0, 0, 0]);
+    mid_head.append(&mut mid);
+    sections.append(&mut bmg_section(b"MID1", mid_head));
+
+    let mut bmg = BMG_MAGIC.to_vec();
+    bmg.extend_from_slice(&((BMG_HEADER_SIZE + sections.len()) as u32).to_be_bytes());
+    bmg.extend_from_slice(&3u32.to_be_bytes());
+    // UTF-16
+    bmg.push(2);
+    bmg.resize(BMG_HEADER_SIZE, 0);
+    bmg.append(&mut sections);
+    bmg
+}
+
+/// Fill in the magic and size of a section whose first 8 bytes are left for them
+fn bmg_section(magic: &[u8; 4], mut section: Vec<u8>) -> Vec<u8> {
+    section.resize((section.len() + 31) / 32 * 32, 0);
+    let size = (section.len() as u32).to_be_bytes();
+    section[0..4].copy_from_slice(magic);
+    section[4..8].copy_from_slice(&size);
+    section
+}
+
+fn encode_utf16(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(|v| v.to_be_bytes()).collect()
+}
+
+/// Length in bytes of the text up to the terminating zero, which isn't searched inside escape sequences
+fn bmg_text_len(text: &[u8], encoding: u8) -> usize {
+    let mut i = 0;
+    match encoding {
+        2 => {
+            while i + 2 <= text.len() {
+                let v = as_u16_be(&text[i..]);
+                if v == 0 {
+                    return i;
+                }
+                i += if v == BMG_ESCAPE && i + 3 < text.len() {
+                    (text[i + 2] as usize).max(4)
+                } else {
+                    2
+                };
+            }
+        }
+        _ => {
+            while i < text.len() {
+                if text[i] == 0 {
+                    return i;
+                }
+                i += if text[i] == BMG_ESCAPE as u8 && i + 1 < text.len() {
+                    (text[i + 1] as usize).max(2)
+                } else {
+                    1
+                };
+            }
+        }
+    }
+    text.len()
+}
+
+/// Text up to the terminating zero, in the encoding given by the BMG header
+fn decode_bmg_text(text: &[u8], encoding: u8) -> String {
+    match encoding {
+        // UTF-16
+        2 => {
+            let mut units: Vec<u16> = vec![];
+            let mut i = 0;
+            while i + 2 <= text.len() {
+                let v = as_u16_be(&text[i..]);
+                if v == 0 {
+                    break;
+                }
+                if v == BMG_ESCAPE && i + 3 < text.len() {
+                    // The byte after the escape is the length of the whole sequence
+                    i += (text[i + 2] as usize).max(4);
+                    continue;
+                }
+                units.push(v);
+                i += 2;
+            }
+            String::from_utf16_lossy(&units)
+        }
+        // CP1252, UTF-8 and Shift-JIS are read as UTF-8
+        _ => {
+            let mut bytes: Vec<u8> = vec![];
+            let mut i = 0;
+            while i < text.len() && text[i] != 0 {
+                if text[i] == BMG_ESCAPE as u8 && i + 1 < text.len() {
+                    i += (text[i + 1] as usize).max(2);
+                    continue;
+                }
+                bytes.push(text[i]);
+                i += 1;
+            }
+            String::from_utf8_lossy(&bytes).to_string()
+        }
+    }
+}
+
+fn as_u32_be(array: &[u8]) -> u32 {
+    ((array[0] as u32) << 24) + ((array[1] as u32) << 16) + ((array[2] as u32) << 8) + ((array[3] as u32) << 0)
+}
+
+fn as_u16_be(array: &[u8]) -> u16 {
+    ((array[0] as u16) << 8) + ((array[1] as u16) << 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::profile::latest_build;
+
+    /// Offset of LPAR in the fixture, right after the header
+    const LPAR: usize = 0x40;
+    /// Offset of the tables from the start of LPAR
+    const TABLES: usize = 0x40;
+
+    /// LE-CODE binary of one racing and one battle cup over `slots` slots, laid out as `find_lpar` reads it.
+    /// It's made by hand from the offsets above, not cut from a released lecode-PAL.bin.
+    fn fixture(layout: &LeBinaryLayout, slots: usize) -> Vec<u8> {
+        let mut data = LE_BINARY_MAGIC.to_vec();
+        data.extend_from_slice(&layout.header_version.to_be_bytes());
+        data.resize(LPAR, 0);
+        data[layout.param_offset..layout.param_offset + 4].copy_from_slice(&(LPAR as u32).to_be_bytes());
+
+        let mut lpar = LE_PARAM_MAGIC.to_vec();
+        lpar.resize(TABLES, 0);
+        lpar[LPAR_SIZE..LPAR_SIZE + 4].copy_from_slice(&(layout.lpar_size as u32).to_be_bytes());
+        lpar[LPAR_ENGINE..LPAR_ENGINE + 3].copy_from_slice(&[10, 60, 30]);
+        lpar[LPAR_SPEEDOMETER] = 1;
+        let mut tables: Vec<(usize, Vec<u8>)> = vec![
+            (LPAR_CUP_PAR, [1u32, 1, slots as u32].iter().flat_map(|v| v.to_be_bytes()).collect()),
+            (LPAR_CUP_TRACK, (0..4u32).flat_map(|v| (0x44 + v).to_be_bytes()).collect()),
+            (LPAR_CUP_ARENA, (0..5u32).flat_map(|v| (0x20 + v).to_be_bytes()).collect()),
+            (LPAR_PROPERTY, (0..slots as u8).collect()),
+            (LPAR_MUSIC, (0..slots as u8).rev().collect()),
+            (LPAR_FLAGS, vec![0; slots]),
+        ];
+        for (at, table) in tables.iter_mut() {
+            let offset = lpar.len() as u32;
+            lpar[*at..*at + 4].copy_from_slice(&offset.to_be_bytes());
+            lpar.append(table);
+        }
+        data.append(&mut lpar);
+        data
+    }
+
+    #[test]
+    fn parse_fixture() {
+        let layout = &profile(latest_build()).layout;
+        let le = LeBinary::parse(&fixture(layout, 0x50), layout).unwrap();
+        assert_eq!(le.racing_cups, vec![[0x44, 0x45, 0x46, 0x47]]);
+        assert_eq!(le.battle_cups, vec![[0x20, 0x21, 0x22, 0x23, 0x24]]);
+        assert_eq!(le.property.len(), 0x50);
+        assert_eq!(le.music[0], 0x4F);
+        let p = &le.settings.engine_probs;
+        assert_eq!((p.low, p.mid, p.high), (10, 60, 30));
+        assert!(!le.settings.toggle_200cc);
+    }
+
+    #[test]
+    fn lpar_only_where_the_header_points() {
+        let layout = &profile(latest_build()).layout;
+        let mut data = fixture(layout, 0x50);
+        data[layout.param_offset..layout.param_offset + 4].copy_from_slice(&0x20u32.to_be_bytes());
+        assert!(LeBinary::parse(&data, layout).is_err());
+        assert!(LeBinary::parse(b"LPAR", layout).is_err());
+    }
+
+    #[test]
+    fn patch_round_trip() {
+        let layout = &profile(latest_build()).layout;
+        let mut data = fixture(layout, 0x50);
+        let settings = SettingApp {
+            toggle_200cc: true,
+            engine_probs: EngineProbSet { low: 20, mid: 50, high: 30 },
+            toggle_ct_tt: true,
+            prevent_selection_online: 1,
+            toggle_som: SpeedometerMode::Three,
+            toggle_drag_blue: true,
+            time_cloud: 300,
+            ..Default::default()
+        };
+        write_settings(&mut data, &settings, layout).unwrap();
+
+        let le = LeBinary::parse(&data, layout).unwrap();
+        let read = &le.settings;
+        assert!(read.toggle_200cc && read.toggle_ct_tt && read.toggle_drag_blue);
+        let p = &read.engine_probs;
+        assert_eq!((p.low, p.mid, p.high), (20, 50, 30));
+        assert_eq!(read.prevent_selection_online, 1);
+        assert!(read.toggle_som == SpeedometerMode::Three);
+        assert_eq!(read.time_cloud, 300);
+    }
+
+    #[test]
+    fn tables_round_trip() {
+        let layout = &profile(latest_build()).layout;
+        let mut data = fixture(layout, 0x50);
+        let slots = 0x4A;
+        let tables = LeBinary {
+            settings: Default::default(),
+            racing_cups: vec![[0, 1, 2, 3], [0x44, 0x45, 0x46, 0x47]],
+            battle_cups: vec![[0x21, 0x22, 0x23, 0x24, 0x25], [0x48, 0x49, 0x48, 0x49, 0x48]],
+            property: (0..slots as u8).collect(),
+            music: (0..slots as u8).map(|v| v + 0x75).collect(),
+            flags: (0..slots as u8).map(|v| v % 3).collect(),
+        };
+        write_tables(&mut data, &tables, layout).unwrap();
+
+        let le = LeBinary::parse(&data, layout).unwrap();
+        assert_eq!(le.racing_cups, tables.racing_cups);
+        assert_eq!(le.battle_cups, tables.battle_cups);
+        assert_eq!((le.property, le.music, le.flags), (tables.property, tables.music, tables.flags));
+        // The settings are kept
+        let p = &le.settings.engine_probs;
+        assert_eq!((p.low, p.mid, p.high), (10, 60, 30));
+    }
+
+    #[test]
+    fn refuse_other_layouts() {
+        let layout = &profile(latest_build()).layout;
+        let original = fixture(layout, 0x50);
+        let older = older_layout(layout);
+        let mut data = original.clone();
+        assert!(write_settings(&mut data, &SettingApp::default(), &older).is_err());
+        let tables = LeBinary::parse(&original, layout).unwrap();
+        assert!(write_tables(&mut data, &tables, &older).is_err());
+
+        data[HEADER_VERSION + 3] += 1;
+        assert!(write_settings(&mut data, &SettingApp::default(), layout).is_err());
+        data[HEADER_VERSION + 3] -= 1;
+        assert_eq!(data, original);
+    }
+
+    #[test]
+    fn detect_layout() {
+        let latest = profile(latest_build());
+        let data = fixture(&latest.layout, 0x50);
+        assert_eq!(detect_profile(&data, 0).unwrap().build, latest.build);
+        assert_eq!(detect_profile(&data, latest.build).unwrap().build, latest.build);
+        let data = fixture(&older_layout(&latest.layout), 0x50);
+        assert!(detect_profile(&data, latest.build).is_err());
+        assert!(detect_profile(b"LECT", latest.build).is_err());
+    }
+
+    /// `layout` with the shorter LPAR block of `LPAR_SIZE_V1`
+    fn older_layout(layout: &LeBinaryLayout) -> LeBinaryLayout {
+        LeBinaryLayout {
+            header_version: layout.header_version,
+            param_offset: layout.param_offset,
+            lpar_size: LPAR_SIZE_V1,
+        }
+    }
+
+    /// BMG file of UTF-16 `messages`, with a colour escape sequence before the text of ID 0
+    fn bmg(messages: &[(u32, &str)]) -> Vec<u8> {
+        let stored = messages
+            .iter()
+            .map(|(id, text)| {
+                let mut escaped = if *id == 0 { vec![0, 0x1A, 8, 0, 0, 0, 0, 0x20] } else { vec![] };
+                escaped.append(&mut encode_utf16(text));
+                (*id, BmgMessage { attributes: vec![1, 2, 3, 4], text: escaped })
+            })
+            .collect();
+        write_bmg(&stored)
+    }
+
+    #[test]
+    fn bmg_round_trip() {
+        let data = bmg(&[(0, "Red"), (0x2454, "Mario Kart Wii"), (MID_CUP_NAME, "")]);
+        let messages = parse_bmg(&data).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[&0], "Red");
+        assert_eq!(messages[&0x2454], "Mario Kart Wii");
+        assert_eq!(messages[&MID_CUP_NAME], "");
+    }
+
+    #[test]
+    fn merge_keeps_other_messages() {
+        let data = bmg(&[(0, "Red"), (0x2454, "Mario Kart Wii")]);
+        let names = BTreeMap::from([(0x2454, "Mario Kart Wiï".to_string()), (MID_TRACK_NAME + 0x44, "Neu".to_string())]);
+        let merged = merge_bmg(&data, &names).unwrap();
+        let (encoding, stored) = read_bmg(&merged).unwrap();
+        assert_eq!(encoding, 2);
+        // The escape sequence and the attributes are written back as they were
+        assert_eq!(stored[&0].text, read_bmg(&data).unwrap().1[&0].text);
+        assert_eq!(stored[&0].attributes, [1, 2, 3, 4]);
+        assert_eq!(stored[&(MID_TRACK_NAME + 0x44)].attributes, [0; 4]);
+
+        let messages = parse_bmg(&merged).unwrap();
+        assert_eq!(messages[&0], "Red");
+        assert_eq!(messages[&0x2454], "Mario Kart Wiï");
+        assert_eq!(messages[&(MID_TRACK_NAME + 0x44)], "Neu");
+
+        let mut other = data.clone();
+        other[0x10] = 1;
+        assert!(merge_bmg(&other, &names).is_err());
+        assert!(parse_bmg(b"MESGbmg1").is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use super::profile::LeCodeProfile;
+
+/// Parameter of the LPAR text. Which LE-CODE builds read it is given by the version table.
+pub struct LparKey {
+    pub name: &'static str,
+    pub about: &'static str,
+}
+
+impl LparKey {
+    /// Hover text of the option
+    pub fn note(&self) -> String {
+        format!("{}\n{} in the LPAR text.", self.about, self.name)
+    }
+
+    /// Note for options which `profile` doesn't read
+    pub fn unsupported_note(&self, profile: &LeCodeProfile) -> String {
+        format!("{} doesn't read this parameter", profile.name)
+    }
+}
+
+pub const ENABLE_200CC: LparKey = LparKey {
+    name: "ENABLE-200CC",
+    about: "Enable 200cc. The engine classes online become 150cc, 200cc and mirror.",
+};
+pub const ENGINE: LparKey = LparKey {
+    name: "ENGINE",
+    about: "Probabilities of the engine classes online.",
+};
+pub const PERF_MONITOR: LparKey = LparKey {
+    name: "PERF-MONITOR",
+    about: "Show the performance monitor.",
+};
+pub const CUSTOM_TT: LparKey = LparKey {
+    name: "CUSTOM-TT",
+    about: "Allow to select custom tracks in time trial.",
+};
+pub const XPFLAGS: LparKey = LparKey {
+    name: "XPFLAGS",
+    about: "Allow to work extended presence flags.",
+};
+pub const BLOCK_TRACK: LparKey = LparKey {
+    name: "BLOCK-TRACK",
+    about: "Number of races that a previously raced track is blocked online.",
+};
+pub const SPEEDOMETER: LparKey = LparKey {
+    name: "SPEEDOMETER",
+    about: "Show a speedometer at the bottom right.",
+};
+pub const DEBUG: LparKey = LparKey {
+    name: "DEBUG",
+    about: "Show debug information on the screen.",
+};
+pub const ITEM_CHEAT: LparKey = LparKey {
+    name: "ITEM-CHEAT",
+    about: "Allow to choose items in offline races.",
+};
+pub const DRAG_BLUE_SHELL: LparKey = LparKey {
+    name: "DRAG-BLUE-SHELL",
+    about: "Allow player to drag blue shell.",
+};
+pub const THCLOUD_TIME: LparKey = LparKey {
+    name: "THCLOUD-TIME",
+    about: "Time in frames a player is small after being struck by a thundercloud.",
+};
+pub const CHEAT_MODE: LparKey = LparKey {
+    name: "CHEAT-MODE",
+    about: "Where cheat codes of the distribution may be used.",
+};
+pub const BT_WORLDWIDE: LparKey = LparKey {
+    name: "BT-WORLDWIDE",
+    about: "Matchmaking of worldwide battles.",
+};
+pub const VS_WORLDWIDE: LparKey = LparKey {
+    name: "VS-WORLDWIDE",
+    about: "Matchmaking of worldwide races.",
+};
+pub const BT_REGION: LparKey = LparKey {
+    name: "BT-REGION",
+    about: "Region number of battles with players of the same region. 0 keeps the original region.",
+};
+pub const VS_REGION: LparKey = LparKey {
+    name: "VS-REGION",
+    about: "Region number of races with players of the same region. 0 keeps the original region.",
+};
+pub const BLOCK_TEXTURES: LparKey = LparKey {
+    name: "BLOCK-TEXTURES",
+    about: "Ignore texture hacks of players online.",
+};
+pub const TT_200CC: LparKey = LparKey {
+    name: "TT-200CC",
+    about: "Allow time trials in 200cc.",
+};
+pub const SPEED_LIMIT: LparKey = LparKey {
+    name: "SPEED-LIMIT",
+    about: "Maximum speed in km/h. 0 keeps the limit of the game.",
+};
+
+/// All parameters in the order of the LPAR text
+pub const KEYS: [&'static LparKey; 19] = [
+    &ENABLE_200CC,
+    &ENGINE,
+    &PERF_MONITOR,
+    &CUSTOM_TT,
+    &TT_200CC,
+    &XPFLAGS,
+    &BLOCK_TRACK,
+    &SPEEDOMETER,
+    &DEBUG,
+    &ITEM_CHEAT,
+    &CHEAT_MODE,
+    &DRAG_BLUE_SHELL,
+    &THCLOUD_TIME,
+    &BT_WORLDWIDE,
+    &VS_WORLDWIDE,
+    &BT_REGION,
+    &VS_REGION,
+    &BLOCK_TEXTURES,
+    &SPEED_LIMIT,
+];
@@ -0,0 +1,156 @@
+use crate::apps::tracks::{Cup, CupList, Icon, Id, TrackDefinition};
+
+/// The cup select screen shows 2 rows of 4 cups, filled column by column
+pub const ROWS: usize = 2;
+pub const COLUMNS: usize = 4;
+pub const CUPS_PER_PAGE: usize = ROWS * COLUMNS;
+/// Widths the game shows of a name, in heights of the font
+pub const CUP_NAME_WIDTH: f32 = 9.0;
+pub const TRACK_NAME_WIDTH: f32 = 13.0;
+
+const NINTENDO_CUPS: [&'static str; 8] = [
+    "Mushroom Cup",
+    "Flower Cup",
+    "Star Cup",
+    "Special Cup",
+    "Shell Cup",
+    "Banana Cup",
+    "Leaf Cup",
+    "Lightning Cup",
+];
+/// Order which looks like the original menu when filled column by column
+const NINTENDO_SWAP: [usize; 8] = [0, 4, 1, 5, 2, 6, 3, 7];
+const NINTENDO_BATTLE_CUPS: [&'static str; 2] = ["Wii Arenas", "Retro Arenas"];
+
+/// Where a cup of the cup select screen comes from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuCupKind {
+    /// `n`th original cup of the list
+    Nintendo(usize),
+    /// Position in the list of the editor
+    Custom(usize),
+    Wiimm,
+}
+
+/// Cup as the cup select screen shows it
+pub struct MenuCup<'a> {
+    pub name: String,
+    /// Custom cups only, Nintendo cups have their original icons
+    pub icon: Option<&'a Icon>,
+    /// Track names in the order of the cup
+    pub tracks: Vec<String>,
+    pub kind: MenuCupKind,
+}
+
+impl TrackDefinition {
+    /// Cups of `list` in the order of the cup select screen.
+    /// Racing cups start with the Nintendo cups and end with the Wiimm cup when `mode` shows them.
+    pub fn menu_cups(&self, list: CupList) -> Vec<MenuCup<'_>> {
+        let mut cups: Vec<MenuCup> = vec![];
+        match list {
+            CupList::Racing => {
+                if self.mode.nintendo {
+                    let order: Vec<usize> = if self.mode.nin_swap { NINTENDO_SWAP.to_vec() } else { (0..8).collect() };
+                    cups.extend(order.into_iter().map(|n| nintendo_cup(NINTENDO_CUPS[n], n, CupList::Racing)));
+                }
+                cups.extend(self.cups.iter().enumerate().map(|(i, v)| self.custom_cup(i, v)));
+                if self.mode.wiimm_cup {
+                    cups.push(MenuCup {
+                        name: self.wiimm.name.clone(),
+                        icon: Some(&self.wiimm.icon),
+                        tracks: self.wiimm.slots.iter().filter(|v| v.shown).map(|v| v.name().to_string()).collect(),
+                        kind: MenuCupKind::Wiimm,
+                    });
+                }
+            }
+            CupList::Battle if self.battle_cups.is_empty() => {
+                cups.extend(NINTENDO_BATTLE_CUPS.iter().enumerate().map(|(n, v)| nintendo_cup(v, n, CupList::Battle)));
+            }
+            CupList::Battle => cups.extend(self.battle_cups.iter().enumerate().map(|(i, v)| self.custom_cup(i, v))),
+        }
+        cups
+    }
+
+    /// Cups by their index in LE-CODE, which gives both their icon in the sheet and their message ID:
+    /// the racing cups as the cup select screen shows them, then the battle cups
+    pub fn lecode_cups(&self) -> Vec<(CupList, MenuCup<'_>)> {
+        CupList::VALUES
+            .into_iter()
+            .flat_map(|list| self.menu_cups(list).into_iter().map(move |v| (list, v)))
+            .collect()
+    }
+
+    /// Groups show the name of their header
+    fn custom_cup<'a>(&'a self, index: usize, cup: &'a Cup) -> MenuCup<'a> {
+        MenuCup {
+            name: cup.name.clone(),
+            icon: Some(&cup.icon),
+            tracks: cup
+                .groups()
+                .into_iter()
+                .map(|v| self.template.to_plain(&cup.trackset[v.start], None))
+                .collect(),
+            kind: MenuCupKind::Custom(index),
+        }
+    }
+}
+
+/// Tracks of the `n`th original cup of `list`, which are in the order of `Id` for both lists
+pub fn nintendo_tracks(list: CupList, n: usize) -> Vec<Id> {
+    let first = match list {
+        CupList::Racing => 0,
+        CupList::Battle => Id::BlockPlaza as usize,
+    };
+    let size = list.tracks_per_cup();
+    Id::VALUES[first + n * size..first + (n + 1) * size].to_vec()
+}
+
+fn nintendo_cup<'a>(name: &str, n: usize, list: CupList) -> MenuCup<'a> {
+    MenuCup {
+        name: name.to_string(),
+        icon: None,
+        tracks: nintendo_tracks(list, n).iter().map(|v| v.as_str().to_string()).collect(),
+        kind: MenuCupKind::Nintendo(n),
+    }
+}
+
+/// Number of pages to show `cups` cups, which is at least one
+pub fn page_count(cups: usize) -> usize {
+    ((cups + CUPS_PER_PAGE - 1) / CUPS_PER_PAGE).max(1)
+}
+
+/// Index in the list of the cup at `row` and `column` of `page`
+pub fn cup_at(page: usize, row: usize, column: usize) -> usize {
+    page * CUPS_PER_PAGE + column * ROWS + row
+}
+
+/// Rough width of `text` in the game font, in heights of the font
+pub fn text_width(text: &str) -> f32 {
+    text.chars()
+        .map(|c| match c {
+            ' ' | 'i' | 'l' | 'j' | 'I' | '.' | ',' | '\'' | '!' | ':' | ';' | '|' => 0.28,
+            'f' | 't' | 'r' | '(' | ')' | '[' | ']' | '-' => 0.38,
+            'm' | 'w' | 'M' | 'W' => 0.85,
+            c if c.is_ascii_uppercase() || c.is_ascii_digit() => 0.62,
+            c if c.is_ascii() => 0.52,
+            // Kana, kanji and hangul are square
+            _ => 1.0,
+        })
+        .sum()
+}
+
+/// `text` as much as fits in `width`, with `…` when it's cut
+pub fn truncate(text: &str, width: f32) -> String {
+    if text_width(text) <= width {
+        return text.to_string();
+    }
+    let mut s = String::new();
+    for c in text.chars() {
+        if text_width(&s) + text_width(&c.to_string()) + text_width("…") > width {
+            break;
+        }
+        s.push(c);
+    }
+    s.push('…');
+    s
+}
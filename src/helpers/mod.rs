@@ -1,5 +1,20 @@
 use serde::{Deserialize, Serialize};
 
+pub mod archive;
+pub mod build;
 pub mod custom_widget;
+pub mod disc;
+pub mod export;
 pub mod file;
+pub mod game;
+pub mod history;
+pub mod icon;
+pub mod import;
+pub mod lecode;
+pub mod lpar;
+pub mod menu;
 pub mod parser;
+pub mod profile;
+pub mod template;
+pub mod texture;
+pub mod validate;
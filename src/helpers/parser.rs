@@ -1,17 +1,52 @@
-use std::{fs, io::Cursor, path::PathBuf, str, vec};
+use std::{collections::BTreeMap, fs, io::Cursor, path::PathBuf, str, vec};
+
+use image::RgbaImage;
+use resize::px::RGBA;
 
 use crate::{
     apps::{
-        tracks::{CupSettings, TrackDefinition},
-        CheatCodeApp, EngineProbSet, SettingApp, SpeedometerMode, TrackDefApp,
+        tracks::{
+            Cup, CupList, CupSettings, GroupFlag, Icon, IconFilter, IconFit, Id, RandomPool, RandomSlot, Track,
+            TrackDefinition, WiimmCup,
+        },
+        CheatCodeApp, CheatMode, CodeStruct, EngineProbSet, Language, PerfMonitorMode, Region, SettingApp,
+        SpeedometerMode, TrackDefApp,
     },
     Distro,
 };
 
+use super::{profile::profile, template::NameTemplate, texture::TextureFormat};
+
 const MAGIC: &'static str = "ZRP0DIB1";
 // Later should be moved to other source code
 // Increment per push
-const FILE_BUILD_NUMBER: &'static [u8; 2] = &[0, 4];
+const FILE_BUILD_NUMBER: &'static [u8; 2] = &[0, 13];
+/// Oldest build number which can still be opened
+const OLDEST_BUILD_NUMBER: u16 = 4;
+/// Build number which started saving the number of cheat codes.
+/// Older builds wrote the size of the block over the first code, so their codes are not read.
+const BUILD_CHEAT_COUNT: u16 = 5;
+/// Build number which started saving icon options and the original image
+const BUILD_ICON_SOURCE: u16 = 6;
+/// Build number which started saving track thumbnails
+const BUILD_TRACK_THUMBNAIL: u16 = 7;
+/// Build number which started saving battle cups and the number of tracks per cup
+const BUILD_CUP_LISTS: u16 = 8;
+/// Build number which started saving all LPAR parameters and the target LE-CODE build
+const BUILD_LPAR_EXTENDED: u16 = 9;
+/// Size of the settings block before `BUILD_LPAR_EXTENDED`
+pub const SETTINGS_SIZE_V1: usize = 32;
+const SETTINGS_SIZE: usize = 48;
+/// Build number which started saving cup and track names in other languages
+const BUILD_NAME_LANGUAGES: u16 = 10;
+/// Build number which started saving the name template, and track prefixes and versions
+const BUILD_NAME_TEMPLATE: u16 = 11;
+/// Build number which started saving the name, icon and random slots of the Wiimm cup
+const BUILD_WIIMM_CUP: u16 = 12;
+/// Build number which started saving the length of strings in 4 bytes instead of 1
+const BUILD_LONG_STRINGS: u16 = 13;
+const PNG_MAGIC: &'static [u8; 8] = b"\x89PNG\r\n\x1a\n";
+const ERROR_BROKEN: &'static str = "The opened file is not a project file or failed on parsing for any reasons.";
 
 impl Distro {
     pub fn encode(&self) -> Vec<u8> {
@@ -27,8 +62,8 @@ impl Distro {
         initial_size += (setting.len() + cup.len() + cheats.1.len()) as u32;
 
         m.append(&mut magic);
-        initial_size.to_be_bytes().map(|v| m.push(v));
-        FILE_BUILD_NUMBER.map(|v| m.push(v));
+        m.extend_from_slice(&initial_size.to_be_bytes());
+        m.extend_from_slice(FILE_BUILD_NUMBER);
         m.append(&mut cheats.0);
         m.append(&mut setting);
         m.append(&mut cup);
@@ -38,35 +73,49 @@ impl Distro {
     }
 
     pub fn decode(&mut self, path: &PathBuf) -> Result<Self, &'static str> {
-        let file = fs::read(path).unwrap();
-        if str::from_utf8(&file[0..8]).unwrap() != MAGIC && &file[12..14] == FILE_BUILD_NUMBER.as_slice() {
-            return Err("The opened file is not a project file or failed on parsing for any reasons.");
+        let file = fs::read(path).map_err(|_| "Failed to read the project file.")?;
+        if file.len() < 16 || &file[0..8] != MAGIC.as_bytes() {
+            return Err(ERROR_BROKEN);
+        }
+        let build = as_u16_be(&file[12..14]);
+        if build < OLDEST_BUILD_NUMBER {
+            return Err("The project was saved by an older version of the tool, which this version can't open.");
+        }
+        if build > as_u16_be(FILE_BUILD_NUMBER) {
+            return Err("The project was saved by a newer version of the tool. Please update the tool to open it.");
         }
 
         let readable_size = as_u32_be(&file[8..12]) as usize;
-        let mut loc_settings = &file[14..readable_size];
-        let mut loc_cup = &file[48..];
-
-        let cheat_enabled = loc_settings.take(..2).unwrap()[1] != 0;
+        if readable_size > file.len() {
+            return Err(ERROR_BROKEN);
+        }
+        let cheat_enabled = file[15] != 0;
 
-        let cup_flag = loc_cup.take(..1).unwrap()[0];
+        let mut r = Reader::new(&file[..readable_size], 16, build);
+        let settings_size = if build >= BUILD_LPAR_EXTENDED {
+            SETTINGS_SIZE
+        } else {
+            SETTINGS_SIZE_V1
+        };
+        let settings = decode_settings(r.bytes(settings_size)?);
+        let editor = decode_cups(&mut r, build)?;
+        let codes = if build >= BUILD_CHEAT_COUNT {
+            decode_cheats(&mut r)?
+        } else {
+            vec![CodeStruct::default()]
+        };
 
         let distro = Self {
             path: Some(path.to_path_buf()),
-            settings: decode_settings(loc_settings),
+            settings,
             codes: CheatCodeApp {
                 enabled: cheat_enabled,
+                codes: Box::new(codes),
                 ..Default::default()
             },
             tracks: TrackDefApp {
-                editor: TrackDefinition {
-                    mode: CupSettings {
-                        nintendo: (0b001u8 & cup_flag) != 0,
-                        nin_swap: (0b010u8 & cup_flag) != 0,
-                        wiimm_cup: (0b100u8 & cup_flag) != 0,
-                    },
-                    ..Default::default()
-                },
+                editor,
+                ..Default::default()
             },
             ..Default::default()
         };
@@ -104,6 +153,21 @@ pub fn encode_settings(s: &SettingApp) -> Vec<u8> {
     for ele in s.time_cloud.to_be_bytes() {
         pl.push(ele);
     }
+    // Target LE-CODE build
+    pl.extend_from_slice(&s.lecode_build.to_be_bytes());
+    pl.push(s.toggle_perf as u8);
+    pl.push(s.toggle_tt_200cc as u8);
+    pl.push(s.toggle_debug as u8);
+    pl.push(s.cheat_mode as u8);
+    pl.push(s.item_cheat as u8);
+    pl.push(s.toggle_block_textures as u8);
+    pl.push(s.toggle_bt_worldwide as u8);
+    pl.push(s.toggle_vs_worldwide as u8);
+    pl.extend_from_slice(&s.bt_region.to_be_bytes());
+    pl.extend_from_slice(&s.vs_region.to_be_bytes());
+    pl.extend_from_slice(&s.speed_limit.to_be_bytes());
+    // Regions to build
+    pl.push(encode_regions(&s.regions));
 
     let unfilled = 16 - (pl.len() % 16);
     pl.append(&mut zeros(unfilled));
@@ -115,56 +179,59 @@ pub fn encode_cups(c: &TrackDefinition) -> Vec<u8> {
     let mut pl: Vec<u8> = vec![];
 
     // Cup Settings
-    pl.push((c.mode.nintendo as u8) + ((c.mode.nin_swap as u8) << 1) + ((c.mode.wiimm_cup as u8) << 2));
+    pl.push(encode_cup_mode(&c.mode));
     pl.append(&mut zeros(1));
-    // Cup Length
+    // Cup Length of racing and battle cups
     pl.append(&mut (c.cups.len() as u16).to_be_bytes().to_vec());
+    pl.append(&mut (c.battle_cups.len() as u16).to_be_bytes().to_vec());
 
     let unfilled = 16 - (pl.len() % 16);
     pl.append(&mut zeros(unfilled));
 
-    // Cup Sets
-    for cup in &c.cups {
+    // Name template
+    pl.append(&mut encode_str(&c.template.0));
+
+    // Cup Sets, racing cups first
+    for cup in c.cups.iter().chain(&c.battle_cups) {
         let mut cl: Vec<u8> = vec![];
 
         // Cup name
-        let mut cup_name: Vec<u8> = (cup.name.len() as u8).to_be_bytes().to_vec();
-        cup_name.append(&mut cup.name.clone().into_bytes());
-        cup_name.append(&mut zeros(8 - (cup_name.len() % 8)));
-        cl.append(&mut cup_name);
+        cl.append(&mut encode_str(&cup.name));
 
         // Icon binary
         let compiled_image = encode_image(&cup.icon.image);
-        let mut icon_bin: Vec<u8> = compiled_image.len().to_be_bytes().to_vec();
+        let mut icon_bin: Vec<u8> = (compiled_image.len() as u64).to_be_bytes().to_vec();
         icon_bin.append(&mut compiled_image.clone());
         icon_bin.append(&mut zeros(8 - (compiled_image.len() % 8)));
-        // let mut icon_bin: Vec<u8> = cup.icon.image.len().to_be_bytes().to_vec();
-        // icon_bin.append(&mut cup.icon.image.to_vec());
-        // icon_bin.append(&mut zeros(8 - (icon_bin.len() % 8)));
         cl.append(&mut icon_bin);
         // Icon filename
-        let mut icon_filename: Vec<u8> = (cup.icon.filename.len() as u8).to_be_bytes().to_vec();
-        icon_filename.append(&mut cup.icon.filename.clone().into_bytes());
-        icon_filename.append(&mut zeros(8 - (icon_filename.len() % 8)));
-        cl.append(&mut icon_filename);
+        cl.append(&mut encode_str(&cup.icon.filename));
+        // Icon options
+        cl.push(cup.icon.fit as u8);
+        cl.push(cup.icon.filter as u8);
+        cl.push(cup.icon.keep_source as u8);
+        // Track count
+        cl.push(cup.trackset.len() as u8);
+        cl.append(&mut zeros(4));
+        // Original image
+        let source: &[u8] = if cup.icon.keep_source { &cup.icon.source } else { &[] };
+        cl.append(&mut (source.len() as u64).to_be_bytes().to_vec());
+        cl.extend_from_slice(source);
+        cl.append(&mut zeros(8 - (source.len() % 8)));
+        // Names in other languages
+        cl.append(&mut encode_names(&cup.names));
 
         // Tracks
         for track in &cup.trackset {
             // Unused ID
-            let mut id: Vec<u8> = track.id.to_be_bytes().to_vec();
+            let mut id: Vec<u8> = (track.id as u64).to_be_bytes().to_vec();
             cl.append(&mut id);
 
             // Name
-            let mut name: Vec<u8> = (track.name.len() as u8).to_be_bytes().to_vec();
-            name.append(&mut track.name.clone().into_bytes());
-            name.append(&mut zeros(8 - (name.len() % 8)));
-            cl.append(&mut name);
+            cl.append(&mut encode_str(&track.name));
 
             // Author
-            let mut author: Vec<u8> = (track.author.len() as u8).to_be_bytes().to_vec();
-            author.append(&mut track.author.clone().into_bytes());
-            author.append(&mut zeros(8 - (author.len() % 8)));
-            cl.append(&mut author);
+            cl.append(&mut encode_str(&track.author));
 
             // Special IDs
             cl.push(track.property as u8);
@@ -177,15 +244,27 @@ pub fn encode_cups(c: &TrackDefinition) -> Vec<u8> {
             cl.push(track.flag as u8);
 
             // Filename
-            let mut filename: Vec<u8> = (track.filename.len() as u8).to_be_bytes().to_vec();
-            filename.append(&mut track.filename.clone().into_bytes());
-            filename.append(&mut zeros(8 - (filename.len() % 8)));
-            cl.append(&mut filename);
+            cl.append(&mut encode_str(&track.filename));
+
+            // Thumbnail
+            cl.append(&mut (track.thumbnail.len() as u64).to_be_bytes().to_vec());
+            cl.extend_from_slice(&track.thumbnail);
+            cl.append(&mut zeros(8 - (track.thumbnail.len() % 8)));
+
+            // Names in other languages
+            cl.append(&mut encode_names(&track.names));
+
+            // Prefix and version for the name template
+            cl.append(&mut encode_str(&track.prefix));
+            cl.append(&mut encode_str(&track.version));
         }
 
         pl.append(&mut cl);
     }
 
+    // Wiimm cup
+    pl.append(&mut encode_wiimm_cup(&c.wiimm));
+
     let unfilled = 16 - (pl.len() % 16);
     pl.append(&mut zeros(unfilled));
 
@@ -194,66 +273,73 @@ pub fn encode_cups(c: &TrackDefinition) -> Vec<u8> {
 
 pub fn encode_cheats(c: &CheatCodeApp) -> (Vec<u8>, Vec<u8>) {
     let b = c.enabled() as u16;
-    let mut pl: Vec<u8> = vec![];
+    // Count and length of the block are filled at last
+    let mut pl: Vec<u8> = zeros(16);
 
     let codes = c.codes.as_ref();
     for code in codes {
-        pl.append(&mut (code.name.len() as u16).to_be_bytes().to_vec());
-        pl.append(&mut code.clone().name.into_bytes());
-        pl.append(&mut zeros(8 - (pl.len() % 8)));
-
-        let mut code_ntsc = code.clone().code_ntsc;
-        code_ntsc.remove_matches(" ");
-        code_ntsc.remove_matches("\n");
-        pl.append(&mut (code_ntsc.len() as u32).to_be_bytes().to_vec());
-        pl.append(&mut code_ntsc.into_bytes());
-        pl.append(&mut zeros(8 - (pl.len() % 8)));
-
-        let mut code_pal = code.clone().code_pal;
-        code_pal.remove_matches(" ");
-        code_pal.remove_matches("\n");
-        pl.append(&mut (code_pal.len() as u32).to_be_bytes().to_vec());
-        pl.append(&mut code_pal.into_bytes());
-        pl.append(&mut zeros(8 - (pl.len() % 8)));
-
-        let mut code_jp = code.clone().code_jp;
-        code_jp.remove_matches(" ");
-        code_jp.remove_matches("\n");
-        pl.append(&mut (code_jp.len() as u32).to_be_bytes().to_vec());
-        pl.append(&mut code_jp.into_bytes());
-        pl.append(&mut zeros(8 - (pl.len() % 8)));
-
-        let mut code_kor = code.clone().code_kor;
-        code_kor.remove_matches(" ");
-        code_kor.remove_matches("\n");
-        pl.append(&mut (code_kor.len() as u32).to_be_bytes().to_vec());
-        pl.append(&mut code_kor.into_bytes());
-        pl.append(&mut zeros(8 - (pl.len() % 8)));
+        pl.append(&mut encode_str(&code.name));
+
+        for body in [&code.code_ntsc, &code.code_pal, &code.code_jp, &code.code_kor] {
+            let mut body = body.clone();
+            body.remove_matches(" ");
+            body.remove_matches("\n");
+            pl.append(&mut (body.len() as u32).to_be_bytes().to_vec());
+            pl.append(&mut body.into_bytes());
+            pl.append(&mut zeros(8 - (pl.len() % 8)));
+        }
     }
 
     pl.append(&mut zeros(16 - (pl.len() % 16)));
 
+    let count = (codes.len() as u32).to_be_bytes();
     let len = (pl.len() as u32).to_be_bytes();
 
-    for (l, v) in len.iter().enumerate() {
-        pl[4 + l] = *v;
+    for l in 0..4 {
+        pl[l] = count[l];
+        pl[4 + l] = len[l];
     }
 
     (b.to_be_bytes().to_vec(), pl)
 }
 
-// While saving a project the file size mostly could be larger, so try to convert into Webp because it's smaller
+// Icons are stored as PNG, so saving never loses quality.
+// Projects of older builds have WebP icons, which are converted here.
 pub fn encode_image(i: &[u8]) -> Vec<u8> {
-    let image = image::load_from_memory(i).expect("Failed to consider image");
+    if i.is_empty() || i.starts_with(PNG_MAGIC) {
+        return i.to_vec();
+    }
+    let image = match image::load_from_memory(i) {
+        Ok(v) => v,
+        Err(_) => return vec![],
+    };
 
-    let encoder = webp::Encoder::from_image(&image).expect("Failed to consider image for webp");
-    let encoded_webp: webp::WebPMemory = encoder.encode(65f32);
+    encode_png(&image.to_rgba8()).unwrap_or_default()
+}
 
-    encoded_webp.to_vec()
+pub fn encode_cup_mode(m: &CupSettings) -> u8 {
+    (m.nintendo as u8)
+        + ((m.nin_swap as u8) << 1)
+        + ((m.wiimm_cup as u8) << 2)
+        + (((m.icon_format == TextureFormat::Rgb5a3) as u8) << 3)
 }
 
-fn decode_settings(a: &[u8]) -> SettingApp {
-    SettingApp {
+pub fn decode_cup_mode(flag: u8) -> CupSettings {
+    CupSettings {
+        nintendo: (0b0001u8 & flag) != 0,
+        nin_swap: (0b0010u8 & flag) != 0,
+        wiimm_cup: (0b0100u8 & flag) != 0,
+        icon_format: if (0b1000u8 & flag) != 0 {
+            TextureFormat::Rgb5a3
+        } else {
+            TextureFormat::Cmpr
+        },
+    }
+}
+
+/// Settings of older projects keep the defaults of the parameters added later
+pub fn decode_settings(a: &[u8]) -> SettingApp {
+    let mut s = SettingApp {
         toggle_200cc: a[0] != 0,
         engine_probs: EngineProbSet {
             low: as_u32_be(&a[1..5]) as i32,
@@ -263,27 +349,408 @@ fn decode_settings(a: &[u8]) -> SettingApp {
         toggle_ct_tt: a[13] != 0,
         toggle_custom_presence: a[14] != 0,
         prevent_selection_online: a[15] as i8,
-        toggle_som: SpeedometerMode::from_usize(a[16].into()).unwrap(),
+        toggle_som: SpeedometerMode::from_usize(a[16].into()).unwrap_or(SpeedometerMode::Two),
         toggle_drag_blue: a[17] != 0,
         time_cloud: as_u16_be(&a[18..20]) as i16,
+        ..Default::default()
+    };
+    if a.len() >= SETTINGS_SIZE {
+        // Builds which the version table no longer lists fall back to a listed one
+        s.lecode_build = profile(as_u16_be(&a[20..22])).build;
+        s.toggle_perf = PerfMonitorMode::from_u8(a[22]).unwrap_or(PerfMonitorMode::Off);
+        s.toggle_tt_200cc = a[23] != 0;
+        s.toggle_debug = a[24] != 0;
+        s.cheat_mode = CheatMode::from_u8(a[25]).unwrap_or(CheatMode::Off);
+        s.item_cheat = CheatMode::from_u8(a[26]).unwrap_or(CheatMode::Off);
+        s.toggle_block_textures = a[27] != 0;
+        s.toggle_bt_worldwide = a[28] != 0;
+        s.toggle_vs_worldwide = a[29] != 0;
+        s.bt_region = as_u16_be(&a[30..32]);
+        s.vs_region = as_u16_be(&a[32..34]);
+        s.speed_limit = as_u16_be(&a[34..36]);
+        s.regions = decode_regions(a[36]);
+    }
+    s
+}
+
+fn encode_regions(regions: &[Region]) -> u8 {
+    let mut flag = 0u8;
+    for (i, region) in Region::VALUES.iter().enumerate() {
+        if regions.contains(region) {
+            flag |= 1 << i;
+        }
     }
+    flag
 }
 
-pub fn decode_image(path: PathBuf) -> Result<Vec<u8>, image::ImageError> {
-    let mut image: Vec<u8> = vec![];
+/// Projects saved before the region selection have zero, which selects all regions
+fn decode_regions(flag: u8) -> Vec<Region> {
+    let regions: Vec<Region> = Region::VALUES
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| flag & (1 << i) != 0)
+        .map(|(_, v)| v)
+        .collect();
+    if regions.is_empty() {
+        Region::VALUES.to_vec()
+    } else {
+        regions
+    }
+}
 
-    match image::open(path) {
-        Ok(v) => {
-            v.resize(128, 128, image::imageops::FilterType::Triangle)
-                .write_to(&mut Cursor::new(&mut image), image::ImageOutputFormat::Png)
-                .unwrap();
+fn decode_cups(r: &mut Reader, build: u16) -> Result<TrackDefinition, &'static str> {
+    let head = r.bytes(16)?;
+    let cup_flag = head[0];
+    let count = as_u16_be(&head[2..4]) as usize;
+    let battle_count = if build >= BUILD_CUP_LISTS {
+        as_u16_be(&head[4..6]) as usize
+    } else {
+        0
+    };
+    let start = r.pos;
+    let template = if build >= BUILD_NAME_TEMPLATE {
+        NameTemplate(r.string()?)
+    } else {
+        Default::default()
+    };
+
+    let mut cups: Vec<Cup> = Vec::with_capacity(count + battle_count);
+    for n in 0..count + battle_count {
+        let list = if n < count { CupList::Racing } else { CupList::Battle };
+        let mut track_count = list.tracks_per_cup();
+        let name = r.string()?;
+        let image_len = r.u64()? as usize;
+        let image = r.bytes(image_len)?.to_vec();
+        r.skip(8 - (image_len % 8))?;
+        let filename = r.string()?;
+        let mut icon = Icon {
+            filename,
+            image,
+            ..Default::default()
+        };
+        if build >= BUILD_ICON_SOURCE {
+            let options = r.bytes(8)?;
+            icon.fit = IconFit::from_u8(options[0]).ok_or(ERROR_BROKEN)?;
+            icon.filter = IconFilter::from_u8(options[1]).ok_or(ERROR_BROKEN)?;
+            icon.keep_source = options[2] != 0;
+            if build >= BUILD_CUP_LISTS {
+                track_count = options[3] as usize;
+            }
+            let source_len = r.u64()? as usize;
+            icon.source = r.bytes(source_len)?.to_vec();
+            r.skip(8 - (source_len % 8))?;
         }
-        Err(err) => {
-            return Err(err);
+        let names = if build >= BUILD_NAME_LANGUAGES {
+            decode_names(r)?
+        } else {
+            BTreeMap::new()
+        };
+
+        let mut trackset: Vec<Track> = Vec::with_capacity(track_count);
+        for _ in 0..track_count {
+            let id = r.u64()? as usize;
+            let name = r.string()?;
+            let author = r.string()?;
+            let flags = r.bytes(4)?;
+            let filename = r.string()?;
+            let thumbnail = if build >= BUILD_TRACK_THUMBNAIL {
+                let len = r.u64()? as usize;
+                let v = r.bytes(len)?.to_vec();
+                r.skip(8 - (len % 8))?;
+                v
+            } else {
+                vec![]
+            };
+            let names = if build >= BUILD_NAME_LANGUAGES {
+                decode_names(r)?
+            } else {
+                BTreeMap::new()
+            };
+            let (prefix, version) = if build >= BUILD_NAME_TEMPLATE {
+                (r.string()?, r.string()?)
+            } else {
+                (String::new(), String::new())
+            };
+            trackset.push(Track {
+                id,
+                name,
+                names,
+                prefix,
+                version,
+                author,
+                property: Id::from_u8(flags[0]).ok_or(ERROR_BROKEN)?,
+                music: Id::from_u8(flags[1]).ok_or(ERROR_BROKEN)?,
+                new: flags[2] != 0,
+                flag: GroupFlag::from_u8(flags[3]).ok_or(ERROR_BROKEN)?,
+                filename,
+                thumbnail,
+            });
         }
+
+        cups.push(Cup {
+            icon,
+            name,
+            names,
+            trackset,
+        });
     }
+    let wiimm = if build >= BUILD_WIIMM_CUP {
+        decode_wiimm_cup(r)?
+    } else {
+        Default::default()
+    };
+    r.skip(16 - ((r.pos - start) % 16))?;
+
+    let battle_cups = cups.split_off(count);
+    if cups.is_empty() {
+        cups.push(Cup::default(0));
+    }
+
+    Ok(TrackDefinition {
+        mode: decode_cup_mode(cup_flag),
+        cups,
+        battle_cups,
+        template,
+        wiimm,
+        ..Default::default()
+    })
+}
+
+fn decode_cheats(r: &mut Reader) -> Result<Vec<CodeStruct>, &'static str> {
+    let start = r.pos;
+    let head = r.bytes(16)?;
+    let count = as_u32_be(&head[0..4]) as usize;
+
+    let mut codes: Vec<CodeStruct> = vec![];
+    for _ in 0..count {
+        let name = if r.build >= BUILD_LONG_STRINGS {
+            r.string()?
+        } else {
+            let len = as_u16_be(r.bytes(2)?) as usize;
+            let name = String::from_utf8_lossy(r.bytes(len)?).to_string();
+            r.skip(8 - ((r.pos - start) % 8))?;
+            name
+        };
+
+        let mut bodies: Vec<String> = vec![];
+        for _ in 0..4 {
+            let len = as_u32_be(r.bytes(4)?) as usize;
+            bodies.push(format_code(&String::from_utf8_lossy(r.bytes(len)?)));
+            r.skip(8 - ((r.pos - start) % 8))?;
+        }
+
+        codes.push(CodeStruct {
+            name,
+            code_kor: bodies.pop().unwrap(),
+            code_jp: bodies.pop().unwrap(),
+            code_pal: bodies.pop().unwrap(),
+            code_ntsc: bodies.pop().unwrap(),
+        });
+    }
+
+    if codes.is_empty() {
+        codes.push(CodeStruct::default());
+    }
+
+    Ok(codes)
+}
+
+/// Codes are saved without spaces and line breaks, so put them back as `XXXXXXXX XXXXXXXX` lines
+fn format_code(s: &str) -> String {
+    let chars: Vec<char> = s.chars().filter(|v| !v.is_whitespace()).collect();
+    chars
+        .chunks(16)
+        .map(|v| {
+            let (first, second) = v.split_at(v.len().min(8));
+            let first: String = first.iter().collect();
+            if second.is_empty() {
+                first
+            } else {
+                format!("{} {}", first, second.iter().collect::<String>())
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Resize an image file into a square of `side` pixels as PNG
+pub fn resize_image(source: &[u8], side: u32, fit: IconFit, filter: IconFilter) -> Result<Vec<u8>, String> {
+    let image = image::load_from_memory(source).map_err(|e| e.to_string())?.to_rgba8();
+    let (w, h) = (image.width(), image.height());
+
+    // Part of the image to use, and its size in the icon
+    let (crop, size) = match fit {
+        IconFit::Crop => {
+            let crop = w.min(h);
+            (((w - crop) / 2, (h - crop) / 2, crop, crop), (side, side))
+        }
+        IconFit::Fit => {
+            let scale = side as f32 / w.max(h) as f32;
+            let scaled = |v: u32| ((v as f32 * scale).round() as u32).max(1);
+            ((0, 0, w, h), (scaled(w), scaled(h)))
+        }
+        IconFit::Fill => ((0, 0, w, h), (side, side)),
+    };
+
+    let cropped = image::imageops::crop_imm(&image, crop.0, crop.1, crop.2, crop.3).to_image();
+    let src: Vec<RGBA<u8>> = cropped.pixels().map(|p| RGBA::new(p[0], p[1], p[2], p[3])).collect();
+    let mut dst = vec![RGBA::new(0, 0, 0, 0); (size.0 * size.1) as usize];
+    resize::new(
+        crop.2 as usize,
+        crop.3 as usize,
+        size.0 as usize,
+        size.1 as usize,
+        resize::Pixel::RGBA8P,
+        filter.kind(),
+    )
+    .and_then(|mut v| v.resize(&src, &mut dst))
+    .map_err(|e| e.to_string())?;
+
+    let resized = RgbaImage::from_fn(size.0, size.1, |x, y| {
+        let p = dst[(y * size.0 + x) as usize];
+        image::Rgba([p.r, p.g, p.b, p.a])
+    });
+    let mut square = RgbaImage::new(side, side);
+    image::imageops::replace(&mut square, &resized, ((side - size.0) / 2) as i64, ((side - size.1) / 2) as i64);
+    encode_png(&square)
+}
 
-    Ok(image)
+pub fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, String> {
+    let mut png: Vec<u8> = vec![];
+    image
+        .write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(png)
+}
+
+/// Name and icon like cups, then the pool, visibility and name of each random slot
+fn encode_wiimm_cup(w: &WiimmCup) -> Vec<u8> {
+    let mut pl: Vec<u8> = vec![];
+    pl.append(&mut encode_str(&w.name));
+    pl.append(&mut (w.icon.image.len() as u64).to_be_bytes().to_vec());
+    pl.extend_from_slice(&w.icon.image);
+    pl.append(&mut zeros(8 - (w.icon.image.len() % 8)));
+    pl.append(&mut encode_str(&w.icon.filename));
+    pl.push(w.icon.fit as u8);
+    pl.push(w.icon.filter as u8);
+    pl.push(w.icon.keep_source as u8);
+    pl.push(w.slots.len() as u8);
+    pl.append(&mut zeros(4));
+    let source: &[u8] = if w.icon.keep_source { &w.icon.source } else { &[] };
+    pl.append(&mut (source.len() as u64).to_be_bytes().to_vec());
+    pl.extend_from_slice(source);
+    pl.append(&mut zeros(8 - (source.len() % 8)));
+    for slot in &w.slots {
+        pl.push(slot.pool as u8);
+        pl.push(slot.shown as u8);
+        pl.append(&mut zeros(6));
+        pl.append(&mut encode_str(&slot.name));
+    }
+    pl
+}
+
+fn decode_wiimm_cup(r: &mut Reader) -> Result<WiimmCup, &'static str> {
+    let name = r.string()?;
+    let image_len = r.u64()? as usize;
+    let image = r.bytes(image_len)?.to_vec();
+    r.skip(8 - (image_len % 8))?;
+    let filename = r.string()?;
+    let options = r.bytes(8)?;
+    let source_len = r.u64()? as usize;
+    let source = r.bytes(source_len)?.to_vec();
+    r.skip(8 - (source_len % 8))?;
+    let icon = Icon {
+        filename,
+        image,
+        source,
+        keep_source: options[2] != 0,
+        fit: IconFit::from_u8(options[0]).ok_or(ERROR_BROKEN)?,
+        filter: IconFilter::from_u8(options[1]).ok_or(ERROR_BROKEN)?,
+    };
+    let mut slots = Vec::with_capacity(options[3] as usize);
+    for _ in 0..options[3] {
+        let head = r.bytes(8)?;
+        slots.push(RandomSlot {
+            pool: RandomPool::from_u8(head[0]).ok_or(ERROR_BROKEN)?,
+            shown: head[1] != 0,
+            name: r.string()?,
+        });
+    }
+    Ok(WiimmCup { name, icon, slots })
+}
+
+/// Count, then the language and string of each name
+fn encode_names(names: &BTreeMap<Language, String>) -> Vec<u8> {
+    let mut pl: Vec<u8> = vec![names.len() as u8];
+    pl.append(&mut zeros(7));
+    for (language, name) in names {
+        pl.push(*language as u8);
+        pl.append(&mut zeros(7));
+        pl.append(&mut encode_str(name));
+    }
+    pl
+}
+
+fn decode_names(r: &mut Reader) -> Result<BTreeMap<Language, String>, &'static str> {
+    let count = r.bytes(8)?[0] as usize;
+    let mut names = BTreeMap::new();
+    for _ in 0..count {
+        let language = Language::from_u8(r.bytes(8)?[0]).ok_or(ERROR_BROKEN)?;
+        names.insert(language, r.string()?);
+    }
+    Ok(names)
+}
+
+/// Length-prefixed string padded to 8 bytes
+fn encode_str(s: &str) -> Vec<u8> {
+    let mut pl: Vec<u8> = (s.len() as u32).to_be_bytes().to_vec();
+    pl.extend_from_slice(s.as_bytes());
+    pl.append(&mut zeros(8 - (pl.len() % 8)));
+    pl
+}
+
+/// Cursor over the project file
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Build number of the file, for the parts which are read differently by build
+    build: u16,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], pos: usize, build: u16) -> Self {
+        Self { data, pos, build }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        if self.pos + len > self.data.len() {
+            return Err(ERROR_BROKEN);
+        }
+        let v = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(v)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), &'static str> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u64(&mut self) -> Result<u64, &'static str> {
+        let v = self.bytes(8)?;
+        Ok(((as_u32_be(&v[0..4]) as u64) << 32) + as_u32_be(&v[4..8]) as u64)
+    }
+
+    /// Read a string written by `encode_str`
+    fn string(&mut self) -> Result<String, &'static str> {
+        let prefix = if self.build >= BUILD_LONG_STRINGS { 4 } else { 1 };
+        let len = match self.bytes(prefix)? {
+            [v] => *v as usize,
+            v => as_u32_be(v) as usize,
+        };
+        let s = String::from_utf8_lossy(self.bytes(len)?).to_string();
+        self.skip(8 - ((len + prefix) % 8))?;
+        Ok(s)
+    }
 }
 
 // Code below brings from Stack Overflow (https://stackoverflow.com/questions/29530011/creating-a-vector-of-zeros-for-a-specific-size),
@@ -303,3 +770,106 @@ fn as_u32_be(array: &[u8]) -> u32 {
 fn as_u16_be(array: &[u8]) -> u16 {
     ((array[0] as u16) << 8) + ((array[1] as u16) << 0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Project saved by the encoder of `build`. Every fixture has the same sample content,
+    /// as far as the build could save it.
+    fn fixture(build: u16) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("tests/fixtures/project-build-{}.mkprj", build))
+    }
+
+    /// Check the sample content which every build saves
+    fn check_fixture(d: &Distro) {
+        assert!(d.settings.toggle_200cc);
+        let p = &d.settings.engine_probs;
+        assert_eq!((p.low, p.mid, p.high), (20, 50, 30));
+        assert_eq!(d.settings.time_cloud, 300);
+        let editor = &d.tracks.editor;
+        assert!(editor.mode.nin_swap);
+        assert_eq!(editor.cups[0].name, "Fixture Cup");
+        let track = &editor.cups[0].trackset[0];
+        assert_eq!((track.name.as_str(), track.author.as_str()), ("Fixture Track", "Fixture Author"));
+        assert!(track.new);
+        assert_eq!(track.filename, "fixture.szs");
+        assert!(!editor.cups[0].icon.image.is_empty());
+        assert!(d.codes.enabled);
+    }
+
+    #[test]
+    fn decode_build_4() {
+        let d = Distro::default().decode(&fixture(4)).unwrap();
+        check_fixture(&d);
+        // The codes of build 4 can't be read back
+        assert_eq!(d.codes.codes.len(), 1);
+    }
+
+    #[test]
+    fn decode_every_build() {
+        for build in BUILD_CHEAT_COUNT..BUILD_LONG_STRINGS {
+            let d = match Distro::default().decode(&fixture(build)) {
+                Ok(v) => v,
+                Err(e) => panic!("build {}: {}", build, e),
+            };
+            check_fixture(&d);
+            let code = &d.codes.codes[0];
+            assert_eq!(code.name, "Fixture Code", "build {}", build);
+            assert!(code.code_pal.contains("04000000 00000000"), "build {}", build);
+        }
+    }
+
+    #[test]
+    fn current_build_round_trip() {
+        let path = std::env::temp_dir().join("mkw-distro-tool-round-trip.mkprj");
+        let saved = Distro::default().decode(&fixture(BUILD_LONG_STRINGS - 1)).unwrap();
+        saved.save_project(&path).unwrap();
+        assert_eq!(as_u16_be(&fs::read(&path).unwrap()[12..14]), as_u16_be(FILE_BUILD_NUMBER));
+        let d = Distro::default().decode(&path).unwrap();
+        check_fixture(&d);
+        assert_eq!(d.codes.codes.len(), saved.codes.codes.len());
+        assert_eq!(d.codes.codes[0].name, "Fixture Code");
+        assert!(d.tracks.editor.wiimm == saved.tracks.editor.wiimm);
+        assert!(d.tracks.editor.cups == saved.tracks.editor.cups);
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn refuse_unknown_builds() {
+        let path = std::env::temp_dir().join("mkw-distro-tool-build-test.mkprj");
+        let mut data = fs::read(fixture(4)).unwrap();
+        for build in [OLDEST_BUILD_NUMBER - 1, as_u16_be(FILE_BUILD_NUMBER) + 1] {
+            data[12..14].copy_from_slice(&build.to_be_bytes());
+            fs::write(&path, &data).unwrap();
+            match Distro::default().decode(&path) {
+                Err(e) => assert!(e.contains("version of the tool")),
+                Ok(_) => panic!("build {} is opened", build),
+            }
+        }
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn format_codes() {
+        assert_eq!(format_code("04000000 00000000\n0400000100000001"), "04000000 00000000\n04000001 00000001");
+        assert_eq!(format_code("0400"), "0400");
+        // Not a code, but it must not cut a character in two
+        assert_eq!(format_code("0400000ä00000000"), "0400000ä 00000000");
+    }
+
+    #[test]
+    fn keep_long_strings() {
+        let path = std::env::temp_dir().join("mkw-distro-tool-string-test.mkprj");
+        let mut dist = Distro::default();
+        let long = "a".repeat(300);
+        dist.tracks.editor.cups[0].trackset[0].filename = long.clone();
+        dist.codes.codes[0].name = long.clone();
+        dist.save_project(&path).unwrap();
+        let d = Distro::default().decode(&path).unwrap();
+        assert_eq!(d.tracks.editor.cups[0].trackset[0].filename, long);
+        assert_eq!(d.codes.codes[0].name, long);
+        fs::remove_file(&path).ok();
+    }
+}
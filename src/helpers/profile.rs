@@ -0,0 +1,110 @@
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use super::lpar::LparKey;
+
+/// Version table, bundled with the tool
+const PROFILES_JSON: &'static str = include_str!("../../data/lecode-profiles.json");
+
+/// Version table, read on first use and shared by all threads
+static PROFILES: OnceLock<Vec<LeCodeProfile>> = OnceLock::new();
+
+/// What a LE-CODE build supports, read from the version table
+#[derive(Deserialize)]
+pub struct LeCodeProfile {
+    pub name: String,
+    pub build: u16,
+    /// Where the entry is taken from, like the changelog of the build
+    pub source: String,
+    pub limits: LeCodeLimits,
+    pub layout: LeBinaryLayout,
+    /// LPAR parameters the build reads
+    pub lpar_keys: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LeCodeLimits {
+    /// All track slots, including the ones reserved below `FIRST_CUSTOM_SLOT`
+    pub slots: usize,
+    pub racing_cups: usize,
+    pub battle_cups: usize,
+}
+
+/// Where the parameters are in lecode-*.bin
+#[derive(Deserialize)]
+pub struct LeBinaryLayout {
+    /// Version in the header, which changes with the header layout
+    pub header_version: u32,
+    /// Offset in the header which points to the LPAR block
+    pub param_offset: usize,
+    /// Size of the LPAR block up to the cup tables
+    pub lpar_size: usize,
+}
+
+#[derive(Deserialize)]
+struct ProfileTable {
+    profiles: Vec<LeCodeProfile>,
+}
+
+fn load_profiles() -> Vec<LeCodeProfile> {
+    let mut profiles = serde_json::from_str::<ProfileTable>(PROFILES_JSON)
+        .expect("The LE-CODE version table is broken.")
+        .profiles;
+    profiles.sort_by_key(|v| v.build);
+    profiles
+}
+
+/// Known LE-CODE builds, oldest first
+pub fn profiles() -> &'static [LeCodeProfile] {
+    PROFILES.get_or_init(load_profiles)
+}
+
+pub fn latest_build() -> u16 {
+    profiles().last().map(|v| v.build).unwrap_or_default()
+}
+
+/// Newest profile not newer than `build`, or the oldest one for older builds
+pub fn profile(build: u16) -> &'static LeCodeProfile {
+    let profiles = profiles();
+    profiles.iter().rev().find(|v| v.build <= build).unwrap_or(&profiles[0])
+}
+
+impl LeCodeProfile {
+    pub fn supports(&self, key: &LparKey) -> bool {
+        self.lpar_keys.iter().any(|v| v == key.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::{build::FIRST_CUSTOM_SLOT, lpar::KEYS};
+
+    #[test]
+    fn embedded_table() {
+        let profiles = load_profiles();
+        assert!(!profiles.is_empty());
+        assert!(profiles.windows(2).all(|v| v[0].build < v[1].build));
+        for profile in &profiles {
+            assert!(!profile.source.is_empty(), "{} has no source", profile.name);
+            assert!(profile.layout.lpar_size >= 0x3C, "{} has a short LPAR", profile.name);
+            assert!(profile.limits.slots > FIRST_CUSTOM_SLOT, "{} has no custom slots", profile.name);
+            assert!(profile.limits.racing_cups * 4 <= profile.limits.slots, "{} has more cups than slots", profile.name);
+            for (i, key) in profile.lpar_keys.iter().enumerate() {
+                assert!(KEYS.iter().any(|v| v.name == key), "{} has unknown key {}", profile.name, key);
+                assert!(!profile.lpar_keys[..i].contains(key), "{} lists {} twice", profile.name, key);
+            }
+        }
+        for key in KEYS {
+            assert!(profiles.iter().any(|v| v.supports(key)), "No build reads {}", key.name);
+        }
+    }
+
+    #[test]
+    fn unknown_builds() {
+        let latest = latest_build();
+        assert_eq!(profile(0).build, profiles()[0].build);
+        assert_eq!(profile(latest + 1).build, latest);
+    }
+}
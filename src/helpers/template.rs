@@ -0,0 +1,168 @@
+use egui::{text::LayoutJob, Color32, FontId, TextFormat};
+
+use crate::apps::{tracks::Track, Language};
+
+/// Template of new projects, like `Wii Rainbow Road v1.2`
+pub const DEFAULT_TEMPLATE: &'static str = "{prefix} {name} {version}";
+/// Fields of `Track` which can be placed in a template
+pub const FIELDS: [&'static str; 4] = ["prefix", "name", "version", "author"];
+
+/// Start of an escape sequence in BMG text
+const ESCAPE: char = '\u{1A}';
+/// Size of the colour escape in bytes, in the high byte, and its group
+const COLOUR_HEAD: [char; 2] = ['\u{0800}', '\u{0001}'];
+/// Colour value which goes back to the default colour
+const COLOUR_OFF: u16 = 0xFFFF;
+
+/// Colour of the game's BMG colour escape
+pub struct BmgColour {
+    pub name: &'static str,
+    pub value: u16,
+    /// How the colour roughly looks in the game, for the preview
+    pub preview: Color32,
+}
+
+pub const COLOURS: [BmgColour; 7] = [
+    BmgColour { name: "red", value: 0x0040, preview: Color32::from_rgb(255, 64, 64) },
+    BmgColour { name: "orange", value: 0x0004, preview: Color32::from_rgb(255, 160, 32) },
+    BmgColour { name: "yellow", value: 0x0000, preview: Color32::from_rgb(255, 232, 0) },
+    BmgColour { name: "green", value: 0x0060, preview: Color32::from_rgb(64, 208, 64) },
+    BmgColour { name: "blue", value: 0x0050, preview: Color32::from_rgb(64, 128, 255) },
+    BmgColour { name: "white", value: 0x0070, preview: Color32::WHITE },
+    BmgColour { name: "off", value: COLOUR_OFF, preview: Color32::LIGHT_GRAY },
+];
+
+/// Name template over the fields of `Track`, like `{red}{prefix}{off} {name} ({author})`.
+/// Fields are in `FIELDS` and colours in `COLOURS`.
+/// Brackets left empty by an empty field are removed, and spaces are collapsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameTemplate(pub String);
+
+enum Token<'a> {
+    Text(&'a str),
+    Field(&'a str),
+    Colour(&'static BmgColour),
+}
+
+impl Default for NameTemplate {
+    fn default() -> Self {
+        Self(DEFAULT_TEMPLATE.to_string())
+    }
+}
+
+impl NameTemplate {
+    /// Tokens which aren't a field or a colour
+    pub fn check(&self) -> Result<(), String> {
+        let unknown: Vec<String> = self
+            .tokens()
+            .into_iter()
+            .filter_map(|v| match v {
+                Err(name) => Some(format!("{{{}}}", name)),
+                Ok(_) => None,
+            })
+            .collect();
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Unknown in the name template: {}", unknown.join(", ")))
+        }
+    }
+
+    /// Name of `track` in `language`, or the default name, for BMG files, with the colour escapes
+    pub fn to_bmg(&self, track: &Track, language: Option<Language>) -> String {
+        let mut s = String::new();
+        for token in self.tokens().into_iter().flatten() {
+            match token {
+                Token::Text(v) => s.push_str(v),
+                Token::Field(v) => s.push_str(field(track, v, language)),
+                Token::Colour(v) => {
+                    s.push(ESCAPE);
+                    s.extend(COLOUR_HEAD);
+                    // Colour values are never surrogates
+                    s.push(char::from_u32(v.value as u32).unwrap_or_default());
+                }
+            }
+        }
+        tidy(&s)
+    }
+
+    /// Name of `track` in `language`, or the default name, without colours, as in definition files
+    pub fn to_plain(&self, track: &Track, language: Option<Language>) -> String {
+        strip_colours(&self.to_bmg(track, language))
+    }
+
+    /// Name of `track` in `language`, or the default name, as it roughly looks in the game
+    pub fn preview(&self, track: &Track, language: Option<Language>, font: FontId, default: Color32) -> LayoutJob {
+        let mut job = LayoutJob::default();
+        let mut colour = default;
+        for (i, part) in self.to_bmg(track, language).split(ESCAPE).enumerate() {
+            let mut chars = part.chars();
+            if i > 0 {
+                let value = chars.nth(2).map_or(COLOUR_OFF, |v| v as u16);
+                colour = match COLOURS.iter().find(|v| v.value == value) {
+                    Some(v) if v.value != COLOUR_OFF => v.preview,
+                    _ => default,
+                };
+            }
+            job.append(chars.as_str(), 0.0, TextFormat::simple(font.clone(), colour));
+        }
+        job
+    }
+
+    /// Text, fields and colours in the order of the template, or the name in unknown braces
+    fn tokens(&self) -> Vec<Result<Token<'_>, &str>> {
+        let mut tokens = vec![];
+        let mut rest = self.0.as_str();
+        while let Some(open) = rest.find('{') {
+            let close = match rest[open..].find('}') {
+                Some(v) => open + v,
+                None => break,
+            };
+            tokens.push(Ok(Token::Text(&rest[..open])));
+            let name = &rest[open + 1..close];
+            tokens.push(if FIELDS.contains(&name) {
+                Ok(Token::Field(name))
+            } else if let Some(v) = COLOURS.iter().find(|v| v.name == name) {
+                Ok(Token::Colour(v))
+            } else {
+                Err(name)
+            });
+            rest = &rest[close + 1..];
+        }
+        tokens.push(Ok(Token::Text(rest)));
+        tokens
+    }
+}
+
+fn field<'a>(track: &'a Track, name: &str, language: Option<Language>) -> &'a str {
+    match name {
+        "prefix" => &track.prefix,
+        "name" => language.map_or(track.name.as_str(), |v| track.name_in(v)),
+        "version" => &track.version,
+        "author" => &track.author,
+        _ => "",
+    }
+}
+
+/// Remove `()` and `[]` and collapse the spaces around them
+fn tidy(s: &str) -> String {
+    let mut s = s.to_string();
+    for empty in ["()", "[]"] {
+        s = s.replace(empty, "");
+    }
+    s.split(' ').filter(|v| !v.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+/// BMG text without the colour escapes
+pub fn strip_colours(s: &str) -> String {
+    let mut plain = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == ESCAPE {
+            chars.nth(2);
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
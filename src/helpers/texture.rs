@@ -0,0 +1,341 @@
+use image::RgbaImage;
+
+const TPL_MAGIC: u32 = 0x0020AF30;
+const TPL_IMAGE_TABLE: u32 = 0x0C;
+const TPL_IMAGE_HEADER: u32 = 0x14;
+const TPL_DATA: u32 = 0x40;
+/// Alpha at or above this is stored as opaque
+const RGB5A3_OPAQUE: u8 = 0xE0;
+/// Alpha below this becomes the transparent colour in CMPR
+const CMPR_TRANSPARENT: u8 = 0x80;
+
+/// Texture formats of the Wii GPU used by the cup icons in the menu
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureFormat {
+    /// 4 bits per pixel with DXT1 compression, as the original cup icons
+    Cmpr,
+    /// 16 bits per pixel, RGB555 or ARGB3444 for translucent pixels
+    Rgb5a3,
+}
+
+impl TextureFormat {
+    pub const VALUES: [Self; 2] = [TextureFormat::Cmpr, TextureFormat::Rgb5a3];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TextureFormat::Cmpr => "CMPR",
+            TextureFormat::Rgb5a3 => "RGB5A3",
+        }
+    }
+
+    /// Format ID in TPL and TEX0 headers
+    pub fn id(&self) -> u32 {
+        match self {
+            TextureFormat::Cmpr => 0x0E,
+            TextureFormat::Rgb5a3 => 0x05,
+        }
+    }
+
+    /// Width and height of one block in the texture data
+    fn block_size(&self) -> (u32, u32) {
+        match self {
+            TextureFormat::Cmpr => (8, 8),
+            TextureFormat::Rgb5a3 => (4, 4),
+        }
+    }
+}
+
+/// Encode into the tiled texture data of `format`.
+/// The image is padded with transparent pixels to the block size.
+pub fn encode_texture(image: &RgbaImage, format: TextureFormat) -> Vec<u8> {
+    let (bw, bh) = format.block_size();
+    let mut data: Vec<u8> = vec![];
+
+    for by in (0..image.height()).step_by(bh as usize) {
+        for bx in (0..image.width()).step_by(bw as usize) {
+            match format {
+                TextureFormat::Cmpr => {
+                    for (sx, sy) in [(0, 0), (4, 0), (0, 4), (4, 4)] {
+                        let mut block = [[0u8; 4]; 16];
+                        for i in 0..16 {
+                            block[i] = pixel(image, bx + sx + (i as u32 % 4), by + sy + (i as u32 / 4));
+                        }
+                        data.extend_from_slice(&encode_dxt1(&block));
+                    }
+                }
+                TextureFormat::Rgb5a3 => {
+                    for y in by..by + bh {
+                        for x in bx..bx + bw {
+                            data.extend_from_slice(&encode_rgb5a3(pixel(image, x, y)).to_be_bytes());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    data
+}
+
+/// Decode the tiled texture data of `format`, as the console would show it
+pub fn decode_texture(data: &[u8], width: u32, height: u32, format: TextureFormat) -> RgbaImage {
+    let (bw, bh) = format.block_size();
+    let mut image = RgbaImage::new(width, height);
+    let mut chunks = data.chunks_exact(8);
+    let put = |image: &mut RgbaImage, x: u32, y: u32, p: [u8; 4]| {
+        if x < width && y < height {
+            image.put_pixel(x, y, image::Rgba(p));
+        }
+    };
+
+    for by in (0..height).step_by(bh as usize) {
+        for bx in (0..width).step_by(bw as usize) {
+            match format {
+                TextureFormat::Cmpr => {
+                    for (sx, sy) in [(0, 0), (4, 0), (0, 4), (4, 4)] {
+                        let block = match chunks.next() {
+                            Some(v) => decode_dxt1(v),
+                            None => return image,
+                        };
+                        for i in 0..16 {
+                            put(&mut image, bx + sx + (i as u32 % 4), by + sy + (i as u32 / 4), block[i]);
+                        }
+                    }
+                }
+                TextureFormat::Rgb5a3 => {
+                    // 4x4 pixels of 2 bytes are 4 chunks of 8 bytes
+                    for row in 0..bh {
+                        let chunk = match chunks.next() {
+                            Some(v) => v,
+                            None => return image,
+                        };
+                        for i in 0..bw {
+                            let v = as_u16_be(&chunk[(i * 2) as usize..]);
+                            put(&mut image, bx + i, by + row, decode_rgb5a3(v));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    image
+}
+
+/// The image after a round trip through `format`
+pub fn quantize(image: &RgbaImage, format: TextureFormat) -> RgbaImage {
+    decode_texture(&encode_texture(image, format), image.width(), image.height(), format)
+}
+
+/// TPL file holding a single texture
+pub fn encode_tpl(image: &RgbaImage, format: TextureFormat) -> Vec<u8> {
+    let mut tpl: Vec<u8> = vec![];
+    tpl.extend_from_slice(&TPL_MAGIC.to_be_bytes());
+    tpl.extend_from_slice(&1u32.to_be_bytes());
+    tpl.extend_from_slice(&TPL_IMAGE_TABLE.to_be_bytes());
+
+    // Image table: image header and palette header (none)
+    tpl.extend_from_slice(&TPL_IMAGE_HEADER.to_be_bytes());
+    tpl.extend_from_slice(&0u32.to_be_bytes());
+
+    // Image header
+    tpl.extend_from_slice(&(image.height() as u16).to_be_bytes());
+    tpl.extend_from_slice(&(image.width() as u16).to_be_bytes());
+    tpl.extend_from_slice(&format.id().to_be_bytes());
+    tpl.extend_from_slice(&TPL_DATA.to_be_bytes());
+    // Wrap S/T: clamp
+    tpl.extend_from_slice(&0u32.to_be_bytes());
+    tpl.extend_from_slice(&0u32.to_be_bytes());
+    // Min/mag filter: linear
+    tpl.extend_from_slice(&1u32.to_be_bytes());
+    tpl.extend_from_slice(&1u32.to_be_bytes());
+    // LOD bias, edge LOD, min LOD, max LOD, unpacked
+    tpl.extend_from_slice(&0f32.to_be_bytes());
+    tpl.extend_from_slice(&[0, 0, 0, 0]);
+
+    tpl.resize(TPL_DATA as usize, 0);
+    tpl.append(&mut encode_texture(image, format));
+    tpl
+}
+
+fn pixel(image: &RgbaImage, x: u32, y: u32) -> [u8; 4] {
+    if x < image.width() && y < image.height() {
+        image.get_pixel(x, y).0
+    } else {
+        [0, 0, 0, 0]
+    }
+}
+
+fn encode_rgb5a3(p: [u8; 4]) -> u16 {
+    let [r, g, b, a] = p.map(|v| v as u16);
+    if p[3] >= RGB5A3_OPAQUE {
+        0x8000 | ((r >> 3) << 10) | ((g >> 3) << 5) | (b >> 3)
+    } else {
+        ((a >> 5) << 12) | ((r >> 4) << 8) | ((g >> 4) << 4) | (b >> 4)
+    }
+}
+
+fn decode_rgb5a3(v: u16) -> [u8; 4] {
+    if v & 0x8000 != 0 {
+        let c = |shift: u16| {
+            let c = ((v >> shift) & 0x1F) as u8;
+            (c << 3) | (c >> 2)
+        };
+        [c(10), c(5), c(0), 0xFF]
+    } else {
+        let c = |shift: u16| {
+            let c = ((v >> shift) & 0x0F) as u8;
+            (c << 4) | c
+        };
+        let a = ((v >> 12) & 0x07) as u8;
+        [c(8), c(4), c(0), (a << 5) | (a << 2) | (a >> 1)]
+    }
+}
+
+fn to_rgb565(p: [u8; 4]) -> u16 {
+    ((p[0] as u16 >> 3) << 11) | ((p[1] as u16 >> 2) << 5) | (p[2] as u16 >> 3)
+}
+
+fn from_rgb565(v: u16) -> [u8; 4] {
+    let r = ((v >> 11) & 0x1F) as u8;
+    let g = ((v >> 5) & 0x3F) as u8;
+    let b = (v & 0x1F) as u8;
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2), 0xFF]
+}
+
+/// Colours of a DXT1 block. The block has a transparent colour when `c0 <= c1`.
+fn dxt1_palette(c0: u16, c1: u16) -> [[u8; 4]; 4] {
+    let a = from_rgb565(c0);
+    let b = from_rgb565(c1);
+    let mix = |wa: u32, wb: u32| {
+        let mut p = [0xFFu8; 4];
+        for i in 0..3 {
+            p[i] = ((a[i] as u32 * wa + b[i] as u32 * wb) / (wa + wb)) as u8;
+        }
+        p
+    };
+    if c0 > c1 {
+        [a, b, mix(2, 1), mix(1, 2)]
+    } else {
+        [a, b, mix(1, 1), [0, 0, 0, 0]]
+    }
+}
+
+fn distance(a: [u8; 4], b: [u8; 4]) -> u32 {
+    (0..3).map(|i| (a[i] as i32 - b[i] as i32).pow(2) as u32).sum()
+}
+
+/// Encode 4x4 pixels, using the two most distant colours as the end points
+fn encode_dxt1(block: &[[u8; 4]; 16]) -> [u8; 8] {
+    let opaque: Vec<[u8; 4]> = block.iter().filter(|p| p[3] >= CMPR_TRANSPARENT).copied().collect();
+    if opaque.is_empty() {
+        return [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF];
+    }
+    let transparent = opaque.len() < block.len();
+
+    let (mut a, mut b) = (opaque[0], opaque[0]);
+    let mut farthest = 0;
+    for (i, p) in opaque.iter().enumerate() {
+        for q in &opaque[i + 1..] {
+            let d = distance(*p, *q);
+            if d > farthest {
+                farthest = d;
+                (a, b) = (*p, *q);
+            }
+        }
+    }
+
+    let (mut c0, mut c1) = (to_rgb565(a), to_rgb565(b));
+    if transparent == (c0 > c1) {
+        std::mem::swap(&mut c0, &mut c1);
+    }
+    let palette = dxt1_palette(c0, c1);
+    let colors = if c0 > c1 { 4 } else { 3 };
+
+    let mut out = [0u8; 8];
+    out[0..2].copy_from_slice(&c0.to_be_bytes());
+    out[2..4].copy_from_slice(&c1.to_be_bytes());
+    for (i, p) in block.iter().enumerate() {
+        let index = if p[3] < CMPR_TRANSPARENT {
+            3
+        } else {
+            (0..colors).min_by_key(|&n| distance(*p, palette[n])).unwrap()
+        };
+        out[4 + i / 4] |= (index as u8) << (6 - (i % 4) * 2);
+    }
+    out
+}
+
+fn decode_dxt1(data: &[u8]) -> [[u8; 4]; 16] {
+    let palette = dxt1_palette(as_u16_be(&data[0..2]), as_u16_be(&data[2..4]));
+    let mut block = [[0u8; 4]; 16];
+    for i in 0..16 {
+        let index = (data[4 + i / 4] >> (6 - (i % 4) * 2)) & 0x03;
+        block[i] = palette[index as usize];
+    }
+    block
+}
+
+fn as_u16_be(array: &[u8]) -> u16 {
+    ((array[0] as u16) << 8) + ((array[1] as u16) << 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    const RED: [u8; 4] = [0xFF, 0, 0, 0xFF];
+    const BLUE: [u8; 4] = [0, 0, 0xFF, 0xFF];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    /// Red on the left, blue on the right and a transparent row at the bottom
+    fn sample(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| match (x < width / 2, y + 1 < height) {
+            (_, false) => Rgba(CLEAR),
+            (true, true) => Rgba(RED),
+            (false, true) => Rgba(BLUE),
+        })
+    }
+
+    #[test]
+    fn rgb5a3_pixels() {
+        assert_eq!(encode_rgb5a3(RED), 0xFC00);
+        assert_eq!(decode_rgb5a3(0xFC00), RED);
+        assert_eq!(encode_rgb5a3([0xFF, 0xFF, 0xFF, 0x80]), 0x4FFF);
+        assert_eq!(decode_rgb5a3(0x4FFF), [0xFF, 0xFF, 0xFF, 0x92]);
+        assert_eq!(decode_rgb5a3(encode_rgb5a3(CLEAR)), CLEAR);
+    }
+
+    #[test]
+    fn round_trip() {
+        let image = sample(16, 8);
+        for format in TextureFormat::VALUES {
+            assert_eq!(quantize(&image, format), image, "{}", format.as_str());
+        }
+    }
+
+    #[test]
+    fn pad_to_blocks() {
+        let image = sample(10, 6);
+        // CMPR: 2x1 blocks of 8x8, RGB5A3: 3x2 blocks of 4x4, 32 bytes each
+        assert_eq!(encode_texture(&image, TextureFormat::Cmpr).len(), 2 * 32);
+        assert_eq!(encode_texture(&image, TextureFormat::Rgb5a3).len(), 6 * 32);
+        for format in TextureFormat::VALUES {
+            assert_eq!(quantize(&image, format), image, "{}", format.as_str());
+        }
+    }
+
+    #[test]
+    fn tpl_header() {
+        let image = sample(16, 8);
+        let tpl = encode_tpl(&image, TextureFormat::Cmpr);
+        assert_eq!(as_u16_be(&tpl[0..2]), (TPL_MAGIC >> 16) as u16);
+        assert_eq!(as_u16_be(&tpl[0x14..0x16]), 8);
+        assert_eq!(as_u16_be(&tpl[0x16..0x18]), 16);
+        assert_eq!(tpl[0x1B], TextureFormat::Cmpr.id() as u8);
+        let data = &tpl[TPL_DATA as usize..];
+        assert_eq!(data, encode_texture(&image, TextureFormat::Cmpr));
+        assert_eq!(decode_texture(data, 16, 8, TextureFormat::Cmpr), image);
+    }
+}
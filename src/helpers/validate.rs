@@ -0,0 +1,280 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    apps::{
+        tracks::{Cup, CupList},
+        Region,
+    },
+    Distro,
+};
+
+use super::{build::code_problem, profile::profile};
+
+/// Rules run by `Distro::validate`, in the order of the results
+const RULES: [fn(&Distro, &mut Vec<Problem>); 9] = [
+    check_cup_limits,
+    check_cup_names,
+    check_cup_icons,
+    check_track_names,
+    check_track_files,
+    check_wiimm_cup,
+    check_engine_probs,
+    check_cheat_codes,
+    check_region_codes,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The distribution can't be built or doesn't work
+    Error,
+    /// Probably not intended
+    Warning,
+    Info,
+}
+
+impl Severity {
+    pub const VALUES: [Self; 3] = [Severity::Error, Severity::Warning, Severity::Info];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "Errors",
+            Severity::Warning => "Warnings",
+            Severity::Info => "Info",
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Severity::Error => "⛔",
+            Severity::Warning => "⚠",
+            Severity::Info => "ℹ",
+        }
+    }
+}
+
+/// Where the problem can be fixed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Location {
+    Cup(CupList, usize),
+    /// Cup index and position in the cup
+    Track(CupList, usize, usize),
+    Settings,
+    Code(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub severity: Severity,
+    pub message: String,
+    pub location: Option<Location>,
+}
+
+impl Problem {
+    fn new(severity: Severity, message: String, location: Option<Location>) -> Self {
+        Self {
+            severity,
+            message,
+            location,
+        }
+    }
+}
+
+/// Problems of the given severity, for messages like "2 errors"
+pub fn count(problems: &[Problem], severity: Severity) -> usize {
+    problems.iter().filter(|v| v.severity == severity).count()
+}
+
+impl Distro {
+    /// Run all rules over the project, most severe problems first
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut problems: Vec<Problem> = vec![];
+        for rule in RULES {
+            rule(self, &mut problems);
+        }
+        problems.sort_by_key(|v| v.severity);
+        problems
+    }
+
+    /// Select what `location` points to in the editors
+    pub fn jump_to(&mut self, location: Location) {
+        let editor = &mut self.tracks.editor;
+        match location {
+            Location::Cup(list, cup) => editor.show_cup(list, cup, None),
+            Location::Track(list, cup, track) => editor.show_cup(list, cup, Some(track)),
+            // The LPAR panel is always shown
+            Location::Settings => {}
+            Location::Code(n) => {
+                if n < self.codes.codes.len() {
+                    self.codes.selected_code = n;
+                }
+            }
+        }
+    }
+}
+
+/// Every cup of both lists with its location
+fn cups(d: &Distro) -> impl Iterator<Item = (CupList, usize, &Cup)> {
+    let editor = &d.tracks.editor;
+    let racing = editor.cups.iter().enumerate().map(|(i, v)| (CupList::Racing, i, v));
+    let battle = editor.battle_cups.iter().enumerate().map(|(i, v)| (CupList::Battle, i, v));
+    racing.chain(battle)
+}
+
+fn check_cup_limits(d: &Distro, problems: &mut Vec<Problem>) {
+    for message in d.tracks.editor.check_limits(&profile(d.settings.lecode_build).limits) {
+        problems.push(Problem::new(Severity::Error, message, None));
+    }
+}
+
+fn check_cup_names(d: &Distro, problems: &mut Vec<Problem>) {
+    for (list, i, cup) in cups(d) {
+        if cup.name.trim().is_empty() {
+            problems.push(Problem::new(
+                Severity::Warning,
+                format!("{} cup {} has no name.", list.as_str(), i + 1),
+                Some(Location::Cup(list, i)),
+            ));
+        }
+    }
+}
+
+fn check_cup_icons(d: &Distro, problems: &mut Vec<Problem>) {
+    // LE-CODE shows icons only for racing cups
+    for (list, i, cup) in cups(d).filter(|v| v.0 == CupList::Racing) {
+        if cup.icon.image.is_empty() {
+            problems.push(Problem::new(
+                Severity::Warning,
+                format!("{} has no icon.", cup.name),
+                Some(Location::Cup(list, i)),
+            ));
+        }
+    }
+}
+
+fn check_track_names(d: &Distro, problems: &mut Vec<Problem>) {
+    if let Err(e) = d.tracks.editor.template.check() {
+        problems.push(Problem::new(Severity::Warning, e, None));
+    }
+    for (list, i, cup) in cups(d) {
+        for (n, track) in cup.trackset.iter().enumerate() {
+            if track.name.trim().is_empty() {
+                problems.push(Problem::new(
+                    Severity::Warning,
+                    format!("Track {} of {} has no name.", n + 1, cup.name),
+                    Some(Location::Track(list, i, n)),
+                ));
+            }
+        }
+    }
+}
+
+fn check_track_files(d: &Distro, problems: &mut Vec<Problem>) {
+    let mut used: BTreeMap<&str, usize> = BTreeMap::new();
+    for (list, i, cup) in cups(d) {
+        for (n, track) in cup.trackset.iter().enumerate() {
+            let location = Some(Location::Track(list, i, n));
+            let name = if track.name.is_empty() { "(no name)" } else { &track.name };
+            if track.filename.is_empty() {
+                problems.push(Problem::new(
+                    Severity::Error,
+                    format!("{} in {} has no SZS file.", name, cup.name),
+                    location,
+                ));
+            } else if !Path::new(&track.filename).is_file() {
+                problems.push(Problem::new(
+                    Severity::Error,
+                    format!("The SZS file of {} is not found: {}", name, track.filename),
+                    location,
+                ));
+            } else {
+                *used.entry(&track.filename).or_default() += 1;
+            }
+        }
+    }
+    for (file, times) in used.into_iter().filter(|v| v.1 > 1) {
+        problems.push(Problem::new(
+            Severity::Info,
+            format!("{} is used by {} tracks.", file, times),
+            None,
+        ));
+    }
+}
+
+fn check_wiimm_cup(d: &Distro, problems: &mut Vec<Problem>) {
+    let editor = &d.tracks.editor;
+    if !editor.mode.wiimm_cup {
+        return;
+    }
+    for slot in editor.wiimm.slots.iter().filter(|v| v.shown) {
+        if editor.random_pool(slot.pool).is_empty() {
+            problems.push(Problem::new(
+                Severity::Warning,
+                format!("{} of the Wiimm cup has no tracks to pick from.", slot.name()),
+                None,
+            ));
+        }
+    }
+}
+
+fn check_engine_probs(d: &Distro, problems: &mut Vec<Problem>) {
+    let p = &d.settings.engine_probs;
+    if [p.low, p.mid, p.high].iter().any(|v| *v < 0) {
+        problems.push(Problem::new(
+            Severity::Error,
+            "Engine probabilities can't be negative.".to_string(),
+            Some(Location::Settings),
+        ));
+    }
+    let total = p.low + p.mid + p.high;
+    if total != 100 {
+        problems.push(Problem::new(
+            Severity::Error,
+            format!("Engine probabilities add up to {} instead of 100.", total),
+            Some(Location::Settings),
+        ));
+    }
+}
+
+fn check_cheat_codes(d: &Distro, problems: &mut Vec<Problem>) {
+    if !d.codes.enabled {
+        return;
+    }
+    for (i, code) in d.codes.codes.iter().enumerate() {
+        // Codes with a body which can't be built, in any region
+        let broken = Region::VALUES
+            .into_iter()
+            .filter(|v| !code.body(*v).trim().is_empty())
+            .find_map(|v| code_problem(code, v));
+        if let Some(problem) = broken {
+            let name = if code.name.is_empty() { "(no name)" } else { &code.name };
+            problems.push(Problem::new(
+                Severity::Error,
+                format!("Cheat code {} {}.", name, problem),
+                Some(Location::Code(i)),
+            ));
+        }
+    }
+}
+
+fn check_region_codes(d: &Distro, problems: &mut Vec<Problem>) {
+    if !d.codes.enabled {
+        return;
+    }
+    for (i, code) in d.codes.codes.iter().enumerate() {
+        let missing: Vec<&str> = d
+            .settings
+            .regions
+            .iter()
+            .filter(|v| code.body(**v).trim().is_empty() && code_problem(code, **v).is_some())
+            .map(|v| v.as_str())
+            .collect();
+        if !missing.is_empty() {
+            let name = if code.name.is_empty() { "(no name)" } else { &code.name };
+            problems.push(Problem::new(
+                Severity::Warning,
+                format!("Cheat code {} has no code for {} and is left out there.", name, missing.join(", ")),
+                Some(Location::Code(i)),
+            ));
+        }
+    }
+}
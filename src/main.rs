@@ -5,15 +5,21 @@
 mod apps;
 mod helpers;
 
-use crate::apps::{CheatCodeApp, MainView, SettingApp, TrackDefApp};
+use crate::apps::{CheatCodeApp, MainView, PreferenceApp, ProblemsApp, Region, SettingApp, SourceApp, TrackDefApp};
+use crate::helpers::file::{file_dialog, find_recovery, remember_dir, DIALOG_BUILD, DIALOG_PROJECT, DIALOG_TRACK};
+use crate::helpers::history::History;
+use crate::helpers::validate::{count, Severity};
 use eframe::emath::Align;
 use eframe::{App, Frame};
-use egui::{Context, Layout};
+use egui::{Context, Key, Layout, Modifiers, Visuals};
 use std::path::PathBuf;
-use std::process::exit;
+use std::time::{Duration, Instant};
 
 const APP_NAME: &'static str = "mkw-distro-tool";
 const APP_VERSION: &'static str = "v0.1.0";
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+/// How often the open problems panel runs the validation again
+const VALIDATE_INTERVAL: Duration = Duration::from_secs(1);
 
 #[cfg(debug_assertions)]
 fn sprint_version() -> String {
@@ -26,28 +32,81 @@ fn sprint_version() -> String {
 }
 
 fn main() {
-    // let _track_bytes: &[u8] = include_bytes!("../res/tracks.bin") as &[u8];
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() == 3 && args[1] == "validate" {
+        attach_console();
+        std::process::exit(validate_project(&PathBuf::from(&args[2])));
+    }
 
     let options = eframe::NativeOptions {
         initial_window_size: Some([1280.0, 640.0].into()),
         min_window_size: Some([1280.0, 640.0].into()),
         ..eframe::NativeOptions::default()
     };
-    eframe::run_native(APP_NAME, options, Box::new(|_| Box::new(Distro::default())));
+    eframe::run_native(APP_NAME, options, Box::new(|cc| Box::new(Distro::new(cc))));
+}
+
+/// `validate <project>` prints the problems of a project without opening the window.
+/// Exits with 1 if there are errors, or 2 if the project can't be opened.
+fn validate_project(path: &PathBuf) -> i32 {
+    let distro = match Distro::default().decode(path) {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 2;
+        }
+    };
+    let problems = distro.validate();
+    for problem in &problems {
+        println!("{}: {}", format!("{:?}", problem.severity).to_lowercase(), problem.message);
+    }
+    if count(&problems, Severity::Error) > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Release builds are GUI programs on Windows and have no console of their own,
+/// so print into the console of the shell which started the program
+#[cfg(windows)]
+fn attach_console() {
+    /// `AttachConsole` of the parent process, from `wincon.h`
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    // Without a parent console, like when started from Explorer, there is nowhere to print
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+/// Action waiting for the user to choose Save / Discard / Cancel
+#[derive(Clone, PartialEq)]
+enum PendingAction {
+    Close,
+    New,
+    Open,
+    OpenRecent(PathBuf),
 }
 
 struct Distro {
-    /// To allow displaying closing confirmation
-    close_confirm_dialog: bool,
+    /// To allow displaying the unsaved changes confirmation for the action
+    pending_action: Option<PendingAction>,
+    /// Set after the user has confirmed closing, to let `on_close_event` pass
+    allow_close: bool,
     /// To allow displaying error confirmation.
     /// Messages are determined in Self::err_msg
     confirm_dialog: bool,
-    /// (Currently keeps in true) Check the application closable immediately.
-    /// Generally the closing confirmation dialog will display if it's true
-    disallow_to_close: bool,
-    _disallow_to_ignore_change: bool,
+    /// Title of the message dialog
+    msg_title: &'static str,
     /// Error message
-    err_msg: &'static str,
+    err_msg: String,
     /// Path to loaded file
     path: Option<PathBuf>,
     //-- Any apps
@@ -57,20 +116,44 @@ struct Distro {
     settings: SettingApp,
     /// Cheat code definition
     codes: CheatCodeApp,
+    /// Nintendo content location
+    source: SourceApp,
+    /// Results of the validation
+    problems: ProblemsApp,
+    /// Undo/redo stack and unsaved changes state
+    history: History,
+    /// Time of the last autosave into the recovery file
+    last_autosave: Instant,
+    /// Whether the last autosave failed, to show a failure only once until it works again
+    autosave_failed: bool,
+    /// Recovery file and its project found on startup, to offer restoring
+    recovery: Option<(PathBuf, Option<PathBuf>)>,
+    /// Kept between runs
+    preferences: PreferenceApp,
 }
 
 impl Default for Distro {
     fn default() -> Self {
+        let tracks = TrackDefApp::default();
+        let settings = SettingApp::default();
+        let codes = CheatCodeApp::default();
         Self {
-            close_confirm_dialog: false,
-            disallow_to_close: false,
-            tracks: Default::default(),
-            settings: Default::default(),
-            codes: Default::default(),
+            pending_action: None,
+            allow_close: false,
+            history: History::new(&tracks, &settings, &codes),
+            tracks,
+            settings,
+            codes,
+            source: Default::default(),
+            problems: Default::default(),
             path: None,
             confirm_dialog: false,
-            _disallow_to_ignore_change: false,
-            err_msg: "",
+            msg_title: "Error",
+            err_msg: String::new(),
+            last_autosave: Instant::now(),
+            autosave_failed: false,
+            recovery: find_recovery(),
+            preferences: Default::default(),
         }
     }
 }
@@ -79,12 +162,57 @@ impl App for Distro {
     fn update(&mut self, ctx: &Context, frame: &mut Frame) {
         frame.set_window_title(&self.gen_title());
         self.view_top_menu(ctx, frame);
+        // Panels shown earlier take the whole edge of the window
+        if self.problems.open && self.problems.checked.map_or(true, |v| v.elapsed() >= VALIDATE_INTERVAL) {
+            self.problems.problems = self.validate();
+            self.problems.checked = Some(Instant::now());
+        }
+        self.problems.ui(ctx);
+        if let Some(location) = self.problems.jump.take() {
+            self.jump_to(location);
+        }
         self.settings.ui(ctx);
         self.codes.ui(ctx);
+        self.tracks.lecode_build = self.settings.lecode_build;
         self.tracks.ui(ctx);
+        self.source.ui(ctx);
+        self.preferences.ui(ctx);
+        self.preferences.dark_mode = ctx.style().visuals.dark_mode;
 
-        if self.close_confirm_dialog {
-            self.close_confirm(ctx, frame);
+        // Text fields handle Ctrl+Z by themselves while focused
+        if ctx.memory().focus().is_none() {
+            if ctx.input_mut().consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z)
+                || ctx.input_mut().consume_key(Modifiers::COMMAND, Key::Y)
+            {
+                self.redo();
+            } else if ctx.input_mut().consume_key(Modifiers::COMMAND, Key::Z) {
+                self.undo();
+            }
+        }
+        self.history
+            .record(ctx, &self.tracks, &self.settings, &self.codes);
+
+        if self.last_autosave.elapsed() >= AUTOSAVE_INTERVAL {
+            if self.history.is_dirty() {
+                match self.autosave() {
+                    Ok(_) => self.autosave_failed = false,
+                    Err(err) if !self.autosave_failed => {
+                        self.autosave_failed = true;
+                        self.show_message("Error", format!("Autosave failed: {}", err));
+                    }
+                    Err(_) => {}
+                }
+            }
+            self.last_autosave = Instant::now();
+        }
+        ctx.request_repaint_after(AUTOSAVE_INTERVAL);
+
+        if self.recovery.is_some() {
+            self.recovery_confirm(ctx, frame);
+        }
+
+        if self.pending_action.is_some() {
+            self.unsaved_confirm(ctx, frame);
         }
 
         if self.confirm_dialog {
@@ -92,26 +220,177 @@ impl App for Distro {
         }
     }
 
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.preferences.store(storage, &self.source);
+    }
+
     fn on_close_event(&mut self) -> bool {
-        self.close_confirm_dialog = true;
-        self.disallow_to_close
+        if self.allow_close || !self.history.is_dirty() {
+            return true;
+        }
+        self.pending_action = Some(PendingAction::Close);
+        false
     }
 }
 
 impl Distro {
+    fn new(cc: &eframe::CreationContext) -> Self {
+        let preferences = PreferenceApp::load(cc.storage);
+        cc.egui_ctx.set_visuals(if preferences.dark_mode {
+            Visuals::dark()
+        } else {
+            Visuals::light()
+        });
+
+        let mut distro = Self::default();
+        preferences.apply_source(&mut distro.source);
+        distro.preferences = preferences;
+        distro.new_project();
+        distro
+    }
+
     fn gen_title(&self) -> String {
+        let modified = if self.history.is_dirty() { "*" } else { "" };
         if self.path.is_none() || self.path.as_ref().unwrap().to_str().unwrap() == "" {
-            return format!("{}", APP_NAME);
+            return format!("{}{}", modified, APP_NAME);
         }
 
         format!(
-            "{} - {}",
+            "{}{} - {}",
+            modified,
             APP_NAME,
             self.path.as_ref().unwrap().to_str().unwrap()
         )
     }
 
-    fn close_confirm(&mut self, ctx: &Context, frame: &mut Frame) {
+    fn undo(&mut self) {
+        self.history
+            .undo(&mut self.tracks, &mut self.settings, &mut self.codes);
+    }
+
+    fn redo(&mut self) {
+        self.history
+            .redo(&mut self.tracks, &mut self.settings, &mut self.codes);
+    }
+
+    /// Forget the undo history and treat the current state as saved
+    fn reset_history(&mut self) {
+        self.history = History::new(&self.tracks, &self.settings, &self.codes);
+    }
+
+    /// Run the action, or ask what to do with unsaved changes first
+    fn request(&mut self, action: PendingAction, frame: &mut Frame) {
+        if self.history.is_dirty() {
+            self.pending_action = Some(action);
+        } else {
+            self.run_action(action, frame);
+        }
+    }
+
+    fn run_action(&mut self, action: PendingAction, frame: &mut Frame) {
+        // Unsaved changes are saved or discarded at this point
+        self.remove_recovery();
+        match action {
+            PendingAction::Close => {
+                self.allow_close = true;
+                frame.close();
+            }
+            PendingAction::New => self.new_project(),
+            PendingAction::Open => {
+                if let Some(path) = file_dialog(DIALOG_PROJECT)
+                    .add_filter(".mkprj", &["mkprj"])
+                    .pick_file()
+                {
+                    remember_dir(DIALOG_PROJECT, &path);
+                    if self.open_project(&path) {
+                        self.preferences.add_recent(&path);
+                    }
+                }
+            }
+            PendingAction::OpenRecent(path) => {
+                if self.open_project(&path) {
+                    self.preferences.add_recent(&path);
+                } else {
+                    self.preferences.recent.retain(|v| *v != path);
+                }
+            }
+        }
+    }
+
+    /// Reset all editors with the defaults in the preferences
+    fn new_project(&mut self) {
+        self.settings = self.preferences.settings.clone();
+        self.tracks = Default::default();
+        self.tracks.editor.mode = self.preferences.cup_mode.clone();
+        self.codes = Default::default();
+        self.path = None;
+        self.reset_history();
+    }
+
+    /// Save to the current path, or ask for a path if there is none or `save_as` is set.
+    /// Returns false if the user cancelled or saving failed.
+    fn save(&mut self, save_as: bool) -> bool {
+        let previous_recovery = self.recovery_path();
+        if save_as || self.path.is_none() {
+            match file_dialog(DIALOG_PROJECT)
+                .add_filter(".mkprj", &["mkprj"])
+                .save_file()
+            {
+                Some(path) => {
+                    remember_dir(DIALOG_PROJECT, &path);
+                    self.path = Some(path)
+                }
+                None => return false,
+            }
+        }
+        match self.save_project(self.path.as_ref().unwrap()) {
+            Ok(_) => {
+                self.history.mark_saved();
+                self.preferences.add_recent(self.path.as_ref().unwrap());
+                if let Some(v) = previous_recovery {
+                    let _ = std::fs::remove_file(v);
+                }
+                self.remove_recovery();
+                true
+            }
+            Err(err) => {
+                self.show_message("Error", format!("Failed to save the project: {}", err));
+                false
+            }
+        }
+    }
+
+    fn recovery_confirm(&mut self, ctx: &Context, frame: &mut Frame) {
+        let (x, y) = (
+            frame.info().window_info.size.x,
+            frame.info().window_info.size.y,
+        );
+        egui::Window::new("Recovery")
+            .title_bar(true)
+            .default_width(400.0)
+            .collapsible(false)
+            .resizable(false)
+            .fixed_pos([(x / 2.0) - 200.0, y / 2.5])
+            .show(ctx, |ui| {
+                ui.label("The previous session did not exit properly. Do you want to restore the autosaved project?");
+                if let Some((_, Some(project))) = &self.recovery {
+                    ui.label(format!("Project: {}", project.to_string_lossy()));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Restore").clicked() {
+                        let (recovery, project) = self.recovery.take().unwrap();
+                        self.restore_recovery(&recovery, project);
+                    }
+                    if ui.button("Discard").clicked() {
+                        let (recovery, _) = self.recovery.take().unwrap();
+                        let _ = std::fs::remove_file(recovery);
+                        self.remove_recovery();
+                    }
+                })
+            });
+    }
+
+    fn unsaved_confirm(&mut self, ctx: &Context, frame: &mut Frame) {
         let (x, y) = (
             frame.info().window_info.size.x,
             frame.info().window_info.size.y,
@@ -121,16 +400,23 @@ impl Distro {
             .default_width(400.0)
             .collapsible(false)
             .resizable(false)
-            .fixed_pos([(x / 2.0) - 100.0, y / 2.5])
+            .fixed_pos([(x / 2.0) - 150.0, y / 2.5])
             .show(ctx, |ui| {
-                ui.label("Are you sure to close this application?");
+                ui.label("There are unsaved changes. Do you want to save them?");
                 ui.vertical_centered_justified(|ui| {
                     ui.horizontal_wrapped(|ui| {
-                        if ui.button("Yes").clicked() {
-                            exit(0);
+                        if ui.button("Save").clicked() {
+                            let action = self.pending_action.take().unwrap();
+                            if self.save(false) {
+                                self.run_action(action, frame);
+                            }
                         }
-                        if ui.button("No").clicked() {
-                            self.close_confirm_dialog = false;
+                        if ui.button("Discard").clicked() {
+                            let action = self.pending_action.take().unwrap();
+                            self.run_action(action, frame);
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.pending_action = None;
                         }
                     })
                 })
@@ -142,14 +428,14 @@ impl Distro {
             frame.info().window_info.size.x,
             frame.info().window_info.size.y,
         );
-        egui::Window::new("Error")
+        egui::Window::new(self.msg_title)
             .title_bar(true)
             .default_width(400.0)
             .collapsible(false)
             .resizable(false)
             .fixed_pos([(x / 2.0) - 200.0, y / 2.5])
             .show(ctx, |ui| {
-                ui.label(self.err_msg);
+                ui.label(&self.err_msg);
                 ui.horizontal(|ui| {
                     if ui.button("Ok").clicked() {
                         self.confirm_dialog = false;
@@ -158,6 +444,28 @@ impl Distro {
             });
     }
 
+    /// Run the validation before `action`. Shows the problems and returns false if there are errors.
+    fn validate_before(&mut self, action: &str) -> bool {
+        self.problems.problems = self.validate();
+        self.problems.checked = Some(Instant::now());
+        let errors = count(&self.problems.problems, Severity::Error);
+        if errors == 0 {
+            return true;
+        }
+        self.problems.open = true;
+        self.show_message(
+            "Error",
+            format!("{} errors must be fixed before {}. See the Problems panel.", errors, action),
+        );
+        false
+    }
+
+    fn show_message(&mut self, title: &'static str, msg: String) {
+        self.msg_title = title;
+        self.err_msg = msg;
+        self.confirm_dialog = true;
+    }
+
     fn view_top_menu(&mut self, ctx: &Context, frame: &mut Frame) {
         egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
@@ -165,62 +473,194 @@ impl Distro {
                 ui.separator();
                 ui.menu_button("File", |ui| {
                     if ui.button("New Project").clicked() {
-                        self.settings = Default::default();
-                        self.tracks = Default::default();
+                        ui.close_menu();
+                        self.request(PendingAction::New, frame);
                     }
                     if ui.button("Open Project").clicked() {
-                        if let Some(path) = rfd::FileDialog::new()
-                            .add_filter(".mkprj", &["mkprj"])
-                            .pick_file()
-                        {
-                            self.open_project(&path);
-                        }
+                        ui.close_menu();
+                        self.request(PendingAction::Open, frame);
                     }
+                    ui.add_enabled_ui(!self.preferences.recent.is_empty(), |ui| {
+                        ui.menu_button("Open Recent", |ui| {
+                            for path in self.preferences.recent.clone() {
+                                if ui.button(path.to_string_lossy()).clicked() {
+                                    ui.close_menu();
+                                    self.request(PendingAction::OpenRecent(path), frame);
+                                }
+                            }
+                        });
+                    });
                     ui.separator();
                     if ui.button("Save").clicked() {
                         println!("File:Save");
-                        if self.path.is_none() {
-                            match rfd::FileDialog::new()
-                                .add_filter(".mkprj", &["mkprj"])
-                                .save_file()
-                            {
-                                Some(path) => self.path = Some(path),
-                                None => return,
-                            }
-                        }
-                        self.save_project(self.path.as_ref().unwrap());
+                        ui.close_menu();
+                        self.save(false);
                     }
                     if ui.button("Save as new").clicked() {
-                        match rfd::FileDialog::new()
-                            .add_filter(".mkprj", &["mkprj"])
-                            .save_file()
-                        {
-                            Some(path) => self.path = Some(path),
-                            None => return,
-                        }
-                        self.save_project(self.path.as_ref().unwrap());
+                        ui.close_menu();
+                        self.save(true);
+                    }
+                    ui.separator();
+                    if ui.button("Preferences").clicked() {
+                        ui.close_menu();
+                        self.preferences.open = true;
                     }
                     ui.separator();
                     if ui.button("Exit").clicked() {
-                        self.close_confirm_dialog = true;
+                        ui.close_menu();
+                        self.request(PendingAction::Close, frame);
+                    }
+                });
+                ui.menu_button("Edit", |ui| {
+                    if ui
+                        .add_enabled(self.history.can_undo(), egui::Button::new("Undo (Ctrl+Z)"))
+                        .clicked()
+                    {
+                        self.undo();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(self.history.can_redo(), egui::Button::new("Redo (Ctrl+Y)"))
+                        .clicked()
+                    {
+                        self.redo();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Find Tracks…").on_hover_text("Search, sort and edit the tracks of all cups").clicked() {
+                        self.tracks.browser.open = true;
+                        ui.close_menu();
                     }
                 });
                 ui.menu_button("Export", |ui| {
                     ui.menu_button("Track Definition", |ui| {
                         let _ = ui.button("CT-DEF");
-                        let _ = ui.button("LE-DEF");
+                        if ui.button("LE-DEF").clicked() {
+                            ui.close_menu();
+                            if self.validate_before("exporting") {
+                                if let Some(path) = file_dialog(DIALOG_TRACK)
+                                    .add_filter("LE-DEF", &["txt"])
+                                    .set_file_name("le-def.txt")
+                                    .save_file()
+                                {
+                                    remember_dir(DIALOG_TRACK, &path);
+                                    if let Err(err) = self.tracks.editor.export_le_def(&path) {
+                                        self.show_message("Error", err);
+                                    }
+                                }
+                            }
+                        }
                     });
                     ui.menu_button("Text", |ui| {
                         let _ = ui.button("Export as BMG");
                         let _ = ui.button("Export as text file");
                     });
-                    let _ = ui.button("LE-CODE Settings");
+                    if ui.button("LE-CODE Settings").clicked() {
+                        ui.close_menu();
+                        if self.validate_before("exporting") {
+                            if let Some(path) = file_dialog(DIALOG_TRACK)
+                                .add_filter("LPAR", &["txt"])
+                                .set_file_name("lpar.txt")
+                                .save_file()
+                            {
+                                remember_dir(DIALOG_TRACK, &path);
+                                if let Err(err) = self.settings.export_lpar(&path) {
+                                    self.show_message("Error", err);
+                                }
+                            }
+                        }
+                    }
                 });
                 ui.menu_button("Import", |ui| {
-                    let _ = ui.button("Track Files");
+                    if ui
+                        .button("Track Files")
+                        .on_hover_text("Add every SZS file in a folder as new cups")
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        if let Some(path) = file_dialog(DIALOG_TRACK).pick_folder() {
+                            remember_dir(DIALOG_TRACK, &path);
+                            match self.tracks.editor.import_track_folder(&path) {
+                                Ok(v) => self.show_message("Import", v.summary()),
+                                Err(err) => self.show_message("Error", err),
+                            }
+                        }
+                    }
+                    if ui
+                        .button("Track Definition")
+                        .on_hover_text("Replace the cups with a CT-DEF or LE-DEF file")
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        if let Some(path) = file_dialog(DIALOG_TRACK)
+                            .add_filter("CT-DEF/LE-DEF", &["txt", "def"])
+                            .pick_file()
+                        {
+                            remember_dir(DIALOG_TRACK, &path);
+                            match self.tracks.editor.import_definition(&path) {
+                                Ok(v) => self.show_message("Import", v.summary()),
+                                Err(err) => self.show_message("Error", err),
+                            }
+                        }
+                    }
+                    if ui
+                        .button("LE-CODE Pack")
+                        .on_hover_text("Replace the cups and LPAR settings with a built pack")
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        if let Some(path) = file_dialog(DIALOG_TRACK)
+                            .add_filter("LE-CODE binary", &["bin"])
+                            .pick_file()
+                        {
+                            remember_dir(DIALOG_TRACK, &path);
+                            match self.import_lecode_pack(&path) {
+                                Ok(v) => self.show_message("Import", v.summary()),
+                                Err(err) => self.show_message("Error", err),
+                            }
+                        }
+                    }
                 });
                 ui.menu_button("Build", |ui| {
-                    let _ = ui.button("LE-CODE Distribution");
+                    if ui.button("Nintendo Source").clicked() {
+                        self.source.open = true;
+                        ui.close_menu();
+                    }
+                    ui.menu_button("Regions", |ui| {
+                        for region in Region::VALUES {
+                            let mut selected = self.settings.regions.contains(&region);
+                            // A build needs at least one region
+                            let last = selected && self.settings.regions.len() == 1;
+                            if ui
+                                .add_enabled(!last, egui::Checkbox::new(&mut selected, region.as_str()))
+                                .changed()
+                            {
+                                if selected {
+                                    self.settings.regions.push(region);
+                                } else {
+                                    self.settings.regions.retain(|v| *v != region);
+                                }
+                            }
+                        }
+                    });
+                    ui.separator();
+                    if ui.button("Validate Project").clicked() {
+                        ui.close_menu();
+                        self.problems.open = true;
+                        self.problems.checked = None;
+                    }
+                    if ui.button("LE-CODE Distribution").clicked() {
+                        ui.close_menu();
+                        if self.validate_before("building") {
+                            if let Some(path) = file_dialog(DIALOG_BUILD).pick_folder() {
+                                remember_dir(DIALOG_BUILD, &path);
+                                match self.build_distribution(&path) {
+                                    Ok(v) => self.show_message("Build", v.summary()),
+                                    Err(err) => self.show_message("Error", err),
+                                }
+                            }
+                        }
+                    }
                 });
                 ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                     ui.label(sprint_version());